@@ -0,0 +1,223 @@
+// Copyright 2025 Nicholas Jordan. All Rights Reserved.
+// github.com/cvusmo/lustre
+// src/block.rs
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+
+/// Numeric block id, stable for the lifetime of a registry.
+pub type BlockId = u16;
+
+/// Id of the empty block. Always registered first.
+pub const AIR: BlockId = 0;
+
+/// Gameplay and rendering properties shared by every instance of a block.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BlockProperties {
+    /// Blocks movement and is considered for collision.
+    pub solid: bool,
+    /// Lets neighbouring faces show through (glass, leaves, water).
+    pub transparent: bool,
+    /// Can be swum through.
+    pub fluid: bool,
+    /// Seconds to mine by hand; negative means unbreakable.
+    pub hardness: f32,
+    /// Linear RGB color used until textures exist.
+    pub color: [f32; 3],
+}
+
+impl Default for BlockProperties {
+    fn default() -> Self {
+        Self {
+            solid: true,
+            transparent: false,
+            fluid: false,
+            hardness: 1.0,
+            color: [1.0, 0.0, 1.0],
+        }
+    }
+}
+
+/// A registered block type.
+#[derive(Debug, Clone)]
+pub struct Block {
+    pub id: BlockId,
+    /// Namespaced name, e.g. `lustre:stone`.
+    pub name: String,
+    pub properties: BlockProperties,
+}
+
+/// Errors returned when registering blocks.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BlockRegistryError {
+    DuplicateName(String),
+    Full,
+}
+
+impl fmt::Display for BlockRegistryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BlockRegistryError::DuplicateName(name) => {
+                write!(f, "block '{}' is already registered", name)
+            }
+            BlockRegistryError::Full => write!(f, "block registry is full"),
+        }
+    }
+}
+
+impl std::error::Error for BlockRegistryError {}
+
+/// Maps block ids to names and properties.
+#[derive(Debug, Clone)]
+pub struct BlockRegistry {
+    blocks: Vec<Block>,
+    ids_by_name: HashMap<String, BlockId>,
+}
+
+impl Default for BlockRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BlockRegistry {
+    /// Creates a registry containing only `lustre:air`.
+    pub fn new() -> Self {
+        let mut registry = Self {
+            blocks: Vec::new(),
+            ids_by_name: HashMap::new(),
+        };
+        registry
+            .register(
+                "lustre:air",
+                BlockProperties {
+                    solid: false,
+                    transparent: true,
+                    hardness: 0.0,
+                    color: [0.0, 0.0, 0.0],
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        registry
+    }
+
+    /// Creates a registry with the engine's built-in blocks.
+    pub fn with_defaults() -> Self {
+        let mut registry = Self::new();
+        let builtin = [
+            ("lustre:stone", 1.5, [0.5, 0.5, 0.5]),
+            ("lustre:dirt", 0.5, [0.45, 0.3, 0.18]),
+            ("lustre:grass", 0.6, [0.3, 0.6, 0.2]),
+            ("lustre:sand", 0.5, [0.85, 0.8, 0.55]),
+            ("lustre:snow", 0.2, [0.95, 0.95, 0.97]),
+            ("lustre:bedrock", -1.0, [0.15, 0.15, 0.15]),
+        ];
+        for (name, hardness, color) in builtin {
+            registry
+                .register(
+                    name,
+                    BlockProperties {
+                        hardness,
+                        color,
+                        ..Default::default()
+                    },
+                )
+                .unwrap();
+        }
+        registry
+            .register(
+                "lustre:water",
+                BlockProperties {
+                    solid: false,
+                    transparent: true,
+                    fluid: true,
+                    hardness: -1.0,
+                    color: [0.15, 0.35, 0.8],
+                },
+            )
+            .unwrap();
+        let ores = [
+            ("lustre:coal_ore", 2.0, [0.2, 0.2, 0.22]),
+            ("lustre:iron_ore", 3.0, [0.65, 0.5, 0.4]),
+            ("lustre:gold_ore", 3.0, [0.9, 0.75, 0.2]),
+        ];
+        for (name, hardness, color) in ores {
+            registry
+                .register(
+                    name,
+                    BlockProperties {
+                        hardness,
+                        color,
+                        ..Default::default()
+                    },
+                )
+                .unwrap();
+        }
+        registry
+    }
+
+    /// Registers a new block and returns its id.
+    pub fn register(
+        &mut self,
+        name: &str,
+        properties: BlockProperties,
+    ) -> Result<BlockId, BlockRegistryError> {
+        if self.ids_by_name.contains_key(name) {
+            return Err(BlockRegistryError::DuplicateName(name.to_string()));
+        }
+        let id = BlockId::try_from(self.blocks.len()).map_err(|_| BlockRegistryError::Full)?;
+
+        self.blocks.push(Block {
+            id,
+            name: name.to_string(),
+            properties,
+        });
+        self.ids_by_name.insert(name.to_string(), id);
+        Ok(id)
+    }
+
+    pub fn get(&self, id: BlockId) -> Option<&Block> {
+        self.blocks.get(id as usize)
+    }
+
+    pub fn by_name(&self, name: &str) -> Option<&Block> {
+        self.id_of(name).and_then(|id| self.get(id))
+    }
+
+    pub fn id_of(&self, name: &str) -> Option<BlockId> {
+        self.ids_by_name.get(name).copied()
+    }
+
+    /// Properties of a block; unknown ids behave like air.
+    pub fn properties(&self, id: BlockId) -> &BlockProperties {
+        &self
+            .blocks
+            .get(id as usize)
+            .unwrap_or(&self.blocks[0])
+            .properties
+    }
+
+    pub fn is_solid(&self, id: BlockId) -> bool {
+        self.properties(id).solid
+    }
+
+    pub fn is_transparent(&self, id: BlockId) -> bool {
+        self.properties(id).transparent
+    }
+
+    pub fn len(&self) -> usize {
+        self.blocks.len()
+    }
+
+    /// Always false; the registry contains at least air.
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Block> {
+        self.blocks.iter()
+    }
+}
@@ -0,0 +1,138 @@
+// Copyright 2025 Nicholas Jordan. All Rights Reserved.
+// github.com/cvusmo/lustre
+// src/camera.rs
+
+use rapier3d::na::{Matrix4, Perspective3, Point3, Unit, UnitQuaternion, Vector3, Vector4};
+
+/// Converts OpenGL clip space (y up, depth -1..1) to Vulkan clip space (y down, depth 0..1).
+#[rustfmt::skip]
+const VULKAN_CLIP_CORRECTION: Matrix4<f32> = Matrix4::new(
+    1.0,  0.0, 0.0, 0.0,
+    0.0, -1.0, 0.0, 0.0,
+    0.0,  0.0, 0.5, 0.5,
+    0.0,  0.0, 0.0, 1.0,
+);
+
+/// A perspective camera in a right-handed world.
+///
+/// Orientation is stored as yaw (around the camera's up) and pitch (around its right axis),
+/// both in radians. Up is +Y unless changed with `set_up`, e.g. to point away from a
+/// planet's centre. With +Y up, yaw 0 looks down -Z.
+#[derive(Debug, Clone)]
+pub struct Camera {
+    pub position: Point3<f32>,
+    pub yaw: f32,
+    pub pitch: f32,
+    pub fov_y: f32,
+    pub near: f32,
+    pub far: f32,
+    // Rotates the y-up frame that yaw and pitch are measured in to the current up.
+    frame: UnitQuaternion<f32>,
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Self {
+            position: Point3::new(0.0, 0.0, 2.0),
+            yaw: 0.0,
+            pitch: 0.0,
+            fov_y: 70f32.to_radians(),
+            near: 0.1,
+            far: 1000.0,
+            frame: UnitQuaternion::identity(),
+        }
+    }
+}
+
+impl Camera {
+    /// Unit vector the camera treats as up.
+    pub fn up(&self) -> Vector3<f32> {
+        self.frame * Vector3::y()
+    }
+
+    /// Changes the camera's up direction, keeping the view as steady as possible.
+    ///
+    /// The frame is rotated by the smallest rotation between the old and new up, so calling
+    /// this every frame while moving over a planet does not spin the view around up.
+    pub fn set_up(&mut self, up: Vector3<f32>) {
+        let Some(up) = Unit::try_new(up, f32::EPSILON) else {
+            return;
+        };
+        let current = self.up();
+        let rotation = UnitQuaternion::rotation_between(&current, &up).unwrap_or_else(|| {
+            // Opposite directions: flip around the camera's right axis.
+            UnitQuaternion::from_axis_angle(
+                &Unit::new_normalize(self.right()),
+                std::f32::consts::PI,
+            )
+        });
+        self.frame = rotation * self.frame;
+    }
+
+    /// Unit vector the camera is looking along.
+    pub fn forward(&self) -> Vector3<f32> {
+        let (sin_yaw, cos_yaw) = self.yaw.sin_cos();
+        let (sin_pitch, cos_pitch) = self.pitch.sin_cos();
+        self.frame * Vector3::new(-sin_yaw * cos_pitch, sin_pitch, -cos_yaw * cos_pitch)
+    }
+
+    /// Unit vector pointing to the camera's right, parallel to the ground.
+    pub fn right(&self) -> Vector3<f32> {
+        let (sin_yaw, cos_yaw) = self.yaw.sin_cos();
+        self.frame * Vector3::new(cos_yaw, 0.0, -sin_yaw)
+    }
+
+    /// World-to-view transform.
+    pub fn view_matrix(&self) -> Matrix4<f32> {
+        let target = self.position + self.forward();
+        Matrix4::look_at_rh(&self.position, &target, &self.up())
+    }
+
+    /// View-to-clip transform for Vulkan, for a viewport with the given aspect ratio.
+    pub fn projection_matrix(&self, aspect: f32) -> Matrix4<f32> {
+        let projection = Perspective3::new(aspect, self.fov_y, self.near, self.far);
+        VULKAN_CLIP_CORRECTION * projection.to_homogeneous()
+    }
+
+    /// The volume the camera can see, for culling.
+    pub fn frustum(&self, aspect: f32) -> Frustum {
+        Frustum::from_matrix(&(self.projection_matrix(aspect) * self.view_matrix()))
+    }
+}
+
+/// Six inward-facing planes bounding what a camera can see.
+#[derive(Debug, Clone)]
+pub struct Frustum {
+    // (normal, distance) with normal·p + distance >= 0 inside.
+    planes: [Vector4<f32>; 6],
+}
+
+impl Frustum {
+    /// Extracts the planes of a Vulkan world-to-clip matrix (depth 0..1).
+    pub fn from_matrix(m: &Matrix4<f32>) -> Self {
+        let row = |i: usize| m.row(i).transpose();
+        let (r0, r1, r2, r3) = (row(0), row(1), row(2), row(3));
+        let planes = [r3 + r0, r3 - r0, r3 + r1, r3 - r1, r2, r3 - r2].map(|plane| {
+            let length = plane.xyz().norm();
+            if length > 0.0 {
+                plane / length
+            } else {
+                plane
+            }
+        });
+        Self { planes }
+    }
+
+    /// Whether an axis-aligned box is at least partly inside.
+    pub fn intersects_aabb(&self, min: &Point3<f32>, max: &Point3<f32>) -> bool {
+        self.planes.iter().all(|plane| {
+            // The corner furthest along the plane normal.
+            let corner = Vector3::new(
+                if plane.x >= 0.0 { max.x } else { min.x },
+                if plane.y >= 0.0 { max.y } else { min.y },
+                if plane.z >= 0.0 { max.z } else { min.z },
+            );
+            plane.xyz().dot(&corner) + plane.w >= 0.0
+        })
+    }
+}
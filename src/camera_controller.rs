@@ -0,0 +1,275 @@
+// Copyright 2025 Nicholas Jordan. All Rights Reserved.
+// github.com/cvusmo/lustre
+// src/camera_controller.rs
+
+use crate::camera::Camera;
+use crate::character::CharacterInput;
+use crate::settings;
+
+use rapier3d::na::Point3;
+use serde::{Deserialize, Serialize};
+use std::f32::consts::FRAC_PI_2;
+use winit::event::{ElementState, MouseButton, MouseScrollDelta};
+use winit::keyboard::KeyCode;
+
+// Keep pitch just short of straight up/down so the view matrix never degenerates.
+const MAX_PITCH: f32 = FRAC_PI_2 - 0.01;
+
+// Pixels of a touchpad scroll that count as one wheel line.
+const PIXELS_PER_SCROLL_LINE: f32 = 20.0;
+
+/// How mouse and keyboard input move the camera.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CameraMode {
+    /// WASD to move, mouse to look while the pointer is grabbed.
+    FreeFly,
+    /// Drag to rotate around a focus point, scroll to zoom.
+    Orbit,
+    /// WASD to walk a character through the world, mouse to look while the pointer is grabbed.
+    Walk,
+}
+
+/// Tunable speeds and sensitivities for the camera controller.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CameraSettings {
+    pub mode: CameraMode,
+    /// Free-fly speed in world units per second.
+    pub move_speed: f32,
+    /// Speed multiplier while Shift is held.
+    pub sprint_multiplier: f32,
+    /// Radians of rotation per pixel of mouse motion.
+    pub mouse_sensitivity: f32,
+    /// Fraction of the orbit distance zoomed per scroll line.
+    pub scroll_sensitivity: f32,
+    pub invert_y: bool,
+    pub orbit_distance: f32,
+    pub orbit_min_distance: f32,
+    pub orbit_max_distance: f32,
+}
+
+impl Default for CameraSettings {
+    fn default() -> Self {
+        Self {
+            mode: CameraMode::FreeFly,
+            move_speed: 10.0,
+            sprint_multiplier: 4.0,
+            mouse_sensitivity: 0.002,
+            scroll_sensitivity: 0.1,
+            invert_y: false,
+            orbit_distance: 10.0,
+            orbit_min_distance: 1.0,
+            orbit_max_distance: 500.0,
+        }
+    }
+}
+
+impl CameraSettings {
+    /// Reads the `[camera]` table of a config file.
+    pub fn load(path: &str) -> Self {
+        settings::load_section(path, "camera")
+    }
+}
+
+// Movement keys currently held down.
+#[derive(Debug, Default)]
+struct MovementKeys {
+    forward: bool,
+    backward: bool,
+    left: bool,
+    right: bool,
+    up: bool,
+    down: bool,
+    sprint: bool,
+}
+
+/// Turns winit input into camera motion, in free-fly or orbit mode.
+pub struct CameraController {
+    pub settings: CameraSettings,
+    mode: CameraMode,
+    keys: MovementKeys,
+    mouse_delta: (f32, f32),
+    scroll_lines: f32,
+    pointer_grabbed: bool,
+    dragging: bool,
+    focus: Point3<f32>,
+    orbit_distance: f32,
+}
+
+impl CameraController {
+    pub fn new(settings: CameraSettings) -> Self {
+        Self {
+            mode: settings.mode,
+            orbit_distance: settings.orbit_distance,
+            settings,
+            keys: MovementKeys::default(),
+            mouse_delta: (0.0, 0.0),
+            scroll_lines: 0.0,
+            pointer_grabbed: false,
+            dragging: false,
+            focus: Point3::origin(),
+        }
+    }
+
+    pub fn mode(&self) -> CameraMode {
+        self.mode
+    }
+
+    /// Point the orbit camera rotates around.
+    pub fn focus(&self) -> Point3<f32> {
+        self.focus
+    }
+
+    pub fn set_focus(&mut self, focus: Point3<f32>) {
+        self.focus = focus;
+    }
+
+    /// Switches modes, keeping the view continuous: entering orbit mode focuses
+    /// on the point `orbit_distance` in front of the camera.
+    pub fn set_mode(&mut self, mode: CameraMode, camera: &Camera) {
+        if mode == CameraMode::Orbit && self.mode != CameraMode::Orbit {
+            self.focus = camera.position + camera.forward() * self.orbit_distance;
+        }
+        self.mode = mode;
+        self.dragging = false;
+    }
+
+    /// Toggles between orbit mode and free-fly mode. Walking switches to orbit mode.
+    pub fn toggle_mode(&mut self, camera: &Camera) {
+        let mode = match self.mode {
+            CameraMode::FreeFly | CameraMode::Walk => CameraMode::Orbit,
+            CameraMode::Orbit => CameraMode::FreeFly,
+        };
+        self.set_mode(mode, camera);
+    }
+
+    /// Whether free-fly or walking mouse look is active. The window grabs the pointer to match.
+    pub fn pointer_grabbed(&self) -> bool {
+        self.pointer_grabbed
+    }
+
+    pub fn set_pointer_grabbed(&mut self, grabbed: bool) {
+        self.pointer_grabbed = grabbed;
+        if !grabbed {
+            self.mouse_delta = (0.0, 0.0);
+        }
+    }
+
+    /// Records a key press or release. Returns true if the key is a camera binding.
+    pub fn process_keyboard(&mut self, key: KeyCode, state: ElementState) -> bool {
+        let pressed = state == ElementState::Pressed;
+        match key {
+            KeyCode::KeyW | KeyCode::ArrowUp => self.keys.forward = pressed,
+            KeyCode::KeyS | KeyCode::ArrowDown => self.keys.backward = pressed,
+            KeyCode::KeyA | KeyCode::ArrowLeft => self.keys.left = pressed,
+            KeyCode::KeyD | KeyCode::ArrowRight => self.keys.right = pressed,
+            KeyCode::Space | KeyCode::KeyE => self.keys.up = pressed,
+            KeyCode::ControlLeft | KeyCode::KeyQ => self.keys.down = pressed,
+            KeyCode::ShiftLeft | KeyCode::ShiftRight => self.keys.sprint = pressed,
+            _ => return false,
+        }
+        true
+    }
+
+    /// Forgets held keys and buttons, for when the window stops receiving their releases.
+    pub fn release_all(&mut self) {
+        self.keys = MovementKeys::default();
+        self.dragging = false;
+        self.mouse_delta = (0.0, 0.0);
+    }
+
+    /// Records a mouse button; dragging with the left or right button rotates the orbit camera.
+    pub fn process_mouse_button(&mut self, button: MouseButton, state: ElementState) {
+        if matches!(button, MouseButton::Left | MouseButton::Right) {
+            self.dragging = state == ElementState::Pressed;
+        }
+    }
+
+    /// Accumulates raw mouse motion until the next update.
+    pub fn process_mouse_motion(&mut self, dx: f64, dy: f64) {
+        let rotating = match self.mode {
+            CameraMode::FreeFly | CameraMode::Walk => self.pointer_grabbed,
+            CameraMode::Orbit => self.dragging,
+        };
+        if rotating {
+            self.mouse_delta.0 += dx as f32;
+            self.mouse_delta.1 += dy as f32;
+        }
+    }
+
+    /// Accumulates scroll wheel or touchpad input until the next update.
+    pub fn process_scroll(&mut self, delta: &MouseScrollDelta) {
+        self.scroll_lines += match delta {
+            MouseScrollDelta::LineDelta(_, y) => *y,
+            MouseScrollDelta::PixelDelta(position) => position.y as f32 / PIXELS_PER_SCROLL_LINE,
+        };
+    }
+
+    /// What the held keys ask of a walking character looking through `camera`.
+    pub fn character_input(&self, camera: &Camera) -> CharacterInput {
+        let axis = |positive: bool, negative: bool| positive as i8 as f32 - negative as i8 as f32;
+        // Forward along the ground, however far up or down the camera looks.
+        let forward = camera.up().cross(&camera.right());
+        let direction = forward * axis(self.keys.forward, self.keys.backward)
+            + camera.right() * axis(self.keys.right, self.keys.left);
+        CharacterInput {
+            direction: direction.try_normalize(f32::EPSILON).unwrap_or_default(),
+            jump: self.keys.up,
+            crouch: self.keys.down,
+            sprint: self.keys.sprint,
+        }
+    }
+
+    /// Applies the input gathered since the last call to the camera. In walk mode only the
+    /// view turns; the character places the camera.
+    pub fn update_camera(&mut self, camera: &mut Camera, dt: f32) {
+        // Mouse look / orbit rotation
+        let (dx, dy) = std::mem::take(&mut self.mouse_delta);
+        let dy = if self.settings.invert_y { -dy } else { dy };
+        camera.yaw -= dx * self.settings.mouse_sensitivity;
+        camera.pitch =
+            (camera.pitch - dy * self.settings.mouse_sensitivity).clamp(-MAX_PITCH, MAX_PITCH);
+
+        let scroll = std::mem::take(&mut self.scroll_lines);
+
+        match self.mode {
+            CameraMode::FreeFly => self.update_free_fly(camera, dt, scroll),
+            CameraMode::Orbit => self.update_orbit(camera, scroll),
+            CameraMode::Walk => (),
+        }
+    }
+
+    fn update_free_fly(&mut self, camera: &mut Camera, dt: f32, scroll: f32) {
+        // Scrolling adjusts the fly speed.
+        if scroll != 0.0 {
+            let factor = (1.0 + self.settings.scroll_sensitivity).powf(scroll);
+            self.settings.move_speed = (self.settings.move_speed * factor).clamp(0.1, 1000.0);
+        }
+
+        let axis = |positive: bool, negative: bool| positive as i8 as f32 - negative as i8 as f32;
+        let direction = camera.forward() * axis(self.keys.forward, self.keys.backward)
+            + camera.right() * axis(self.keys.right, self.keys.left)
+            + camera.up() * axis(self.keys.up, self.keys.down);
+
+        if let Some(direction) = direction.try_normalize(f32::EPSILON) {
+            let mut speed = self.settings.move_speed;
+            if self.keys.sprint {
+                speed *= self.settings.sprint_multiplier;
+            }
+            camera.position += direction * speed * dt;
+        }
+    }
+
+    fn update_orbit(&mut self, camera: &mut Camera, scroll: f32) {
+        // Scrolling up zooms in.
+        if scroll != 0.0 {
+            let factor = (1.0 - self.settings.scroll_sensitivity).powf(scroll);
+            self.orbit_distance = (self.orbit_distance * factor).clamp(
+                self.settings.orbit_min_distance,
+                self.settings.orbit_max_distance,
+            );
+        }
+
+        camera.position = self.focus - camera.forward() * self.orbit_distance;
+    }
+}
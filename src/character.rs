@@ -0,0 +1,340 @@
+// Copyright 2025 Nicholas Jordan. All Rights Reserved.
+// github.com/cvusmo/lustre
+// src/character.rs
+
+use crate::physics::PhysicsWorld;
+use crate::settings;
+use crate::world::{BlockPos, World};
+
+use rapier3d::control::{CharacterAutostep, CharacterLength, KinematicCharacterController};
+use rapier3d::na::{Isometry3, Point3, Unit, UnitQuaternion, Vector3};
+use rapier3d::parry::query;
+use rapier3d::prelude::{Cuboid, QueryFilter, Ray};
+use serde::{Deserialize, Serialize};
+
+// Ground closer than this below the feet counts for crouch-edge protection.
+const EDGE_PROBE_DEPTH: f32 = 0.6;
+
+// The block containing a point.
+fn block_at(point: &Point3<f32>) -> BlockPos {
+    BlockPos::new(
+        point.x.floor() as i32,
+        point.y.floor() as i32,
+        point.z.floor() as i32,
+    )
+}
+
+/// Size and movement tuning for player characters, read from the `[character]` table.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CharacterSettings {
+    /// Width of the collision box in blocks.
+    pub width: f32,
+    /// Total height from the feet to the top of the head.
+    pub height: f32,
+    /// Eye height above the feet while standing; crouching lowers it by `crouch_drop`.
+    pub eye_height: f32,
+    pub crouch_drop: f32,
+    /// Speeds in blocks per second.
+    pub walk_speed: f32,
+    pub sprint_speed: f32,
+    pub crouch_speed: f32,
+    pub swim_speed: f32,
+    /// Apex of a standing jump, in blocks.
+    pub jump_height: f32,
+    /// Tallest ledge walked onto without jumping.
+    pub step_height: f32,
+    /// Steepest slope that can be walked up, in degrees.
+    pub max_slope_degrees: f32,
+    /// Fraction of gravity felt while in a fluid.
+    pub fluid_gravity_scale: f32,
+}
+
+impl Default for CharacterSettings {
+    fn default() -> Self {
+        Self {
+            width: 0.6,
+            height: 1.8,
+            eye_height: 1.62,
+            crouch_drop: 0.3,
+            walk_speed: 4.3,
+            sprint_speed: 5.6,
+            crouch_speed: 1.3,
+            swim_speed: 2.0,
+            jump_height: 1.25,
+            step_height: 1.05,
+            max_slope_degrees: 50.0,
+            fluid_gravity_scale: 0.2,
+        }
+    }
+}
+
+impl CharacterSettings {
+    /// Reads the `[character]` table of a config file.
+    pub fn load(path: &str) -> Self {
+        settings::load_section(path, "character")
+    }
+}
+
+/// What the player wants the character to do this frame.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CharacterInput {
+    /// Desired walking direction in world space. Only the part along the ground is used
+    /// and its length is capped at 1.
+    pub direction: Vector3<f32>,
+    /// Jump when on the ground; swim up in fluids.
+    pub jump: bool,
+    /// Walk slowly without falling off edges; sink in fluids.
+    pub crouch: bool,
+    pub sprint: bool,
+}
+
+/// A first-person character moved kinematically through the physics world's terrain.
+///
+/// Collides with fixed colliders only, so it walks on chunk colliders but pushes nothing.
+/// Up follows the world's gravity, so the same controller works on flat worlds and planets.
+pub struct CharacterController {
+    pub settings: CharacterSettings,
+    controller: KinematicCharacterController,
+    // Bottom of the collision box.
+    position: Point3<f32>,
+    velocity: Vector3<f32>,
+    grounded: bool,
+    swimming: bool,
+    crouching: bool,
+}
+
+impl CharacterController {
+    /// Creates a character standing with its feet at `position`.
+    pub fn new(settings: CharacterSettings, position: Point3<f32>) -> Self {
+        let controller = KinematicCharacterController {
+            offset: CharacterLength::Absolute(0.02),
+            autostep: Some(CharacterAutostep {
+                max_height: CharacterLength::Absolute(settings.step_height),
+                min_width: CharacterLength::Absolute(settings.width / 2.0),
+                include_dynamic_bodies: false,
+            }),
+            max_slope_climb_angle: settings.max_slope_degrees.to_radians(),
+            min_slope_slide_angle: settings.max_slope_degrees.to_radians(),
+            snap_to_ground: Some(CharacterLength::Absolute(0.3)),
+            ..Default::default()
+        };
+        Self {
+            settings,
+            controller,
+            position,
+            velocity: Vector3::zeros(),
+            grounded: false,
+            swimming: false,
+            crouching: false,
+        }
+    }
+
+    /// Position of the feet.
+    pub fn position(&self) -> Point3<f32> {
+        self.position
+    }
+
+    /// Moves the character without collision, e.g. to respawn it.
+    pub fn teleport(&mut self, position: Point3<f32>) {
+        self.position = position;
+        self.velocity = Vector3::zeros();
+        self.grounded = false;
+    }
+
+    /// Where the camera goes for a first-person view.
+    pub fn eye_position(&self, world: &World) -> Point3<f32> {
+        let mut eye_height = self.settings.eye_height;
+        if self.crouching {
+            eye_height -= self.settings.crouch_drop;
+        }
+        self.position + world.up_at(&self.position) * eye_height
+    }
+
+    pub fn velocity(&self) -> Vector3<f32> {
+        self.velocity
+    }
+
+    /// Whether the character stood on the ground after the last update.
+    pub fn is_grounded(&self) -> bool {
+        self.grounded
+    }
+
+    /// Whether the character was in a fluid during the last update.
+    pub fn is_swimming(&self) -> bool {
+        self.swimming
+    }
+
+    pub fn is_crouching(&self) -> bool {
+        self.crouching
+    }
+
+    /// Whether a block at `pos` would overlap the character, e.g. when placing one.
+    pub fn intersects_block(&self, world: &World, pos: BlockPos) -> bool {
+        let up = world.up_at(&self.position);
+        let (shape, pose) = self.shape(world, &self.position, &up);
+        let block_pose =
+            Isometry3::translation(pos.x as f32 + 0.5, pos.y as f32 + 0.5, pos.z as f32 + 0.5);
+        query::intersection_test(
+            &pose,
+            &shape,
+            &block_pose,
+            &Cuboid::new(Vector3::repeat(0.5)),
+        )
+        .unwrap_or(false)
+    }
+
+    // Box standing on `feet` along `up`, as rapier wants it: a y-aligned shape and pose.
+    // Flat-bottomed so it rests on block edges instead of rolling off them, and turned to
+    // the block grid under the feet so it sits flat on the blocks of a planet's cube face.
+    fn shape(
+        &self,
+        world: &World,
+        feet: &Point3<f32>,
+        up: &Vector3<f32>,
+    ) -> (Cuboid, Isometry3<f32>) {
+        let half_width = self.settings.width / 2.0;
+        let [x, y, z] = world.block_up(block_at(feet));
+        let grid_up = Vector3::new(x as f32, y as f32, z as f32);
+        let rotation =
+            UnitQuaternion::rotation_between(&Vector3::y(), &grid_up).unwrap_or_else(|| {
+                UnitQuaternion::from_axis_angle(&Vector3::x_axis(), std::f32::consts::PI)
+            });
+        let center = feet + up * (self.settings.height / 2.0);
+        (
+            Cuboid::new(Vector3::new(
+                half_width,
+                self.settings.height / 2.0,
+                half_width,
+            )),
+            Isometry3::from_parts(center.coords.into(), rotation),
+        )
+    }
+
+    // Whether a fluid block is at a point.
+    fn in_fluid(world: &World, point: &Point3<f32>) -> bool {
+        let block = world.get_block(block_at(point));
+        world.registry().properties(block).fluid
+    }
+
+    // Whether there is ground just below the feet if they were at `feet`.
+    fn has_ground_below(physics: &PhysicsWorld, feet: &Point3<f32>, up: &Vector3<f32>) -> bool {
+        let ray = Ray::new(feet + up * 0.1, -up);
+        physics
+            .query_pipeline()
+            .cast_ray(
+                physics.bodies(),
+                physics.colliders(),
+                &ray,
+                0.1 + EDGE_PROBE_DEPTH,
+                true,
+                QueryFilter::only_fixed(),
+            )
+            .is_some()
+    }
+
+    /// Moves the character for one frame of `dt` seconds.
+    pub fn update(
+        &mut self,
+        physics: &PhysicsWorld,
+        world: &World,
+        input: &CharacterInput,
+        dt: f32,
+    ) {
+        if dt <= 0.0 {
+            return;
+        }
+        let up = world.up_at(&self.position);
+        self.controller.up = Unit::new_normalize(up);
+        let gravity = world.gravity().acceleration(&self.position).dot(&up);
+
+        // Swimming once the water reaches the waist.
+        self.swimming = Self::in_fluid(world, &(self.position + up * (self.settings.height * 0.4)));
+        self.crouching = input.crouch && !self.swimming;
+
+        // Walking velocity along the ground.
+        let along_ground = input.direction - up * input.direction.dot(&up);
+        let along_ground = if along_ground.norm() > 1.0 {
+            along_ground.normalize()
+        } else {
+            along_ground
+        };
+        let speed = if self.swimming {
+            self.settings.swim_speed
+        } else if self.crouching {
+            self.settings.crouch_speed
+        } else if input.sprint {
+            self.settings.sprint_speed
+        } else {
+            self.settings.walk_speed
+        };
+        let horizontal = along_ground * speed;
+
+        // Speed along up.
+        let mut vertical = self.velocity.dot(&up);
+        if self.swimming {
+            vertical += gravity * self.settings.fluid_gravity_scale * dt;
+            if input.jump {
+                vertical = self.settings.swim_speed;
+            } else if input.crouch {
+                vertical = -self.settings.swim_speed;
+            }
+            // Water drag.
+            vertical *= (1.0 - 2.0 * dt).max(0.0);
+        } else if self.grounded && input.jump {
+            vertical = (2.0 * -gravity * self.settings.jump_height).max(0.0).sqrt();
+        } else if self.grounded {
+            vertical = 0.0;
+        } else {
+            vertical += gravity * dt;
+        }
+
+        let mut step = horizontal * dt;
+        // Crouching never walks off an edge; each direction is checked on its own so the
+        // character can still slide along it.
+        if self.crouching
+            && self.grounded
+            && !Self::has_ground_below(physics, &(self.position + step), &up)
+        {
+            let reference = if up.x.abs() < 0.9 {
+                Vector3::x()
+            } else {
+                Vector3::z()
+            };
+            let side = up.cross(&reference).normalize();
+            let forward = up.cross(&side);
+            let mut kept = Vector3::zeros();
+            for axis in [side, forward] {
+                let part = axis * step.dot(&axis);
+                if Self::has_ground_below(physics, &(self.position + kept + part), &up) {
+                    kept += part;
+                }
+            }
+            step = kept;
+        }
+        let desired = step + up * (vertical * dt);
+
+        let (shape, pose) = self.shape(world, &self.position, &up);
+        let movement = self.controller.move_shape(
+            dt,
+            physics.bodies(),
+            physics.colliders(),
+            physics.query_pipeline(),
+            &shape,
+            &pose,
+            desired,
+            QueryFilter::only_fixed(),
+            |_| {},
+        );
+        self.position += movement.translation;
+        // A character moving up has just jumped or is swimming, even if still touching ground.
+        self.grounded = movement.grounded && vertical <= 0.0;
+
+        // Stop on landing or when a ceiling cuts the rise short.
+        let moved_up = movement.translation.dot(&up);
+        if (self.grounded && vertical < 0.0) || (vertical > 0.0 && moved_up < vertical * dt * 0.5) {
+            vertical = 0.0;
+        }
+        self.velocity = horizontal + up * vertical;
+    }
+}
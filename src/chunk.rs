@@ -0,0 +1,263 @@
+// Copyright 2025 Nicholas Jordan. All Rights Reserved.
+// github.com/cvusmo/lustre
+// src/chunk.rs
+
+use crate::block::{BlockId, AIR};
+
+/// Edge length of a chunk in blocks.
+pub const CHUNK_SIZE: usize = 32;
+
+/// Number of blocks in a chunk.
+pub const CHUNK_VOLUME: usize = CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE;
+
+// Index of a local block position; x varies fastest, then z, then y.
+fn block_index(x: usize, y: usize, z: usize) -> usize {
+    debug_assert!(x < CHUNK_SIZE && y < CHUNK_SIZE && z < CHUNK_SIZE);
+    x + z * CHUNK_SIZE + y * CHUNK_SIZE * CHUNK_SIZE
+}
+
+/// Fixed-length array of integers packed into `bits` bits each.
+///
+/// Entries never straddle two words, so a few bits per word may go unused.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackedArray {
+    bits: u32,
+    len: usize,
+    words: Vec<u64>,
+}
+
+impl PackedArray {
+    /// Creates an array of `len` zeros. With `bits == 0` every entry is zero and no memory is used.
+    pub fn new(bits: u32, len: usize) -> Self {
+        assert!(bits <= 32);
+        let words = if bits == 0 {
+            Vec::new()
+        } else {
+            vec![0; len.div_ceil(Self::entries_per_word(bits))]
+        };
+        Self { bits, len, words }
+    }
+
+    /// Rebuilds an array from its raw parts, e.g. when loading from disk.
+    pub fn from_raw(bits: u32, len: usize, words: Vec<u64>) -> Option<Self> {
+        let expected = match bits {
+            0 => 0,
+            1..=32 => len.div_ceil(Self::entries_per_word(bits)),
+            _ => return None,
+        };
+        (words.len() == expected).then_some(Self { bits, len, words })
+    }
+
+    fn entries_per_word(bits: u32) -> usize {
+        (64 / bits) as usize
+    }
+
+    pub fn bits(&self) -> u32 {
+        self.bits
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn words(&self) -> &[u64] {
+        &self.words
+    }
+
+    pub fn get(&self, index: usize) -> u32 {
+        debug_assert!(index < self.len);
+        if self.bits == 0 {
+            return 0;
+        }
+        let per_word = Self::entries_per_word(self.bits);
+        let shift = (index % per_word) as u32 * self.bits;
+        let mask = (1u64 << self.bits) - 1;
+        ((self.words[index / per_word] >> shift) & mask) as u32
+    }
+
+    pub fn set(&mut self, index: usize, value: u32) {
+        debug_assert!(index < self.len);
+        debug_assert!(self.bits == 32 || value < (1 << self.bits));
+        if self.bits == 0 {
+            return;
+        }
+        let per_word = Self::entries_per_word(self.bits);
+        let shift = (index % per_word) as u32 * self.bits;
+        let mask = ((1u64 << self.bits) - 1) << shift;
+        let word = &mut self.words[index / per_word];
+        *word = (*word & !mask) | ((value as u64) << shift);
+    }
+
+    /// Returns a copy of this array using `bits` bits per entry.
+    pub fn resized(&self, bits: u32) -> Self {
+        let mut resized = Self::new(bits, self.len);
+        for index in 0..self.len {
+            resized.set(index, self.get(index));
+        }
+        resized
+    }
+}
+
+// Bits needed to index a palette of `len` entries.
+fn bits_for_palette(len: usize) -> u32 {
+    if len <= 1 {
+        0
+    } else {
+        usize::BITS - (len - 1).leading_zeros()
+    }
+}
+
+/// A cube of `CHUNK_SIZE`³ blocks stored as a palette plus bit-packed palette indices.
+///
+/// Chunks made of a single block type (e.g. all air or all stone) use no index storage.
+#[derive(Debug, Clone)]
+pub struct Chunk {
+    palette: Vec<BlockId>,
+    // Number of blocks using each palette entry; entries with a zero count are reused.
+    counts: Vec<u32>,
+    indices: PackedArray,
+    dirty: bool,
+}
+
+impl Default for Chunk {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Chunk {
+    /// Creates a chunk filled with air.
+    pub fn new() -> Self {
+        Self::filled(AIR)
+    }
+
+    /// Creates a chunk where every block is `block`.
+    pub fn filled(block: BlockId) -> Self {
+        Self {
+            palette: vec![block],
+            counts: vec![CHUNK_VOLUME as u32],
+            indices: PackedArray::new(0, CHUNK_VOLUME),
+            dirty: true,
+        }
+    }
+
+    /// Rebuilds a chunk from its palette and packed indices, e.g. when loading from disk.
+    /// Returns None if an index points outside the palette.
+    pub fn from_parts(palette: Vec<BlockId>, indices: PackedArray) -> Option<Self> {
+        if palette.is_empty() || indices.len() != CHUNK_VOLUME {
+            return None;
+        }
+        let mut counts = vec![0u32; palette.len()];
+        for index in 0..CHUNK_VOLUME {
+            *counts.get_mut(indices.get(index) as usize)? += 1;
+        }
+        Some(Self {
+            palette,
+            counts,
+            indices,
+            dirty: true,
+        })
+    }
+
+    pub fn get(&self, x: usize, y: usize, z: usize) -> BlockId {
+        self.palette[self.indices.get(block_index(x, y, z)) as usize]
+    }
+
+    /// Sets a block and returns the block it replaced.
+    pub fn set(&mut self, x: usize, y: usize, z: usize, block: BlockId) -> BlockId {
+        let index = block_index(x, y, z);
+        let old_entry = self.indices.get(index) as usize;
+        let old_block = self.palette[old_entry];
+        if old_block == block {
+            return old_block;
+        }
+
+        let entry = self.palette_entry(block);
+        self.indices.set(index, entry as u32);
+        self.counts[entry] += 1;
+        self.counts[old_entry] -= 1;
+        self.dirty = true;
+        old_block
+    }
+
+    // Finds or allocates the palette entry for a block, widening the indices if needed.
+    fn palette_entry(&mut self, block: BlockId) -> usize {
+        if let Some(entry) = self.palette.iter().position(|&b| b == block) {
+            return entry;
+        }
+        if let Some(entry) = self.counts.iter().position(|&count| count == 0) {
+            self.palette[entry] = block;
+            return entry;
+        }
+
+        self.palette.push(block);
+        self.counts.push(0);
+        let bits = bits_for_palette(self.palette.len());
+        if bits > self.indices.bits() {
+            self.indices = self.indices.resized(bits);
+        }
+        self.palette.len() - 1
+    }
+
+    /// Drops unused palette entries and shrinks the index width to match.
+    pub fn compact(&mut self) {
+        let mut remap = vec![0u32; self.palette.len()];
+        let mut palette = Vec::new();
+        let mut counts = Vec::new();
+        for (entry, (&block, &count)) in self.palette.iter().zip(&self.counts).enumerate() {
+            if count > 0 {
+                remap[entry] = palette.len() as u32;
+                palette.push(block);
+                counts.push(count);
+            }
+        }
+
+        let mut indices = PackedArray::new(bits_for_palette(palette.len()), CHUNK_VOLUME);
+        for index in 0..CHUNK_VOLUME {
+            indices.set(index, remap[self.indices.get(index) as usize]);
+        }
+
+        self.palette = palette;
+        self.counts = counts;
+        self.indices = indices;
+    }
+
+    /// Block types in this chunk's palette. May include unused entries until `compact`.
+    pub fn palette(&self) -> &[BlockId] {
+        &self.palette
+    }
+
+    pub fn indices(&self) -> &PackedArray {
+        &self.indices
+    }
+
+    /// Returns true if every block in the chunk is `block`.
+    pub fn is_uniform(&self, block: BlockId) -> bool {
+        self.palette
+            .iter()
+            .zip(&self.counts)
+            .all(|(&b, &count)| count == 0 || b == block)
+    }
+
+    /// Returns true if the chunk contains only air.
+    pub fn is_empty(&self) -> bool {
+        self.is_uniform(AIR)
+    }
+
+    /// Whether the chunk changed since the flag was last cleared (e.g. by the mesher).
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    pub fn mark_dirty(&mut self) {
+        self.dirty = true;
+    }
+
+    pub fn clear_dirty(&mut self) {
+        self.dirty = false;
+    }
+}
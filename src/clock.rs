@@ -0,0 +1,219 @@
+// Copyright 2025 Nicholas Jordan. All Rights Reserved.
+// github.com/cvusmo/lustre
+// src/clock.rs
+
+use crate::state::log_info;
+
+use mlua::prelude::*;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+// Steps allowed per frame, so a long frame doesn't snowball into longer ones.
+const MAX_STEPS_PER_FRAME: u32 = 8;
+
+// Slowest and fastest the simulation can run relative to real time.
+const MIN_TIME_SCALE: f32 = 1.0 / 64.0;
+const MAX_TIME_SCALE: f32 = 64.0;
+
+/// How much to simulate and draw this frame.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct FrameTime {
+    /// Real seconds since the last frame, for things that keep going while paused such as
+    /// the free-fly camera.
+    pub real_delta: f32,
+    /// Simulated seconds this frame: real time scaled, or only single steps while paused.
+    pub delta: f32,
+    /// Fixed steps to run this frame.
+    pub steps: u32,
+    /// How far the simulation is between the last step and the next, from 0 to 1.
+    pub alpha: f32,
+}
+
+/// Turns real frame times into fixed simulation steps.
+///
+/// The simulation can be paused, advanced one step at a time while paused, and run slower
+/// or faster than real time. Steps always simulate `fixed_step` seconds, so scaling time
+/// changes how many run per frame rather than how long they are.
+#[derive(Debug, Clone)]
+pub struct Clock {
+    fixed_step: f32,
+    time_scale: f32,
+    paused: bool,
+    // Single steps requested while paused, run on the next frame.
+    pending_steps: u32,
+    // Unsimulated time carried over to the next frame.
+    accumulator: f32,
+    steps: u64,
+}
+
+impl Clock {
+    pub fn new(fixed_step: f32) -> Self {
+        Self {
+            fixed_step,
+            time_scale: 1.0,
+            paused: false,
+            pending_steps: 0,
+            accumulator: 0.0,
+            steps: 0,
+        }
+    }
+
+    /// Seconds simulated by one step.
+    pub fn fixed_step(&self) -> f32 {
+        self.fixed_step
+    }
+
+    /// Steps run since the clock was created.
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    /// Seconds simulated since the clock was created.
+    pub fn time(&self) -> f64 {
+        self.steps as f64 * self.fixed_step as f64
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn pause(&mut self) {
+        if !self.paused {
+            log_info("Simulation paused");
+        }
+        self.paused = true;
+    }
+
+    pub fn resume(&mut self) {
+        if self.paused {
+            log_info("Simulation resumed");
+        }
+        self.paused = false;
+        self.pending_steps = 0;
+    }
+
+    pub fn toggle_pause(&mut self) {
+        if self.paused {
+            self.resume();
+        } else {
+            self.pause();
+        }
+    }
+
+    /// Pauses and queues `count` steps to run on the next frame.
+    pub fn step(&mut self, count: u32) {
+        self.pause();
+        self.pending_steps = self.pending_steps.saturating_add(count);
+    }
+
+    pub fn time_scale(&self) -> f32 {
+        self.time_scale
+    }
+
+    /// Sets how fast the simulation runs relative to real time; 1 is real time.
+    pub fn set_time_scale(&mut self, time_scale: f32) {
+        self.time_scale = if time_scale.is_finite() {
+            time_scale.clamp(MIN_TIME_SCALE, MAX_TIME_SCALE)
+        } else {
+            1.0
+        };
+    }
+
+    /// How far the simulation is between the last step and the next, from 0 to 1.
+    pub fn alpha(&self) -> f32 {
+        self.accumulator / self.fixed_step
+    }
+
+    /// Accounts for `real_delta` seconds of real time and says how much to simulate.
+    pub fn advance(&mut self, real_delta: f32) -> FrameTime {
+        let steps = if self.paused {
+            let steps = self.pending_steps.min(MAX_STEPS_PER_FRAME);
+            self.pending_steps -= steps;
+            steps
+        } else {
+            self.accumulator += real_delta.max(0.0) * self.time_scale;
+            let mut steps = 0;
+            while self.accumulator >= self.fixed_step && steps < MAX_STEPS_PER_FRAME {
+                self.accumulator -= self.fixed_step;
+                steps += 1;
+            }
+            // Drop time we couldn't catch up on rather than carrying it forever.
+            if steps == MAX_STEPS_PER_FRAME {
+                self.accumulator = self.accumulator.min(self.fixed_step);
+            }
+            steps
+        };
+        self.steps += steps as u64;
+
+        FrameTime {
+            real_delta,
+            delta: if self.paused {
+                steps as f32 * self.fixed_step
+            } else {
+                real_delta.max(0.0) * self.time_scale
+            },
+            steps,
+            alpha: self.alpha(),
+        }
+    }
+}
+
+/// Registers the `clock` table with Lua.
+///
+/// `clock.pause()`, `clock.resume()` and `clock.is_paused()` control pausing;
+/// `clock.step(n)` pauses and runs `n` steps (default 1). `clock.time_scale()` and
+/// `clock.set_time_scale(s)` read and change the speed. `clock.time()` is the simulated
+/// time in seconds.
+pub fn register_lua_api(lua: &Lua, clock: Rc<RefCell<Clock>>) -> LuaResult<()> {
+    let table = lua.create_table()?;
+
+    let shared = clock.clone();
+    table.set(
+        "pause",
+        lua.create_function(move |_, ()| {
+            shared.borrow_mut().pause();
+            Ok(())
+        })?,
+    )?;
+    let shared = clock.clone();
+    table.set(
+        "resume",
+        lua.create_function(move |_, ()| {
+            shared.borrow_mut().resume();
+            Ok(())
+        })?,
+    )?;
+    let shared = clock.clone();
+    table.set(
+        "is_paused",
+        lua.create_function(move |_, ()| Ok(shared.borrow().is_paused()))?,
+    )?;
+    let shared = clock.clone();
+    table.set(
+        "step",
+        lua.create_function(move |_, count: Option<u32>| {
+            shared.borrow_mut().step(count.unwrap_or(1));
+            Ok(())
+        })?,
+    )?;
+    let shared = clock.clone();
+    table.set(
+        "time_scale",
+        lua.create_function(move |_, ()| Ok(shared.borrow().time_scale()))?,
+    )?;
+    let shared = clock.clone();
+    table.set(
+        "set_time_scale",
+        lua.create_function(move |_, time_scale: f32| {
+            shared.borrow_mut().set_time_scale(time_scale);
+            Ok(())
+        })?,
+    )?;
+    table.set(
+        "time",
+        lua.create_function(move |_, ()| Ok(clock.borrow().time()))?,
+    )?;
+
+    lua.globals().set("clock", table)?;
+    Ok(())
+}
@@ -0,0 +1,188 @@
+// Copyright 2025 Nicholas Jordan. All Rights Reserved.
+// github.com/cvusmo/lustre
+// src/compute.rs
+
+use crate::headless::{read_image, HeadlessContext};
+
+use image::{ImageBuffer, Rgba};
+use std::error::Error;
+use std::sync::Arc;
+use vulkano::buffer::Subbuffer;
+use vulkano::command_buffer::{AutoCommandBufferBuilder, PrimaryAutoCommandBuffer};
+use vulkano::descriptor_set::{DescriptorSet, WriteDescriptorSet};
+use vulkano::format::Format;
+use vulkano::image::view::ImageView;
+use vulkano::image::ImageUsage;
+use vulkano::pipeline::compute::ComputePipelineCreateInfo;
+use vulkano::pipeline::layout::PipelineDescriptorSetLayoutCreateInfo;
+use vulkano::pipeline::{
+    ComputePipeline, Pipeline, PipelineBindPoint, PipelineLayout, PipelineShaderStageCreateInfo,
+};
+use vulkano::shader::ShaderModule;
+
+/// Local workgroup size shared by the bundled 2D compute shaders.
+pub const LOCAL_SIZE_2D: [u32; 2] = [8, 8];
+
+/// Format of the storage images the bundled compute shaders write (`rgba8`).
+pub const STORAGE_IMAGE_FORMAT: Format = Format::R8G8B8A8_UNORM;
+
+/// A resource bound to descriptor set 0, at the binding matching its position in the slice.
+pub enum ComputeBinding {
+    StorageImage(Arc<ImageView>),
+    StorageBuffer(Subbuffer<[u8]>),
+}
+
+impl ComputeBinding {
+    fn write(&self, binding: u32) -> WriteDescriptorSet {
+        match self {
+            ComputeBinding::StorageImage(view) => {
+                WriteDescriptorSet::image_view(binding, view.clone())
+            }
+            ComputeBinding::StorageBuffer(buffer) => {
+                WriteDescriptorSet::buffer(binding, buffer.clone())
+            }
+        }
+    }
+}
+
+/// A compute pipeline built from a loaded compute shader module.
+pub struct ComputeKernel {
+    pipeline: Arc<ComputePipeline>,
+}
+
+impl ComputeKernel {
+    /// Builds a pipeline for the shader's `main` entry point.
+    pub fn new(
+        context: &HeadlessContext,
+        shader: Arc<ShaderModule>,
+    ) -> Result<Self, Box<dyn Error>> {
+        let device = context.device.clone();
+        let entry_point = shader
+            .entry_point("main")
+            .ok_or("compute shader has no main entry point")?;
+        let stage = PipelineShaderStageCreateInfo::new(entry_point);
+
+        let layout = PipelineLayout::new(
+            device.clone(),
+            PipelineDescriptorSetLayoutCreateInfo::from_stages([&stage])
+                .into_pipeline_layout_create_info(device.clone())?,
+        )?;
+
+        let pipeline = ComputePipeline::new(
+            device,
+            None,
+            ComputePipelineCreateInfo::stage_layout(stage, layout),
+        )?;
+
+        Ok(Self { pipeline })
+    }
+
+    /// Records the descriptor set for `bindings` and a dispatch of `group_counts` workgroups.
+    ///
+    /// # Safety
+    ///
+    /// Vulkan doesn't bounds-check shaders. Every invocation of the dispatch must only read
+    /// and write inside the bound resources, e.g. by skipping invocations past the edge of
+    /// an image as the bundled shaders do.
+    pub unsafe fn record_dispatch(
+        &self,
+        context: &HeadlessContext,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        bindings: &[ComputeBinding],
+        group_counts: [u32; 3],
+    ) -> Result<(), Box<dyn Error>> {
+        let layout = self.pipeline.layout().clone();
+        let set_layout = layout
+            .set_layouts()
+            .first()
+            .ok_or("compute shader declares no descriptor set")?
+            .clone();
+
+        let descriptor_set = DescriptorSet::new(
+            context.descriptor_set_allocator.clone(),
+            set_layout,
+            bindings
+                .iter()
+                .enumerate()
+                .map(|(binding, resource)| resource.write(binding as u32)),
+            [],
+        )?;
+
+        builder
+            .bind_pipeline_compute(self.pipeline.clone())?
+            .bind_descriptor_sets(PipelineBindPoint::Compute, layout, 0, descriptor_set)?;
+
+        // Safety: the caller guarantees the shader stays within its resources.
+        unsafe {
+            builder.dispatch(group_counts)?;
+        }
+        Ok(())
+    }
+}
+
+/// Number of workgroups needed to cover a `width` x `height` grid.
+pub fn workgroup_count(width: u32, height: u32, local_size: [u32; 2]) -> [u32; 3] {
+    [
+        width.div_ceil(local_size[0]),
+        height.div_ceil(local_size[1]),
+        1,
+    ]
+}
+
+/// Dispatches a compute shader over the given bindings and waits for it to finish.
+///
+/// # Safety
+///
+/// As for [`ComputeKernel::record_dispatch`].
+pub unsafe fn dispatch(
+    context: &HeadlessContext,
+    shader: Arc<ShaderModule>,
+    bindings: &[ComputeBinding],
+    group_counts: [u32; 3],
+) -> Result<(), Box<dyn Error>> {
+    let kernel = ComputeKernel::new(context, shader)?;
+    let mut builder = context.command_buffer_builder()?;
+    // Safety: passed on to the caller.
+    unsafe { kernel.record_dispatch(context, &mut builder, bindings, group_counts)? };
+    context.submit_and_wait(builder)
+}
+
+/// Runs a compute shader that writes a single `rgba8` storage image at binding 0,
+/// and returns the image it produced.
+///
+/// # Safety
+///
+/// The shader must only touch pixels inside the image. The dispatch covers
+/// `workgroup_count(width, height, LOCAL_SIZE_2D)` groups, which overhang images whose
+/// size isn't a multiple of 8.
+pub unsafe fn dispatch_to_image(
+    context: &HeadlessContext,
+    shader: Arc<ShaderModule>,
+    width: u32,
+    height: u32,
+) -> Result<ImageBuffer<Rgba<u8>, Vec<u8>>, Box<dyn Error>> {
+    let kernel = ComputeKernel::new(context, shader)?;
+
+    let image = context.create_image(
+        STORAGE_IMAGE_FORMAT,
+        width,
+        height,
+        ImageUsage::STORAGE | ImageUsage::TRANSFER_SRC,
+    )?;
+    let view = ImageView::new_default(image.clone())?;
+
+    let mut builder = context.command_buffer_builder()?;
+    // Safety: passed on to the caller.
+    unsafe {
+        kernel.record_dispatch(
+            context,
+            &mut builder,
+            &[ComputeBinding::StorageImage(view)],
+            workgroup_count(width, height, LOCAL_SIZE_2D),
+        )?;
+    }
+    let buffer = context.copy_image_to_buffer(&mut builder, image)?;
+    context.submit_and_wait(builder)?;
+
+    read_image(&buffer, width, height)
+}
@@ -0,0 +1,247 @@
+// Copyright 2025 Nicholas Jordan. All Rights Reserved.
+// github.com/cvusmo/lustre
+// src/diagnostics.rs
+
+use mlua::prelude::*;
+use std::fmt;
+use std::path::Path;
+
+// Frames listed in a traceback before the rest are elided.
+const MAX_TRACEBACK_FRAMES: usize = 16;
+
+// Reported errors kept until they're shown; later ones are dropped.
+const MAX_REPORTS: usize = 64;
+
+// Lua app data holding errors reported since they were last taken.
+#[derive(Default)]
+struct Reports(Vec<Diagnostic>);
+
+/// A script error with where it happened.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// Lua's short name for the chunk, usually the script's path. Long paths are cut
+    /// down to their end, starting with `...`.
+    pub chunk: String,
+    /// 1-based line, when known.
+    pub line: Option<u32>,
+    pub message: String,
+    /// Stack traceback, innermost call first.
+    pub traceback: Option<String>,
+}
+
+impl Diagnostic {
+    /// Turns an error from loading or calling Lua into a diagnostic.
+    pub fn from_error(error: &LuaError) -> Self {
+        let mut traceback = None;
+        let mut error = error;
+        loop {
+            match error {
+                LuaError::CallbackError {
+                    cause,
+                    traceback: callback_traceback,
+                } => {
+                    traceback.get_or_insert_with(|| callback_traceback.clone());
+                    error = cause;
+                }
+                LuaError::WithContext { cause, .. } => error = cause,
+                _ => break,
+            }
+        }
+        let message = match error {
+            LuaError::SyntaxError { message, .. } => message.clone(),
+            LuaError::RuntimeError(message) => message.clone(),
+            error => error.to_string(),
+        };
+        // Errors from calling a function carry Lua's traceback at the end of the message.
+        let message = match message.split_once("\nstack traceback:") {
+            Some((message, frames)) => {
+                traceback.get_or_insert_with(|| format!("stack traceback:{}", frames));
+                message.to_string()
+            }
+            None => message,
+        };
+        Self {
+            traceback,
+            ..Self::from_message(&message)
+        }
+    }
+
+    /// Splits a Lua message of the form `chunk:line: message` into its parts.
+    pub fn from_message(message: &str) -> Self {
+        match split_location(message) {
+            Some((chunk, line, message)) => Self {
+                chunk: chunk.to_string(),
+                line: Some(line),
+                message: message.to_string(),
+                traceback: None,
+            },
+            None => Self {
+                chunk: String::new(),
+                line: None,
+                message: message.to_string(),
+                traceback: None,
+            },
+        }
+    }
+
+    /// Whether the error is in the chunk loaded under `chunk_name`, as made by
+    /// [`chunk_name`].
+    pub fn is_in(&self, chunk_name: &str) -> bool {
+        let name = chunk_name
+            .strip_prefix('@')
+            .or_else(|| chunk_name.strip_prefix('='))
+            .unwrap_or(chunk_name);
+        match self.chunk.strip_prefix("...") {
+            Some(tail) => name.ends_with(tail),
+            None => self.chunk == name,
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.chunk.is_empty(), self.line) {
+            (false, Some(line)) => write!(f, "{}:{}: {}", self.chunk, line, self.message),
+            (false, None) => write!(f, "{}: {}", self.chunk, self.message),
+            _ => write!(f, "{}", self.message),
+        }
+    }
+}
+
+// Finds the first `chunk:line: ` prefix, skipping colons inside the chunk name.
+fn split_location(message: &str) -> Option<(&str, u32, &str)> {
+    for (colon, _) in message.match_indices(':') {
+        let rest = &message[colon + 1..];
+        let digits = rest.len() - rest.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+        if digits == 0 || !rest[digits..].starts_with(": ") {
+            continue;
+        }
+        if let Ok(line) = rest[..digits].parse() {
+            return Some((&message[..colon], line, &rest[digits + 2..]));
+        }
+    }
+    None
+}
+
+/// Makes errors [`report`]ed through `lua` wait for [`take_reports`] instead of only being
+/// logged.
+pub fn collect_reports(lua: &Lua) {
+    lua.set_app_data(Reports::default());
+}
+
+/// Passes on an error a script raised outside the editor, such as in a mod, an entity
+/// script or an event handler, if `lua` collects reports. An error already waiting isn't
+/// added again, so a script failing every tick is listed once.
+pub fn report(lua: &Lua, diagnostic: Diagnostic) {
+    if let Some(mut reports) = lua.app_data_mut::<Reports>() {
+        if reports.0.len() < MAX_REPORTS && !reports.0.contains(&diagnostic) {
+            reports.0.push(diagnostic);
+        }
+    }
+}
+
+/// Removes and returns the errors reported since the last call.
+pub fn take_reports(lua: &Lua) -> Vec<Diagnostic> {
+    lua.app_data_mut::<Reports>()
+        .map(|mut reports| std::mem::take(&mut reports.0))
+        .unwrap_or_default()
+}
+
+/// Name to load a script from `path` under, so errors and tracebacks show the path.
+pub fn chunk_name(path: &Path) -> String {
+    format!("@{}", path.display())
+}
+
+// Describes the Lua stack from `level` outwards. Returns the traceback and the innermost
+// Lua frame's chunk and line.
+fn traceback(lua: &Lua, level: usize) -> (String, Option<(String, u32)>) {
+    let mut lines = vec!["stack traceback:".to_string()];
+    let mut location = None;
+    let mut level = level;
+    while let Some(frame) = lua.inspect_stack(level) {
+        level += 1;
+        if lines.len() > MAX_TRACEBACK_FRAMES {
+            lines.push("\t...".to_string());
+            break;
+        }
+        let source = frame.source();
+        let chunk = source.short_src.as_deref().unwrap_or("?").to_string();
+        let line = frame.curr_line();
+        let names = frame.names();
+        let function = match (names.name.as_deref(), source.what) {
+            (Some(name), _) => format!("function '{}'", name),
+            (None, "main") => "main chunk".to_string(),
+            (None, "C") => "?".to_string(),
+            (None, _) => format!(
+                "function <{}:{}>",
+                chunk,
+                source.line_defined.unwrap_or_default()
+            ),
+        };
+        if line > 0 {
+            location.get_or_insert_with(|| (chunk.clone(), line as u32));
+            lines.push(format!("\t{}:{}: in {}", chunk, line, function));
+        } else {
+            lines.push(format!("\t[C]: in {}", function));
+        }
+    }
+    (lines.join("\n"), location)
+}
+
+/// Loads and runs a script under `name`, turning any error into a diagnostic with a
+/// traceback of where it was raised.
+pub fn run_chunk(lua: &Lua, code: &str, name: &str) -> Result<(), Diagnostic> {
+    let chunk = lua
+        .load(code)
+        .set_name(name)
+        .into_function()
+        .map_err(|err| Diagnostic::from_error(&err))?;
+
+    // The handler runs where the error was raised, before the stack unwinds.
+    let handler = lua
+        .create_function(|lua, error: LuaValue| {
+            let message = match &error {
+                LuaValue::Error(error) => Diagnostic::from_error(error).to_string(),
+                error => error.to_string()?,
+            };
+            let (traceback, location) = traceback(lua, 1);
+            let report = lua.create_table()?;
+            report.set("message", message)?;
+            report.set("traceback", traceback)?;
+            if let Some((chunk, line)) = location {
+                report.set("chunk", chunk)?;
+                report.set("line", line)?;
+            }
+            Ok(report)
+        })
+        .map_err(|err| Diagnostic::from_error(&err))?;
+    let xpcall = lua
+        .globals()
+        .get::<LuaFunction>("xpcall")
+        .map_err(|err| Diagnostic::from_error(&err))?;
+
+    let (ok, report) = xpcall
+        .call::<(bool, LuaValue)>((chunk, handler))
+        .map_err(|err| Diagnostic::from_error(&err))?;
+    if ok {
+        return Ok(());
+    }
+    let LuaValue::Table(report) = report else {
+        return Err(Diagnostic::from_message(
+            &report.to_string().unwrap_or_default(),
+        ));
+    };
+    let mut diagnostic =
+        Diagnostic::from_message(&report.get::<String>("message").unwrap_or_default());
+    // Errors raised from Rust carry no location, so use the Lua code that called it.
+    if diagnostic.line.is_none() {
+        if let (Ok(chunk), Ok(line)) = (report.get::<String>("chunk"), report.get::<u32>("line")) {
+            diagnostic.chunk = chunk;
+            diagnostic.line = Some(line);
+        }
+    }
+    diagnostic.traceback = report
+        .get::<Option<String>>("traceback")
+        .unwrap_or_default();
+    Err(diagnostic)
+}
@@ -0,0 +1,472 @@
+// Copyright 2025 Nicholas Jordan. All Rights Reserved.
+// github.com/cvusmo/lustre
+// src/events.rs
+
+use crate::block::BlockId;
+use crate::diagnostics::{self, Diagnostic};
+use crate::scripting::{run_with_limits, LuaEntity, ScriptLimits};
+use crate::state::log_error;
+use crate::world::{BlockPos, ChunkPos};
+
+use mlua::prelude::*;
+use specs::Entity;
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+use std::rc::Rc;
+
+/// Names of the events the engine emits. Scripts can't emit these themselves.
+pub const ENGINE_EVENTS: [&str; 6] = [
+    "block_changed",
+    "entity_spawned",
+    "player_joined",
+    "key_pressed",
+    "tick",
+    "chunk_loaded",
+];
+
+/// Something that happened in the engine, sent to subscribers through an [`EventBus`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    /// A block is about to change. Cancelling keeps the old block.
+    BlockChanged {
+        pos: BlockPos,
+        old: BlockId,
+        new: BlockId,
+    },
+    EntitySpawned {
+        entity: Entity,
+    },
+    /// The player's entity was created by the render window.
+    PlayerJoined {
+        entity: Entity,
+    },
+    /// A key was pressed in the render window, named after its winit `KeyCode`, such as
+    /// `KeyW` or `Space`. Cancelling stops the engine from handling it.
+    KeyPressed {
+        key: String,
+    },
+    /// A fixed tick finished.
+    Tick {
+        tick: u64,
+        delta: f32,
+    },
+    /// A chunk was streamed in around the viewer.
+    ChunkLoaded {
+        pos: ChunkPos,
+    },
+    /// An event a script or system emitted under its own name. Always cancellable.
+    Custom {
+        name: String,
+        data: LuaValue,
+    },
+}
+
+impl Event {
+    /// The name handlers subscribe to.
+    pub fn name(&self) -> &str {
+        match self {
+            Event::BlockChanged { .. } => "block_changed",
+            Event::EntitySpawned { .. } => "entity_spawned",
+            Event::PlayerJoined { .. } => "player_joined",
+            Event::KeyPressed { .. } => "key_pressed",
+            Event::Tick { .. } => "tick",
+            Event::ChunkLoaded { .. } => "chunk_loaded",
+            Event::Custom { name, .. } => name,
+        }
+    }
+
+    /// Whether a handler can stop the event, and with it whatever it announces.
+    pub fn is_cancellable(&self) -> bool {
+        matches!(
+            self,
+            Event::BlockChanged { .. } | Event::KeyPressed { .. } | Event::Custom { .. }
+        )
+    }
+}
+
+/// What a handler wants done with the event after it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Propagation {
+    Continue,
+    /// Stops the event from reaching later handlers, if it's cancellable.
+    Cancel,
+}
+
+pub type HandlerId = u64;
+
+type RustHandler = Rc<RefCell<dyn FnMut(&Event) -> Propagation>>;
+
+#[derive(Clone)]
+enum Handler {
+    Rust(RustHandler),
+    // Weak, since the bus lives in the Lua's app data.
+    Lua(mlua::WeakLua, LuaFunction),
+}
+
+pub(crate) struct Subscription {
+    id: HandlerId,
+    event: String,
+    priority: i32,
+    // Mod the handler belongs to, so reloading it can replace its handlers.
+    owner: Option<String>,
+    // Project script whose top level subscribed the handler, so rerunning it replaces them.
+    script: Option<String>,
+    // What each call of a mod's handler may use.
+    limits: Option<ScriptLimits>,
+    handler: Handler,
+}
+
+/// Handlers subscribed to engine and custom events.
+///
+/// Handlers with a higher priority run first; equal priorities run in the order they
+/// subscribed.
+#[derive(Default)]
+pub struct EventBus {
+    subscriptions: Vec<Subscription>,
+    next_id: HandlerId,
+    // Lua handlers being called, so one that emits the event it's handling is skipped.
+    running: HashSet<HandlerId>,
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn subscribe(
+        &mut self,
+        event: &str,
+        priority: i32,
+        handler: impl FnMut(&Event) -> Propagation + 'static,
+    ) -> HandlerId {
+        self.add(
+            event,
+            priority,
+            None,
+            None,
+            None,
+            Handler::Rust(Rc::new(RefCell::new(handler))),
+        )
+    }
+
+    /// Removes a handler. Returns whether it was subscribed.
+    pub fn unsubscribe(&mut self, id: HandlerId) -> bool {
+        let before = self.subscriptions.len();
+        self.subscriptions
+            .retain(|subscription| subscription.id != id);
+        self.subscriptions.len() != before
+    }
+
+    // Removes a Lua handler subscribed by `owner`, leaving everyone else's alone.
+    fn unsubscribe_owned(&mut self, id: HandlerId, owner: Option<&str>) -> bool {
+        let owned = self.subscriptions.iter().any(|subscription| {
+            subscription.id == id
+                && subscription.owner.as_deref() == owner
+                && matches!(subscription.handler, Handler::Lua(..))
+        });
+        owned && self.unsubscribe(id)
+    }
+
+    pub fn has_subscribers(&self, event: &str) -> bool {
+        self.subscriptions
+            .iter()
+            .any(|subscription| subscription.event == event)
+    }
+
+    /// Removes and returns every handler `owner` subscribed.
+    pub(crate) fn take_owned(&mut self, owner: &str) -> Vec<Subscription> {
+        self.take_where(|subscription| subscription.owner.as_deref() == Some(owner))
+    }
+
+    // Removes and returns every handler the top level of `script` subscribed.
+    fn take_script(&mut self, script: &str) -> Vec<Subscription> {
+        self.take_where(|subscription| subscription.script.as_deref() == Some(script))
+    }
+
+    fn take_where(&mut self, taken: impl Fn(&Subscription) -> bool) -> Vec<Subscription> {
+        let (taken, others) = std::mem::take(&mut self.subscriptions)
+            .into_iter()
+            .partition(taken);
+        self.subscriptions = others;
+        taken
+    }
+
+    /// Puts back handlers removed by `take_owned`, in their old places.
+    pub(crate) fn restore(&mut self, subscriptions: Vec<Subscription>) {
+        for subscription in subscriptions {
+            self.insert(subscription);
+        }
+    }
+
+    fn add(
+        &mut self,
+        event: &str,
+        priority: i32,
+        owner: Option<String>,
+        script: Option<String>,
+        limits: Option<ScriptLimits>,
+        handler: Handler,
+    ) -> HandlerId {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.insert(Subscription {
+            id,
+            event: event.to_string(),
+            priority,
+            owner,
+            script,
+            limits,
+            handler,
+        });
+        id
+    }
+
+    fn insert(&mut self, subscription: Subscription) {
+        let index = self.subscriptions.partition_point(|other| {
+            other.priority > subscription.priority
+                || (other.priority == subscription.priority && other.id < subscription.id)
+        });
+        self.subscriptions.insert(index, subscription);
+    }
+}
+
+/// Sends `event` to its handlers in order. Returns whether a handler cancelled it, which
+/// is never the case for events that can't be cancelled.
+///
+/// Handlers may subscribe, unsubscribe and emit events of their own; changes to the
+/// handlers take effect from the next event.
+pub fn emit(bus: &RefCell<EventBus>, event: &Event) -> bool {
+    let handlers: Vec<(HandlerId, Handler, Option<ScriptLimits>)> = bus
+        .borrow()
+        .subscriptions
+        .iter()
+        .filter(|subscription| subscription.event == event.name())
+        .map(|subscription| {
+            (
+                subscription.id,
+                subscription.handler.clone(),
+                subscription.limits,
+            )
+        })
+        .collect();
+
+    for (id, handler, limits) in handlers {
+        let propagation = match handler {
+            // A handler that emits the event it's handling doesn't see it again.
+            Handler::Rust(handler) => match handler.try_borrow_mut() {
+                Ok(mut handler) => handler(event),
+                Err(_) => Propagation::Continue,
+            },
+            Handler::Lua(lua, function) => match lua.try_upgrade() {
+                Some(lua) if bus.borrow_mut().running.insert(id) => {
+                    let propagation = call_lua_handler(&lua, &function, event, limits.as_ref());
+                    bus.borrow_mut().running.remove(&id);
+                    propagation
+                }
+                _ => Propagation::Continue,
+            },
+        };
+        if propagation == Propagation::Cancel && event.is_cancellable() {
+            return true;
+        }
+    }
+    false
+}
+
+// Calls a Lua handler with the event as a table, within `limits` if it has any. Errors are
+// reported rather than stopping the other handlers.
+fn call_lua_handler(
+    lua: &Lua,
+    function: &LuaFunction,
+    event: &Event,
+    limits: Option<&ScriptLimits>,
+) -> Propagation {
+    let result = (|| {
+        let table = event_to_table(lua, event)?;
+        if event.is_cancellable() {
+            table.set("cancelled", false)?;
+            table.set(
+                "cancel",
+                lua.create_function(|_, event: LuaTable| event.set("cancelled", true))?,
+            )?;
+        }
+        match limits {
+            Some(limits) => run_with_limits(lua, limits, || function.call::<()>(&table))?,
+            None => function.call::<()>(&table)?,
+        }
+        table.get::<Option<bool>>("cancelled")
+    })();
+    match result {
+        Ok(Some(true)) => Propagation::Cancel,
+        Ok(_) => Propagation::Continue,
+        Err(err) => {
+            log_error(&format!("Handler for {} failed: {}", event.name(), err));
+            diagnostics::report(lua, Diagnostic::from_error(&err));
+            Propagation::Continue
+        }
+    }
+}
+
+fn position_to_table(lua: &Lua, x: i32, y: i32, z: i32) -> LuaResult<LuaTable> {
+    let table = lua.create_table()?;
+    table.set("x", x)?;
+    table.set("y", y)?;
+    table.set("z", z)?;
+    Ok(table)
+}
+
+fn event_to_table(lua: &Lua, event: &Event) -> LuaResult<LuaTable> {
+    let table = lua.create_table()?;
+    table.set("name", event.name())?;
+    match event {
+        Event::BlockChanged { pos, old, new } => {
+            table.set("pos", position_to_table(lua, pos.x, pos.y, pos.z)?)?;
+            table.set("old", *old)?;
+            table.set("new", *new)?;
+        }
+        Event::EntitySpawned { entity } | Event::PlayerJoined { entity } => {
+            table.set("entity", LuaEntity(*entity))?;
+        }
+        Event::KeyPressed { key } => table.set("key", key.as_str())?,
+        Event::Tick { tick, delta } => {
+            table.set("tick", *tick)?;
+            table.set("delta", *delta)?;
+        }
+        Event::ChunkLoaded { pos } => {
+            table.set("pos", position_to_table(lua, pos.x, pos.y, pos.z)?)?;
+        }
+        Event::Custom { data, .. } => table.set("data", data.clone())?,
+    }
+    Ok(table)
+}
+
+/// The bus engine APIs registered with `lua` emit to, if there is one.
+pub fn shared_bus(lua: &Lua) -> Option<Rc<RefCell<EventBus>>> {
+    lua.app_data_ref::<Rc<RefCell<EventBus>>>()
+        .map(|bus| bus.clone())
+}
+
+// Builds an `events` table whose handlers belong to `owner` and run within `limits`.
+fn create_table(
+    lua: &Lua,
+    bus: Rc<RefCell<EventBus>>,
+    owner: Option<&str>,
+    limits: Option<ScriptLimits>,
+) -> LuaResult<LuaTable> {
+    let table = lua.create_table()?;
+
+    let shared = bus.clone();
+    let owner = owner.map(str::to_string);
+    let subscriber = owner.clone();
+    table.set(
+        "on",
+        lua.create_function(
+            move |lua, (event, function, priority): (String, LuaFunction, Option<i32>)| {
+                let script = match subscriber {
+                    Some(_) => None,
+                    None => lua
+                        .app_data_ref::<RunningScript>()
+                        .map(|running| running.0.clone()),
+                };
+                Ok(shared.borrow_mut().add(
+                    &event,
+                    priority.unwrap_or(0),
+                    subscriber.clone(),
+                    script,
+                    limits,
+                    Handler::Lua(lua.weak(), function),
+                ))
+            },
+        )?,
+    )?;
+
+    let shared = bus.clone();
+    table.set(
+        "off",
+        lua.create_function(move |_, id: HandlerId| {
+            Ok(shared.borrow_mut().unsubscribe_owned(id, owner.as_deref()))
+        })?,
+    )?;
+
+    table.set(
+        "emit",
+        lua.create_function(move |_, (name, data): (String, Option<LuaValue>)| {
+            if ENGINE_EVENTS.contains(&name.as_str()) {
+                return Err(LuaError::runtime(format!(
+                    "'{}' is emitted by the engine and can't be emitted by scripts",
+                    name
+                )));
+            }
+            let event = Event::Custom {
+                name,
+                data: data.unwrap_or(LuaValue::Nil),
+            };
+            Ok(emit(&bus, &event))
+        })?,
+    )?;
+
+    Ok(table)
+}
+
+/// Creates the `events` table for a mod, whose handlers run within `limits` and are
+/// replaced when it reloads. Returns `None` if no bus is registered.
+pub fn create_mod_api(
+    lua: &Lua,
+    owner: &str,
+    limits: &ScriptLimits,
+) -> LuaResult<Option<LuaTable>> {
+    shared_bus(lua)
+        .map(|bus| create_table(lua, bus, Some(owner), Some(*limits)))
+        .transpose()
+}
+
+// Marks the project script whose top level is running.
+struct RunningScript(String);
+
+/// Runs the top level of the project script at `path` through `run`, replacing the
+/// handlers its last run subscribed through the global `events` table. If `run` fails,
+/// the previous handlers are put back.
+pub fn run_script<E>(lua: &Lua, path: &Path, run: impl FnOnce() -> Result<(), E>) -> Result<(), E> {
+    let script = fs::canonicalize(path)
+        .unwrap_or_else(|_| path.to_path_buf())
+        .display()
+        .to_string();
+    let bus = shared_bus(lua);
+    let handlers = bus
+        .as_ref()
+        .map(|bus| bus.borrow_mut().take_script(&script))
+        .unwrap_or_default();
+
+    let outer = lua.set_app_data(RunningScript(script.clone()));
+    let result = run();
+    match outer {
+        Some(outer) => lua.set_app_data(outer),
+        None => lua.remove_app_data::<RunningScript>(),
+    };
+
+    if result.is_err() {
+        if let Some(bus) = &bus {
+            let mut bus = bus.borrow_mut();
+            bus.take_script(&script);
+            bus.restore(handlers);
+        }
+    }
+    result
+}
+
+/// Registers the `events` table with Lua and makes `bus` the one engine APIs emit to.
+///
+/// `events.on(name, handler, priority)` subscribes `handler` to events called `name` and
+/// returns an id for `events.off(id)`, which only removes handlers subscribed through the
+/// same table; `priority` defaults to 0. Handlers get a table with
+/// the event's `name` and fields, and cancellable events have `event:cancel()`.
+/// `events.emit(name, data)` sends a custom event carrying `data` and returns whether a
+/// handler cancelled it.
+pub fn register_lua_api(lua: &Lua, bus: Rc<RefCell<EventBus>>) -> LuaResult<()> {
+    lua.set_app_data(bus.clone());
+    lua.globals()
+        .set("events", create_table(lua, bus, None, None)?)?;
+    Ok(())
+}
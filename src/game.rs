@@ -0,0 +1,453 @@
+// Copyright 2025 Nicholas Jordan. All Rights Reserved.
+// github.com/cvusmo/lustre
+// src/game.rs
+
+use crate::clock::{Clock, FrameTime};
+use crate::events::{emit, Event, EventBus};
+use crate::mesher::{push_box, ChunkMesh};
+use crate::physics::{PhysicsWorld, FIXED_TIMESTEP};
+
+use rapier3d::na::{Isometry3, Point3, Translation3, UnitQuaternion, Vector3};
+use rapier3d::prelude::{ColliderBuilder, RigidBodyBuilder, RigidBodyHandle};
+use specs::prelude::*;
+use specs::World as EcsWorld;
+use std::cell::{Ref, RefCell, RefMut};
+use std::rc::Rc;
+
+/// Where an entity is and which way it faces.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform {
+    pub position: Point3<f32>,
+    pub rotation: UnitQuaternion<f32>,
+}
+
+impl Transform {
+    pub fn new(position: Point3<f32>) -> Self {
+        Self {
+            position,
+            rotation: UnitQuaternion::identity(),
+        }
+    }
+
+    pub fn from_isometry(isometry: &Isometry3<f32>) -> Self {
+        Self {
+            position: isometry.translation.vector.into(),
+            rotation: isometry.rotation,
+        }
+    }
+
+    pub fn isometry(&self) -> Isometry3<f32> {
+        Isometry3::from_parts(Translation3::from(self.position.coords), self.rotation)
+    }
+
+    /// Blends towards `other`; `alpha` 0 is `self` and 1 is `other`.
+    pub fn interpolate(&self, other: &Transform, alpha: f32) -> Transform {
+        Transform {
+            position: self.position + (other.position - self.position) * alpha,
+            rotation: self.rotation.slerp(&other.rotation, alpha),
+        }
+    }
+}
+
+impl Component for Transform {
+    type Storage = VecStorage<Self>;
+}
+
+/// The transform an entity had before the current tick, for drawing it between ticks.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PreviousTransform(pub Transform);
+
+impl Component for PreviousTransform {
+    type Storage = VecStorage<Self>;
+}
+
+/// Linear velocity in blocks per second and angular velocity in radians per second.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Velocity {
+    pub linear: Vector3<f32>,
+    pub angular: Vector3<f32>,
+}
+
+impl Component for Velocity {
+    type Storage = VecStorage<Self>;
+}
+
+/// Ties an entity to a rapier body. Dynamic bodies drive the entity's transform and
+/// velocity; kinematic bodies follow its transform.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PhysicsBody(pub RigidBodyHandle);
+
+impl Component for PhysicsBody {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// Drawn as a solid box centred on the entity's transform.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Renderable {
+    pub half_extents: Vector3<f32>,
+    pub color: [f32; 3],
+}
+
+impl Component for Renderable {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// Marks the entity controlled by the local player.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Player;
+
+impl Component for Player {
+    type Storage = NullStorage<Self>;
+}
+
+/// Marks an entity to be removed, along with its physics body, at the start of the next
+/// tick. Used where the physics world isn't at hand, such as from scripts.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Despawn;
+
+impl Component for Despawn {
+    type Storage = NullStorage<Self>;
+}
+
+/// Seconds simulated by the tick being run, for systems to read.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TickDelta(pub f32);
+
+// Saves each transform before the tick changes it.
+struct RememberTransforms;
+
+impl<'a> System<'a> for RememberTransforms {
+    type SystemData = (
+        Entities<'a>,
+        ReadStorage<'a, Transform>,
+        WriteStorage<'a, PreviousTransform>,
+    );
+
+    fn run(&mut self, (entities, transforms, mut previous): Self::SystemData) {
+        for (entity, transform) in (&entities, &transforms).join() {
+            // Only fails for dead entities, which the join skips.
+            let _ = previous.insert(entity, PreviousTransform(*transform));
+        }
+    }
+}
+
+// Moves entities without a physics body by their velocity.
+struct Movement;
+
+impl<'a> System<'a> for Movement {
+    type SystemData = (
+        Read<'a, TickDelta>,
+        ReadStorage<'a, Velocity>,
+        ReadStorage<'a, PhysicsBody>,
+        WriteStorage<'a, Transform>,
+    );
+
+    fn run(&mut self, (dt, velocities, bodies, mut transforms): Self::SystemData) {
+        for (velocity, transform, ()) in (&velocities, &mut transforms, !&bodies).join() {
+            transform.position += velocity.linear * dt.0;
+            transform.rotation =
+                UnitQuaternion::from_scaled_axis(velocity.angular * dt.0) * transform.rotation;
+        }
+    }
+}
+
+/// Owns the entities of a running game and advances them in fixed ticks.
+///
+/// Each tick runs the dispatcher's systems, pushes velocities and kinematic transforms into
+/// the physics world, steps it once, then copies the bodies back into their entities. Ticks
+/// match the physics step, so rendering interpolates between the last two ticks. A shared
+/// [`Clock`] decides how many ticks each frame runs, so the editor and scripts can pause,
+/// step and slow the game.
+///
+/// The entity world is shared so scripts can reach it; systems and scripts only need a
+/// shared borrow, and the loop takes a mutable one between dispatches.
+pub struct GameLoop {
+    world: Rc<RefCell<EcsWorld>>,
+    dispatcher: Dispatcher<'static, 'static>,
+    clock: Rc<RefCell<Clock>>,
+    ticks: u64,
+    events: Rc<RefCell<EventBus>>,
+    frame_hooks: Vec<Box<dyn FnMut()>>,
+}
+
+impl Default for GameLoop {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GameLoop {
+    /// Creates a loop running only the core systems.
+    pub fn new() -> Self {
+        Self::with_systems(|_| ())
+    }
+
+    /// Creates a loop whose dispatcher also runs the systems `add` puts in the builder.
+    /// They run after the core systems, which are named `remember_transforms` and `movement`.
+    pub fn with_systems(add: impl FnOnce(&mut DispatcherBuilder<'static, 'static>)) -> Self {
+        let mut world = EcsWorld::new();
+        world.register::<Transform>();
+        world.register::<PreviousTransform>();
+        world.register::<Velocity>();
+        world.register::<PhysicsBody>();
+        world.register::<Renderable>();
+        world.register::<Player>();
+        world.register::<Despawn>();
+        world.insert(TickDelta(FIXED_TIMESTEP));
+
+        let mut builder = DispatcherBuilder::new()
+            .with(RememberTransforms, "remember_transforms", &[])
+            .with(Movement, "movement", &["remember_transforms"]);
+        builder.add_barrier();
+        add(&mut builder);
+        let mut dispatcher = builder.build();
+        dispatcher.setup(&mut world);
+
+        Self {
+            world: Rc::new(RefCell::new(world)),
+            dispatcher,
+            clock: Rc::new(RefCell::new(Clock::new(FIXED_TIMESTEP))),
+            ticks: 0,
+            events: Rc::new(RefCell::new(EventBus::new())),
+            frame_hooks: Vec::new(),
+        }
+    }
+
+    pub fn world(&self) -> Ref<'_, EcsWorld> {
+        self.world.borrow()
+    }
+
+    pub fn world_mut(&mut self) -> RefMut<'_, EcsWorld> {
+        self.world.borrow_mut()
+    }
+
+    /// The entity world, for handing to scripts.
+    pub fn shared_world(&self) -> Rc<RefCell<EcsWorld>> {
+        self.world.clone()
+    }
+
+    pub fn clock(&self) -> &Rc<RefCell<Clock>> {
+        &self.clock
+    }
+
+    /// Swaps in a clock shared with the editor or scripts. Its fixed step should match the
+    /// physics step.
+    pub fn set_clock(&mut self, clock: Rc<RefCell<Clock>>) {
+        self.clock = clock;
+    }
+
+    /// Bus the loop emits ticks and spawns to.
+    pub fn events(&self) -> &Rc<RefCell<EventBus>> {
+        &self.events
+    }
+
+    /// Swaps in an event bus shared with the editor or scripts.
+    pub fn set_events(&mut self, events: Rc<RefCell<EventBus>>) {
+        self.events = events;
+    }
+
+    /// Ticks run since the loop was created.
+    pub fn ticks(&self) -> u64 {
+        self.ticks
+    }
+
+    /// Adds a dynamic box body and an entity drawn at its position.
+    pub fn spawn_dynamic_box(
+        &mut self,
+        physics: &mut PhysicsWorld,
+        position: Point3<f32>,
+        half_extents: Vector3<f32>,
+        color: [f32; 3],
+    ) -> Entity {
+        let body = RigidBodyBuilder::dynamic()
+            .translation(position.coords)
+            .build();
+        let collider = ColliderBuilder::cuboid(half_extents.x, half_extents.y, half_extents.z);
+        let handle = physics.add_body(body, collider.build());
+        let transform = Transform::new(position);
+        let entity = self
+            .world
+            .borrow_mut()
+            .create_entity()
+            .with(transform)
+            .with(PreviousTransform(transform))
+            .with(Velocity::default())
+            .with(PhysicsBody(handle))
+            .with(Renderable {
+                half_extents,
+                color,
+            })
+            .build();
+        emit(&self.events, &Event::EntitySpawned { entity });
+        entity
+    }
+
+    /// Removes an entity along with its physics body, if it has one.
+    pub fn despawn(&mut self, physics: &mut PhysicsWorld, entity: Entity) {
+        let mut world = self.world.borrow_mut();
+        if let Some(PhysicsBody(handle)) = world.read_storage::<PhysicsBody>().get(entity) {
+            physics.remove_body(*handle);
+        }
+        let _ = world.delete_entity(entity);
+    }
+
+    // Removes the entities marked with `Despawn`.
+    fn remove_despawned(&mut self, physics: &mut PhysicsWorld) {
+        let despawned: Vec<Entity> = {
+            let world = self.world.borrow();
+            let (entities, despawn) = world.system_data::<(Entities, ReadStorage<Despawn>)>();
+            (&entities, &despawn)
+                .join()
+                .map(|(entity, _)| entity)
+                .collect()
+        };
+        for entity in despawned {
+            self.despawn(physics, entity);
+        }
+    }
+
+    /// Adds a function to run at the start of every frame, paused or not, such as
+    /// reloading changed scripts.
+    pub fn add_frame_hook(&mut self, hook: impl FnMut() + 'static) {
+        self.frame_hooks.push(Box::new(hook));
+    }
+
+    /// Advances the clock by `dt` real seconds and runs the ticks it asks for.
+    pub fn update(&mut self, physics: &mut PhysicsWorld, dt: f32) -> FrameTime {
+        for hook in &mut self.frame_hooks {
+            hook();
+        }
+        let frame = self.clock.borrow_mut().advance(dt);
+        for _ in 0..frame.steps {
+            self.tick(physics);
+        }
+        frame
+    }
+
+    /// Runs one tick.
+    pub fn tick(&mut self, physics: &mut PhysicsWorld) {
+        self.remove_despawned(physics);
+        self.world.borrow_mut().insert(TickDelta(FIXED_TIMESTEP));
+        self.dispatcher.dispatch(&self.world.borrow());
+        self.world.borrow_mut().maintain();
+
+        self.push_bodies(physics);
+        physics.step();
+        self.pull_bodies(physics);
+        self.ticks += 1;
+        emit(
+            &self.events,
+            &Event::Tick {
+                tick: self.ticks,
+                delta: FIXED_TIMESTEP,
+            },
+        );
+    }
+
+    // Hands changed velocities and kinematic transforms to physics.
+    fn push_bodies(&self, physics: &mut PhysicsWorld) {
+        let world = self.world.borrow();
+        let (bodies, transforms, velocities) = world.system_data::<(
+            ReadStorage<PhysicsBody>,
+            ReadStorage<Transform>,
+            ReadStorage<Velocity>,
+        )>();
+        for (PhysicsBody(handle), transform, velocity) in
+            (&bodies, &transforms, (&velocities).maybe()).join()
+        {
+            let Some(body) = physics.body_mut(*handle) else {
+                continue;
+            };
+            if body.is_kinematic() {
+                body.set_next_kinematic_position(transform.isometry());
+            } else if let Some(velocity) = velocity {
+                // Only wake bodies a system actually pushed.
+                if *body.linvel() != velocity.linear {
+                    body.set_linvel(velocity.linear, true);
+                }
+                if *body.angvel() != velocity.angular {
+                    body.set_angvel(velocity.angular, true);
+                }
+            }
+        }
+    }
+
+    // Copies simulated bodies back into their entities.
+    fn pull_bodies(&self, physics: &PhysicsWorld) {
+        let world = self.world.borrow();
+        let (bodies, mut transforms, mut velocities) = world.system_data::<(
+            ReadStorage<PhysicsBody>,
+            WriteStorage<Transform>,
+            WriteStorage<Velocity>,
+        )>();
+        for (PhysicsBody(handle), transform, velocity) in
+            (&bodies, &mut transforms, (&mut velocities).maybe()).join()
+        {
+            let Some(body) = physics.body(*handle) else {
+                continue;
+            };
+            *transform = Transform::from_isometry(body.position());
+            if let Some(velocity) = velocity {
+                velocity.linear = *body.linvel();
+                velocity.angular = *body.angvel();
+            }
+        }
+    }
+
+    /// How far rendering is between the last tick and the next, from 0 to 1.
+    pub fn interpolation_alpha(&self) -> f32 {
+        self.clock.borrow().alpha()
+    }
+
+    /// Where to draw an entity this frame, between its last two ticks.
+    pub fn interpolated_transform(&self, entity: Entity) -> Option<Transform> {
+        let world = self.world.borrow();
+        let transforms = world.read_storage::<Transform>();
+        let previous = world.read_storage::<PreviousTransform>();
+        let current = transforms.get(entity)?;
+        Some(match previous.get(entity) {
+            Some(PreviousTransform(previous)) => {
+                previous.interpolate(current, self.interpolation_alpha())
+            }
+            None => *current,
+        })
+    }
+
+    /// The first entity marked as the player.
+    pub fn player(&self) -> Option<Entity> {
+        let world = self.world.borrow();
+        let (entities, players) = world.system_data::<(Entities, ReadStorage<Player>)>();
+        (&entities, &players)
+            .join()
+            .map(|(entity, _)| entity)
+            .next()
+    }
+
+    /// Builds one mesh of every renderable entity at its interpolated transform.
+    pub fn render_mesh(&self) -> ChunkMesh {
+        let alpha = self.interpolation_alpha();
+        let world = self.world.borrow();
+        let (transforms, previous, renderables) = world.system_data::<(
+            ReadStorage<Transform>,
+            ReadStorage<PreviousTransform>,
+            ReadStorage<Renderable>,
+        )>();
+
+        let mut mesh = ChunkMesh::default();
+        for (transform, previous, renderable) in
+            (&transforms, (&previous).maybe(), &renderables).join()
+        {
+            let transform = match previous {
+                Some(PreviousTransform(previous)) => previous.interpolate(transform, alpha),
+                None => *transform,
+            };
+            push_box(
+                &mut mesh,
+                &transform.isometry(),
+                &renderable.half_extents,
+                renderable.color,
+            );
+        }
+        mesh
+    }
+}
@@ -0,0 +1,243 @@
+// Copyright 2025 Nicholas Jordan. All Rights Reserved.
+// github.com/cvusmo/lustre
+// src/golden.rs
+
+use crate::state::log_info;
+
+use image::{ImageBuffer, Rgba, RgbaImage};
+use std::env;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// Environment variable that, when set to `1`, rewrites reference images instead of comparing.
+pub const BLESS_ENV: &str = "LUSTRE_BLESS_GOLDEN";
+
+/// How far a rendered image may drift from its reference.
+#[derive(Debug, Clone, Copy)]
+pub struct Tolerance {
+    /// Largest allowed absolute difference of any channel before a pixel counts as mismatched.
+    pub max_channel_delta: u8,
+    /// Fraction of pixels (0.0..=1.0) allowed to mismatch, to absorb rasterization differences
+    /// along triangle edges between drivers.
+    pub max_mismatched_ratio: f32,
+}
+
+impl Default for Tolerance {
+    fn default() -> Self {
+        Self {
+            max_channel_delta: 2,
+            max_mismatched_ratio: 0.005,
+        }
+    }
+}
+
+/// Result of comparing two images pixel by pixel.
+pub struct Comparison {
+    pub mismatched_pixels: usize,
+    pub total_pixels: usize,
+    pub max_channel_delta: u8,
+    /// Mismatched pixels in red, matching pixels as a dimmed grayscale of the reference.
+    pub diff: RgbaImage,
+}
+
+impl Comparison {
+    /// Returns true if the comparison is within the given tolerance.
+    pub fn passes(&self, tolerance: &Tolerance) -> bool {
+        let allowed = (self.total_pixels as f32 * tolerance.max_mismatched_ratio) as usize;
+        self.mismatched_pixels <= allowed
+    }
+}
+
+/// Errors reported by golden-image checks.
+#[derive(Debug)]
+pub enum GoldenError {
+    /// No reference image exists and blessing wasn't requested.
+    MissingReference {
+        name: String,
+        path: PathBuf,
+    },
+    SizeMismatch {
+        name: String,
+        expected: (u32, u32),
+        actual: (u32, u32),
+    },
+    Mismatch {
+        name: String,
+        mismatched_pixels: usize,
+        total_pixels: usize,
+        max_channel_delta: u8,
+        diff_path: PathBuf,
+    },
+    Image(image::ImageError),
+    Io(std::io::Error),
+}
+
+impl fmt::Display for GoldenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GoldenError::MissingReference { name, path } => write!(
+                f,
+                "golden image '{}' has no reference at {}; rerun with {}=1 to create it",
+                name,
+                path.display(),
+                BLESS_ENV
+            ),
+            GoldenError::SizeMismatch {
+                name,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "golden image '{}' is {}x{}, rendered image is {}x{}",
+                name, expected.0, expected.1, actual.0, actual.1
+            ),
+            GoldenError::Mismatch {
+                name,
+                mismatched_pixels,
+                total_pixels,
+                max_channel_delta,
+                diff_path,
+            } => write!(
+                f,
+                "golden image '{}' differs in {}/{} pixels (max channel delta {}), diff written to {}",
+                name,
+                mismatched_pixels,
+                total_pixels,
+                max_channel_delta,
+                diff_path.display()
+            ),
+            GoldenError::Image(err) => write!(f, "image error: {}", err),
+            GoldenError::Io(err) => write!(f, "io error: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for GoldenError {}
+
+impl From<image::ImageError> for GoldenError {
+    fn from(err: image::ImageError) -> Self {
+        GoldenError::Image(err)
+    }
+}
+
+impl From<std::io::Error> for GoldenError {
+    fn from(err: std::io::Error) -> Self {
+        GoldenError::Io(err)
+    }
+}
+
+/// Compares two images of the same size pixel by pixel.
+///
+/// Panics if the sizes differ; use `check_golden` for a reportable error.
+pub fn compare_images(
+    actual: &RgbaImage,
+    expected: &RgbaImage,
+    tolerance: &Tolerance,
+) -> Comparison {
+    assert_eq!(actual.dimensions(), expected.dimensions());
+
+    let (width, height) = expected.dimensions();
+    let mut diff = ImageBuffer::new(width, height);
+    let mut mismatched_pixels = 0;
+    let mut max_channel_delta = 0;
+
+    for (x, y, expected_pixel) in expected.enumerate_pixels() {
+        let actual_pixel = actual.get_pixel(x, y);
+        let delta = expected_pixel
+            .0
+            .iter()
+            .zip(actual_pixel.0.iter())
+            .map(|(e, a)| e.abs_diff(*a))
+            .max()
+            .unwrap_or(0);
+        max_channel_delta = max_channel_delta.max(delta);
+
+        let diff_pixel = if delta > tolerance.max_channel_delta {
+            mismatched_pixels += 1;
+            Rgba([255, 0, 0, 255])
+        } else {
+            let [r, g, b, _] = expected_pixel.0;
+            let luma = ((r as u32 + g as u32 + b as u32) / 3 / 4) as u8;
+            Rgba([luma, luma, luma, 255])
+        };
+        diff.put_pixel(x, y, diff_pixel);
+    }
+
+    Comparison {
+        mismatched_pixels,
+        total_pixels: (width * height) as usize,
+        max_channel_delta,
+        diff,
+    }
+}
+
+/// Directory holding the checked-in reference images.
+pub fn golden_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("golden")
+}
+
+/// Directory failed comparisons write their actual and diff images to.
+pub fn golden_output_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("target")
+        .join("golden")
+}
+
+/// Compares `actual` against `tests/golden/<name>.png`.
+///
+/// If `LUSTRE_BLESS_GOLDEN=1` is set, `actual` is written as the new reference instead. A missing
+/// reference is an error otherwise. On failure, `<name>.actual.png` and `<name>.diff.png` are written to
+/// `target/golden/`.
+pub fn check_golden(
+    name: &str,
+    actual: &RgbaImage,
+    tolerance: &Tolerance,
+) -> Result<(), GoldenError> {
+    let reference_path = golden_dir().join(format!("{}.png", name));
+
+    if env::var(BLESS_ENV).as_deref() == Ok("1") {
+        std::fs::create_dir_all(golden_dir())?;
+        actual.save(&reference_path)?;
+        log_info(&format!(
+            "Wrote golden reference {}",
+            reference_path.display()
+        ));
+        return Ok(());
+    }
+    if !reference_path.exists() {
+        return Err(GoldenError::MissingReference {
+            name: name.to_string(),
+            path: reference_path,
+        });
+    }
+
+    let expected = image::open(&reference_path)?.to_rgba8();
+    if expected.dimensions() != actual.dimensions() {
+        return Err(GoldenError::SizeMismatch {
+            name: name.to_string(),
+            expected: expected.dimensions(),
+            actual: actual.dimensions(),
+        });
+    }
+
+    let comparison = compare_images(actual, &expected, tolerance);
+    if comparison.passes(tolerance) {
+        return Ok(());
+    }
+
+    let output_dir = golden_output_dir();
+    std::fs::create_dir_all(&output_dir)?;
+    let diff_path = output_dir.join(format!("{}.diff.png", name));
+    actual.save(output_dir.join(format!("{}.actual.png", name)))?;
+    comparison.diff.save(&diff_path)?;
+
+    Err(GoldenError::Mismatch {
+        name: name.to_string(),
+        mismatched_pixels: comparison.mismatched_pixels,
+        total_pixels: comparison.total_pixels,
+        max_channel_delta: comparison.max_channel_delta,
+        diff_path,
+    })
+}
@@ -4,7 +4,7 @@
 
 use crate::menu_bar::create_menu_bar;
 use crate::state::{log_info, AppState};
-use crate::utils::{create_text_editor, load_project_area};
+use crate::utils::{create_diagnostics_panel, create_text_editor, load_project_area};
 
 use gtk::{prelude::*, Application, ApplicationWindow, DrawingArea, Grid, Label};
 use gtk4 as gtk;
@@ -56,6 +56,12 @@ pub fn build_ui(app: &Application, state: &Arc<Mutex<AppState>>) -> Arc<Applicat
         state_lock.vulkan_area = Some(vulkan_area.clone());
     }
 
+    // Add diagnostics panel beside the Vulkan area
+    log_info("Creating diagnostics panel...");
+    let diagnostics_panel = create_diagnostics_panel(state);
+    diagnostics_panel.add_css_class("diagnostics-panel");
+    grid.attach(&diagnostics_panel, 0, 2, 1, 1);
+
     // Add menu bar
     log_info("Creating menu bar...");
     let menu_bar = create_menu_bar(state, &window, app);
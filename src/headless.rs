@@ -0,0 +1,273 @@
+// Copyright 2025 Nicholas Jordan. All Rights Reserved.
+// github.com/cvusmo/lustre
+// src/headless.rs
+
+use crate::camera::Camera;
+use crate::render::{
+    camera_uniform, get_camera_set, get_framebuffers, get_graphic_pipeline, get_physical_device,
+    get_render_pass, get_triangle_mesh, get_viewport, record_scene,
+};
+use crate::shaders::{fs, vs};
+use crate::state::log_info;
+
+use image::{ImageBuffer, Rgba};
+use rapier3d::na::Matrix4;
+use std::error::Error;
+use std::path::Path;
+use std::sync::Arc;
+use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage, Subbuffer};
+use vulkano::command_buffer::allocator::{
+    StandardCommandBufferAllocator, StandardCommandBufferAllocatorCreateInfo,
+};
+use vulkano::command_buffer::{
+    AutoCommandBufferBuilder, CommandBufferUsage, CopyImageToBufferInfo, PrimaryAutoCommandBuffer,
+};
+use vulkano::descriptor_set::allocator::StandardDescriptorSetAllocator;
+use vulkano::device::{Device, DeviceCreateInfo, DeviceExtensions, Queue, QueueCreateInfo};
+use vulkano::format::Format;
+use vulkano::image::{Image, ImageCreateInfo, ImageType, ImageUsage};
+use vulkano::instance::{Instance, InstanceCreateFlags, InstanceCreateInfo};
+use vulkano::library::VulkanLibrary;
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryTypeFilter, StandardMemoryAllocator};
+use vulkano::sync::{self, GpuFuture};
+
+/// Format of offscreen color targets; matches the RGBA8 layout written to PNG.
+pub const OFFSCREEN_FORMAT: Format = Format::R8G8B8A8_UNORM;
+
+/// A Vulkan device created without a window or surface.
+///
+/// Works with software drivers (e.g. lavapipe) on machines without a display.
+pub struct HeadlessContext {
+    pub device: Arc<Device>,
+    pub queue: Arc<Queue>,
+    pub memory_allocator: Arc<StandardMemoryAllocator>,
+    pub command_buffer_allocator: Arc<StandardCommandBufferAllocator>,
+    pub descriptor_set_allocator: Arc<StandardDescriptorSetAllocator>,
+}
+
+impl HeadlessContext {
+    /// Creates an instance and device with no surface requirement.
+    pub fn new() -> Result<Self, Box<dyn Error>> {
+        let library = VulkanLibrary::new()?;
+        let instance = Instance::new(
+            library,
+            InstanceCreateInfo {
+                flags: InstanceCreateFlags::ENUMERATE_PORTABILITY,
+                ..Default::default()
+            },
+        )?;
+
+        let device_extensions = DeviceExtensions::empty();
+        let (physical_device, queue_family_index) =
+            get_physical_device(&instance, None, &device_extensions)
+                .ok_or("no Vulkan device with a graphics queue available")?;
+
+        log_info(&format!(
+            "Headless physical device is: {}",
+            physical_device.properties().device_name
+        ));
+
+        let (device, mut queues) = Device::new(
+            physical_device,
+            DeviceCreateInfo {
+                queue_create_infos: vec![QueueCreateInfo {
+                    queue_family_index,
+                    ..Default::default()
+                }],
+                enabled_extensions: device_extensions,
+                ..Default::default()
+            },
+        )?;
+        let queue = queues.next().ok_or("device returned no queues")?;
+
+        let memory_allocator = Arc::new(StandardMemoryAllocator::new_default(device.clone()));
+        let command_buffer_allocator = Arc::new(StandardCommandBufferAllocator::new(
+            device.clone(),
+            StandardCommandBufferAllocatorCreateInfo::default(),
+        ));
+        let descriptor_set_allocator = Arc::new(StandardDescriptorSetAllocator::new(
+            device.clone(),
+            Default::default(),
+        ));
+
+        Ok(Self {
+            device,
+            queue,
+            memory_allocator,
+            command_buffer_allocator,
+            descriptor_set_allocator,
+        })
+    }
+
+    /// Creates a new primary command buffer builder on this context's queue.
+    pub fn command_buffer_builder(
+        &self,
+    ) -> Result<AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>, Box<dyn Error>> {
+        Ok(AutoCommandBufferBuilder::primary(
+            self.command_buffer_allocator.clone(),
+            self.queue.queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )?)
+    }
+
+    /// Submits a command buffer and blocks until the GPU has finished it.
+    pub fn submit_and_wait(
+        &self,
+        builder: AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+    ) -> Result<(), Box<dyn Error>> {
+        let command_buffer = builder.build()?;
+        sync::now(self.device.clone())
+            .then_execute(self.queue.clone(), command_buffer)?
+            .then_signal_fence_and_flush()?
+            .wait(None)?;
+        Ok(())
+    }
+
+    /// Creates a 2D image in device memory.
+    pub fn create_image(
+        &self,
+        format: Format,
+        width: u32,
+        height: u32,
+        usage: ImageUsage,
+    ) -> Result<Arc<Image>, Box<dyn Error>> {
+        Ok(Image::new(
+            self.memory_allocator.clone(),
+            ImageCreateInfo {
+                image_type: ImageType::Dim2d,
+                format,
+                extent: [width, height, 1],
+                usage,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                memory_type_filter: MemoryTypeFilter::PREFER_DEVICE,
+                ..Default::default()
+            },
+        )?)
+    }
+
+    /// Creates a host-visible buffer that images can be copied into and read back from.
+    pub fn create_readback_buffer(&self, len: u64) -> Result<Subbuffer<[u8]>, Box<dyn Error>> {
+        Ok(Buffer::new_slice::<u8>(
+            self.memory_allocator.clone(),
+            BufferCreateInfo {
+                usage: BufferUsage::TRANSFER_DST,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                memory_type_filter: MemoryTypeFilter::PREFER_HOST
+                    | MemoryTypeFilter::HOST_RANDOM_ACCESS,
+                ..Default::default()
+            },
+            len,
+        )?)
+    }
+
+    /// Records a copy of an RGBA8 image into a new readback buffer.
+    pub fn copy_image_to_buffer(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        image: Arc<Image>,
+    ) -> Result<Subbuffer<[u8]>, Box<dyn Error>> {
+        let [width, height, _] = image.extent();
+        let buffer = self.create_readback_buffer(width as u64 * height as u64 * 4)?;
+        builder.copy_image_to_buffer(CopyImageToBufferInfo::image_buffer(image, buffer.clone()))?;
+        Ok(buffer)
+    }
+}
+
+/// Reads an RGBA8 readback buffer into an image.
+pub fn read_image(
+    buffer: &Subbuffer<[u8]>,
+    width: u32,
+    height: u32,
+) -> Result<ImageBuffer<Rgba<u8>, Vec<u8>>, Box<dyn Error>> {
+    let content = buffer.read()?;
+    ImageBuffer::from_raw(width, height, content.to_vec())
+        .ok_or_else(|| "readback buffer is smaller than the image".into())
+}
+
+/// Renders the scene offscreen with the given camera matrices and returns the resulting pixels.
+pub fn render_offscreen(
+    context: &HeadlessContext,
+    width: u32,
+    height: u32,
+    view: &Matrix4<f32>,
+    proj: &Matrix4<f32>,
+) -> Result<ImageBuffer<Rgba<u8>, Vec<u8>>, Box<dyn Error>> {
+    let device = context.device.clone();
+
+    // Offscreen color target
+    let image = context.create_image(
+        OFFSCREEN_FORMAT,
+        width,
+        height,
+        ImageUsage::COLOR_ATTACHMENT | ImageUsage::TRANSFER_SRC,
+    )?;
+
+    let render_pass = get_render_pass(device.clone(), OFFSCREEN_FORMAT);
+    let framebuffers = get_framebuffers(
+        std::slice::from_ref(&image),
+        render_pass.clone(),
+        context.memory_allocator.clone(),
+    );
+    let viewport = get_viewport([width, height]);
+
+    let vs_module = vs::load(device.clone())?;
+    let fs_module = fs::load(device.clone())?;
+    let graphic_pipeline = get_graphic_pipeline(device, vs_module, fs_module, render_pass);
+
+    let triangle = get_triangle_mesh(context.memory_allocator.clone());
+
+    let camera_buffer = Buffer::from_data(
+        context.memory_allocator.clone(),
+        BufferCreateInfo {
+            usage: BufferUsage::UNIFORM_BUFFER,
+            ..Default::default()
+        },
+        AllocationCreateInfo {
+            memory_type_filter: MemoryTypeFilter::PREFER_DEVICE
+                | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
+            ..Default::default()
+        },
+        camera_uniform(view, proj),
+    )?;
+    let camera_set = get_camera_set(
+        context.descriptor_set_allocator.clone(),
+        &graphic_pipeline,
+        camera_buffer,
+    );
+
+    // Draw, then copy the color target to host memory.
+    let mut builder = context.command_buffer_builder()?;
+    record_scene(
+        &mut builder,
+        &graphic_pipeline,
+        &framebuffers[0],
+        &viewport,
+        camera_set,
+        [&triangle],
+    );
+    let buffer = context.copy_image_to_buffer(&mut builder, image)?;
+    context.submit_and_wait(builder)?;
+
+    read_image(&buffer, width, height)
+}
+
+/// Renders the scene offscreen from the default camera and writes it to a PNG file.
+pub fn render_to_png(path: &Path, width: u32, height: u32) -> Result<(), Box<dyn Error>> {
+    let context = HeadlessContext::new()?;
+    let camera = Camera::default();
+    let image = render_offscreen(
+        &context,
+        width,
+        height,
+        &camera.view_matrix(),
+        &camera.projection_matrix(width as f32 / height as f32),
+    )?;
+    image.save(path)?;
+
+    log_info(&format!("Headless render saved to {}", path.display()));
+    Ok(())
+}
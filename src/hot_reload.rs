@@ -0,0 +1,154 @@
+// Copyright 2025 Nicholas Jordan. All Rights Reserved.
+// github.com/cvusmo/lustre
+// src/hot_reload.rs
+
+use crate::events;
+use crate::state::log_warn;
+
+use mlua::prelude::*;
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver};
+use std::time::{Duration, Instant};
+
+// Extensions of the files that trigger a reload.
+const SCRIPT_EXTENSIONS: [&str; 2] = ["lua", "toml"];
+
+// How long a file has to stay untouched before it's reported, so editors that save in
+// several writes only cause one reload.
+const SETTLE_TIME: Duration = Duration::from_millis(100);
+
+/// Reports scripts and manifests that changed on disk.
+///
+/// Files are watched through their directory, so editors that save by replacing the file
+/// are still noticed. Paths are reported canonicalized.
+pub struct ScriptWatcher {
+    watcher: RecommendedWatcher,
+    events: Receiver<notify::Result<Event>>,
+    watched: BTreeSet<PathBuf>,
+    // Changed files and when they last changed.
+    pending: BTreeMap<PathBuf, Instant>,
+}
+
+impl ScriptWatcher {
+    pub fn new() -> notify::Result<Self> {
+        let (sender, events) = channel();
+        let watcher = notify::recommended_watcher(move |event| {
+            let _ = sender.send(event);
+        })?;
+        Ok(Self {
+            watcher,
+            events,
+            watched: BTreeSet::new(),
+            pending: BTreeMap::new(),
+        })
+    }
+
+    /// Watches a directory and everything under it, or a single file.
+    pub fn watch(&mut self, path: &Path) -> notify::Result<()> {
+        let path = fs::canonicalize(path)?;
+        let (dir, mode) = if path.is_dir() {
+            (path, RecursiveMode::Recursive)
+        } else {
+            match path.parent() {
+                Some(parent) => (parent.to_path_buf(), RecursiveMode::NonRecursive),
+                None => return Ok(()),
+            }
+        };
+        if self.watched.contains(&dir) {
+            return Ok(());
+        }
+        self.watcher.watch(&dir, mode)?;
+        self.watched.insert(dir);
+        Ok(())
+    }
+
+    /// Scripts and manifests that changed and have since settled.
+    pub fn changed(&mut self) -> Vec<PathBuf> {
+        let now = Instant::now();
+        for event in self.events.try_iter() {
+            match event {
+                Ok(event) if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) => {
+                    for path in event.paths {
+                        let is_script = path
+                            .extension()
+                            .and_then(|s| s.to_str())
+                            .is_some_and(|extension| SCRIPT_EXTENSIONS.contains(&extension));
+                        if is_script {
+                            self.pending.insert(path, now);
+                        }
+                    }
+                }
+                Ok(_) => (),
+                Err(err) => log_warn(&format!("File watcher error: {}", err)),
+            }
+        }
+
+        let settled: Vec<PathBuf> = self
+            .pending
+            .iter()
+            .filter(|(_, changed)| now.duration_since(**changed) >= SETTLE_TIME)
+            .map(|(path, _)| path.clone())
+            .collect();
+        for path in &settled {
+            self.pending.remove(path);
+        }
+        settled
+    }
+}
+
+/// Points the tables `new` defines back at the tables of the same name in `old`.
+///
+/// The old tables get the new tables' functions and replace them in `new`, so anything
+/// still holding an old table, such as an entity's behaviour, runs the new code. Data in
+/// the old tables is kept.
+pub(crate) fn carry_over_tables(old: &LuaTable, new: &LuaTable) -> LuaResult<()> {
+    let mut replaced = Vec::new();
+    for (key, value) in new.pairs::<LuaValue, LuaValue>().flatten() {
+        let LuaValue::Table(table) = value else {
+            continue;
+        };
+        // `_G` is the environment itself, not a table the script defined.
+        if key.as_str().is_some_and(|key| key == "_G") {
+            continue;
+        }
+        if let Ok(previous) = old.raw_get::<LuaTable>(key.clone()) {
+            if previous != table {
+                replaced.push((key, previous, table));
+            }
+        }
+    }
+
+    for (key, previous, table) in replaced {
+        for (name, value) in table.pairs::<LuaValue, LuaValue>().flatten() {
+            if let LuaValue::Function(function) = value {
+                previous.raw_set(name, function)?;
+            }
+        }
+        new.raw_set(key, previous)?;
+    }
+    Ok(())
+}
+
+/// Reruns a script in the global environment, keeping the identity of global tables it
+/// redefines and replacing the event handlers it subscribed, as
+/// [`crate::mods::reload_mod`] does for mods.
+pub fn reload_script(lua: &Lua, path: &Path) -> LuaResult<()> {
+    let code = fs::read_to_string(path).map_err(LuaError::external)?;
+    let chunk = lua
+        .load(&code)
+        .set_name(format!("@{}", path.display()))
+        .into_function()?;
+
+    let globals = lua.globals();
+    let previous = lua.create_table()?;
+    for (key, value) in globals.pairs::<LuaValue, LuaValue>().flatten() {
+        if let LuaValue::Table(table) = value {
+            previous.raw_set(key, table)?;
+        }
+    }
+    events::run_script(lua, path, || chunk.call::<()>(()))?;
+    carry_over_tables(&previous, &globals)
+}
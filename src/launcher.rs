@@ -3,12 +3,12 @@
 // src/launcher.rs
 
 use crate::gui::build_ui;
-use crate::state::{create_state, log_info};
+use crate::state::{log_info, AppState};
 use gtk4::prelude::*;
 use gtk4::Application;
 use std::sync::{Arc, Mutex};
 
-pub fn launcher() {
+pub fn launcher(state: Arc<Mutex<AppState>>) {
     log_info("Launching lustre...");
 
     // Create GTK app
@@ -16,9 +16,6 @@ pub fn launcher() {
         .application_id("org.cvusmo.lustre")
         .build();
 
-    // Create app state
-    let state = create_state();
-
     // Activate signal
     app.connect_activate(move |app| {
         let window = build_ui(app, &state);
@@ -2,16 +2,40 @@
 // github.com/cvusmo/lustre
 // src/lib.rs
 
-pub mod gui;
-pub mod menu_bar;
-pub mod render;
-pub mod state;
-// pub mod physics;
+pub mod block;
+pub mod camera;
+pub mod camera_controller;
+pub mod character;
+pub mod chunk;
+pub mod clock;
+pub mod compute;
+pub mod diagnostics;
+pub mod events;
 pub mod file_explorer;
+pub mod game;
+pub mod golden;
+pub mod gui;
+pub mod headless;
+pub mod hot_reload;
 pub mod launcher;
 pub mod lua_editor;
+pub mod menu_bar;
+pub mod mesher;
+pub mod mods;
+pub mod physics;
+pub mod planet;
+pub mod raycast;
+pub mod region;
+pub mod render;
+pub mod save;
+pub mod scripting;
+pub mod settings;
 pub mod shaders;
+pub mod state;
+pub mod streaming;
+pub mod terrain;
 pub mod utils;
 pub mod window;
+pub mod world;
 
 pub use state::AppState;
@@ -2,14 +2,32 @@
 // github.com/cvusmo/lustre
 //src/lua_editor.rs
 
-use crate::state::{log_error, log_info, AppState};
-use crate::utils::{create_text_editor, execute_lua_script};
-use crate::window::lustre_window;
+use crate::clock;
+use crate::diagnostics::{self, chunk_name, Diagnostic};
+use crate::events;
+use crate::game::GameLoop;
+use crate::hot_reload::{reload_script, ScriptWatcher};
+use crate::mods;
+use crate::raycast;
+use crate::scripting::{self, ScriptLimits};
+use crate::state::{log_error, log_info, log_warn, AppState};
+use crate::terrain::{self, GeneratorRegistry};
+use crate::utils::{add_diagnostics, create_text_editor, editor_chunk_name, execute_lua_script};
+use crate::window::{lustre_window, lustre_window_with};
+use crate::world::{self, World};
+use glib::source::timeout_add_local;
 use gtk4::prelude::*;
 use gtk4::ScrolledWindow;
 use mlua::prelude::*;
+use std::cell::RefCell;
 use std::fs;
+use std::path::PathBuf;
+use std::rc::Rc;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+// How often the editor checks for changed scripts while the render window is closed.
+const RELOAD_INTERVAL: Duration = Duration::from_millis(250);
 
 /// Registers basic Lua functions (e.g. print_message) with the provided Lua context.
 pub fn register_lua_functions(lua: &Lua, _state: Arc<Mutex<AppState>>) -> LuaResult<()> {
@@ -22,9 +40,14 @@ pub fn register_lua_functions(lua: &Lua, _state: Arc<Mutex<AppState>>) -> LuaRes
 }
 
 /// Registers the Vulkan render function so that Lua can trigger it.
-fn register_render_functions(lua: &Lua) -> LuaResult<()> {
-    let launch_fn = lua.create_function(|_, ()| {
-        launch_vulkan_render();
+fn register_render_functions(
+    lua: &Lua,
+    world: Rc<RefCell<World>>,
+    game: Rc<RefCell<GameLoop>>,
+    generators: Rc<RefCell<GeneratorRegistry>>,
+) -> LuaResult<()> {
+    let launch_fn = lua.create_function(move |_, ()| {
+        lustre_window_with(world.clone(), game.clone(), generators.clone());
         Ok(())
     })?;
     lua.globals().set("launch_render", launch_fn)?;
@@ -35,33 +58,163 @@ fn register_render_functions(lua: &Lua) -> LuaResult<()> {
 pub fn register_all(lua: &Lua, state: Arc<Mutex<AppState>>) -> LuaResult<()> {
     // Register basic functions.
     register_lua_functions(lua, state.clone())?;
+    let (generators, world, shared_clock, game, bus) = {
+        let state = state.lock().unwrap();
+        (
+            state.generators.clone(),
+            state.world.clone(),
+            state.clock.clone(),
+            state.game.clone(),
+            state.events.clone(),
+        )
+    };
     // Register the Vulkan render trigger.
-    register_render_functions(lua)?;
+    let entities = game.borrow().shared_world();
+    register_render_functions(lua, world.clone(), game, generators.clone())?;
+    // Keep errors scripts raise outside the editor for the diagnostics panel.
+    diagnostics::collect_reports(lua);
+    // Register event subscriptions; the APIs below emit to the same bus.
+    events::register_lua_api(lua, bus)?;
+    // Register the terrain generator API.
+    terrain::register_lua_api(lua, generators)?;
+    // Register block reads, edits and registry lookups, then raycasts.
+    world::register_lua_api(lua, world.clone())?;
+    raycast::register_lua_api(lua, world)?;
+    // Register pausing, stepping and time scale.
+    clock::register_lua_api(lua, shared_clock)?;
+    // Register entity spawning, components, queries and behaviour scripts.
+    scripting::register_lua_api(lua, entities)?;
     Ok(())
 }
 
-/// Loads additional Lua modules from the "./mods" directory.
-pub fn load_mods(lua: &Lua, _state: &Arc<Mutex<AppState>>) {
-    let mod_path = "./mods";
-    if let Ok(paths) = fs::read_dir(mod_path) {
-        for entry in paths.flatten() {
-            let script_path = entry.path();
-            if script_path.extension().and_then(|s| s.to_str()) == Some("lua") {
-                if let Ok(script_content) = fs::read_to_string(&script_path) {
-                    if let Err(e) = lua.load(&script_content).exec() {
-                        log_error(&format!(
-                            "Failed to load Lua script {:?}: {}",
-                            script_path, e
-                        ));
+/// Loads the mods in the directory named by the state's mod settings, each in its own
+/// sandbox, and keeps them in the state.
+pub fn load_mods(lua: &Lua, state: &Arc<Mutex<AppState>>) {
+    let settings = state.lock().unwrap().mod_settings.clone();
+    let loaded = mods::load_mods(lua, &settings);
+    state.lock().unwrap().mods = loaded;
+}
+
+// Reloads mods and the open project file when they change on disk.
+struct ScriptReloader {
+    watcher: ScriptWatcher,
+    lua: Lua,
+    state: Arc<Mutex<AppState>>,
+    limits: ScriptLimits,
+    project: Option<PathBuf>,
+}
+
+impl ScriptReloader {
+    fn poll(&mut self) {
+        // Scripts may be running with the state locked; try again next time.
+        let Ok(mut state) = self.state.try_lock() else {
+            return;
+        };
+        if state.project_path != self.project {
+            self.project = state.project_path.clone();
+            if let Some(project) = &self.project {
+                if let Err(err) = self.watcher.watch(project) {
+                    log_warn(&format!("Not watching {}: {}", project.display(), err));
+                }
+            }
+        }
+        let changed = self.watcher.changed();
+        if changed.is_empty() {
+            drop(state);
+            self.show_reports();
+            return;
+        }
+        // Reloaded scripts may need the state themselves.
+        let mut loaded_mods = std::mem::take(&mut state.mods);
+        drop(state);
+
+        let project = self
+            .project
+            .as_ref()
+            .and_then(|path| fs::canonicalize(path).ok());
+        let mut reloaded = Vec::new();
+        for path in changed {
+            if let Some(loaded) = loaded_mods
+                .iter_mut()
+                .find(|loaded| fs::canonicalize(&loaded.dir).is_ok_and(|dir| path.starts_with(dir)))
+            {
+                if reloaded.contains(&loaded.dir) {
+                    continue;
+                }
+                reloaded.push(loaded.dir.clone());
+                match mods::reload_mod(&self.lua, loaded, &self.limits) {
+                    Ok(()) => log_info(&format!("Reloaded mod {}", loaded.id())),
+                    Err(err) => {
+                        log_error(&format!("Failed to reload mod {}: {}", loaded.id(), err));
+                        diagnostics::report(&self.lua, Diagnostic::from_error(&err));
+                    }
+                }
+            } else if project.as_ref() == Some(&path) {
+                match reload_script(&self.lua, &path) {
+                    Ok(()) => log_info(&format!("Reloaded {}", path.display())),
+                    Err(err) => {
+                        log_error(&format!("Failed to reload {}: {}", path.display(), err));
+                        diagnostics::report(&self.lua, Diagnostic::from_error(&err));
                     }
                 }
             }
         }
-    } else {
-        log_error("Failed to read mods directory.");
+        self.state.lock().unwrap().mods = loaded_mods;
+        self.show_reports();
+    }
+
+    // Adds errors scripts reported since the last poll to the diagnostics panel.
+    fn show_reports(&self) {
+        let reports = diagnostics::take_reports(&self.lua);
+        if !reports.is_empty() {
+            add_diagnostics(&self.state, reports);
+        }
     }
 }
 
+/// Reloads mods and the open project file in place when they change on disk.
+///
+/// Changes are picked up on a timer while the editor is idle and every frame while the
+/// render window is open, when errors scripts reported in the meantime are also added to
+/// the diagnostics panel. See [`mods::reload_mod`] for how mods carry their state over.
+pub fn watch_scripts(state: &Arc<Mutex<AppState>>) {
+    let (lua, game, settings) = {
+        let state = state.lock().unwrap();
+        let lua = state.lua.lock().unwrap().clone();
+        (lua, state.game.clone(), state.mod_settings.clone())
+    };
+    let mut watcher = match ScriptWatcher::new() {
+        Ok(watcher) => watcher,
+        Err(err) => {
+            log_error(&format!("Scripts won't hot reload: {}", err));
+            return;
+        }
+    };
+    if let Err(err) = watcher.watch(&settings.dir) {
+        log_warn(&format!("Not watching {}: {}", settings.dir.display(), err));
+    }
+
+    let reloader = Rc::new(RefCell::new(ScriptReloader {
+        watcher,
+        lua,
+        state: state.clone(),
+        limits: settings.limits,
+        project: None,
+    }));
+    let idle = reloader.clone();
+    timeout_add_local(RELOAD_INTERVAL, move || {
+        if let Ok(mut reloader) = idle.try_borrow_mut() {
+            reloader.poll();
+        }
+        glib::ControlFlow::Continue
+    });
+    game.borrow_mut().add_frame_hook(move || {
+        if let Ok(mut reloader) = reloader.try_borrow_mut() {
+            reloader.poll();
+        }
+    });
+}
+
 /// Creates a Lua editor widget.
 pub fn create_lua_editor(content: &str, state: &Arc<Mutex<AppState>>) -> ScrolledWindow {
     create_text_editor(content, state)
@@ -69,14 +222,17 @@ pub fn create_lua_editor(content: &str, state: &Arc<Mutex<AppState>>) -> Scrolle
 
 /// Runs a Lua script that is currently in the editor.
 pub fn run_lua_from_editor(state: &Arc<Mutex<AppState>>) {
-    let script_content = {
+    let (script_content, chunk_name) = {
         // Lock state, read text view, then drop lock.
         let state_lock = state.lock().unwrap();
         if let Some(ref text_view) = state_lock.text_view {
             let buffer = text_view.buffer();
             let start = buffer.start_iter();
             let end = buffer.end_iter();
-            buffer.text(&start, &end, true).to_string()
+            (
+                buffer.text(&start, &end, true).to_string(),
+                editor_chunk_name(&state_lock),
+            )
         } else {
             log_error("No text view found in current project.");
             return;
@@ -84,7 +240,7 @@ pub fn run_lua_from_editor(state: &Arc<Mutex<AppState>>) {
     };
 
     // Execute the Lua script.
-    execute_lua_script(state, &script_content);
+    execute_lua_script(state, &script_content, &chunk_name);
 }
 
 /// Runs a Lua script from a file specified in the AppState.
@@ -109,7 +265,7 @@ pub fn run_lua_script(state: &Arc<Mutex<AppState>>) {
         }
     };
 
-    execute_lua_script(state, &script_content);
+    execute_lua_script(state, &script_content, &chunk_name(&script_path));
 }
 
 /// Launches the Vulkan render window.
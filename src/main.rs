@@ -2,23 +2,68 @@
 // github.com/cvusmo/lustre
 // src/main.rs
 
+use clap::{value_parser, Arg, Command};
 use log::LevelFilter;
+use lustre::headless::render_to_png;
 use lustre::launcher::launcher;
-use lustre::lua_editor::register_all;
-use lustre::state::{create_state, initialize_state};
+use lustre::lua_editor::{load_mods, register_all, watch_scripts};
+use lustre::state::{create_state, initialize_state, log_error};
 use lustre::window::lustre_window;
-use mlua::Lua;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+fn main() -> ExitCode {
+    let matches = Command::new("lustre")
+        .about("lustre voxel game engine")
+        .arg(
+            Arg::new("headless")
+                .long("headless")
+                .value_name("PNG")
+                .value_parser(value_parser!(PathBuf))
+                .help("Render offscreen without a window and save the frame to a PNG"),
+        )
+        .arg(
+            Arg::new("width")
+                .long("width")
+                .value_parser(value_parser!(u32))
+                .default_value("1024")
+                .help("Width of the headless render"),
+        )
+        .arg(
+            Arg::new("height")
+                .long("height")
+                .value_parser(value_parser!(u32))
+                .default_value("1024")
+                .help("Height of the headless render"),
+        )
+        .get_matches();
 
-fn main() {
     //TODO: initialize_state
     initialize_state("lustre.log", LevelFilter::Info).expect("Failed to initalize state");
 
-    let state = create_state();
+    // Headless mode: render a single frame to disk and exit.
+    if let Some(path) = matches.get_one::<PathBuf>("headless") {
+        let width = *matches.get_one::<u32>("width").unwrap();
+        let height = *matches.get_one::<u32>("height").unwrap();
+        return match render_to_png(path, width, height) {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(err) => {
+                log_error(&format!("Headless render failed: {}", err));
+                ExitCode::FAILURE
+            }
+        };
+    }
 
-    let lua = Lua::new();
+    let state = create_state();
 
-    register_all(&lua, state.clone()).expect("failed to register Lua functions.");
+    // Register with the Lua the editor runs scripts in.
+    let lua = state.lock().unwrap().lua.clone();
+    register_all(&lua.lock().unwrap(), state.clone()).expect("failed to register Lua functions.");
+    load_mods(&lua.lock().unwrap(), &state);
+    watch_scripts(&state);
 
-    launcher();
+    launcher(state);
     // lustre_window();
+
+    ExitCode::SUCCESS
 }
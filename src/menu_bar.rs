@@ -6,10 +6,9 @@
 
 use crate::file_explorer::open_file;
 use crate::lua_editor::{create_lua_editor, run_lua_script};
-use crate::render::lustre_render;
-use crate::state::{log_error, log_info, AppState};
+use crate::state::{log_info, AppState};
 use crate::utils::{handle_exit, load_project_area, save_as_file, save_file};
-use crate::window::lustre_window;
+use crate::window::lustre_window_with;
 
 use gtk4::prelude::*;
 use gtk4::{
@@ -123,34 +122,64 @@ pub fn create_menu_bar(
     // Render Project Button
     let render_button = Button::with_label("Render");
     project_box.append(&render_button);
-    render_button.connect_clicked({
-        let state_clone = Arc::clone(state);
-        move |_| {
-            // Retrieve the Vulkan instance and surface from AppState.
-            let (maybe_instance, maybe_surface) = {
-                let state_lock = state_clone.lock().unwrap();
-                (
-                    state_lock.vulkan_instance.clone(),
-                    state_lock.vulkan_surface.clone(),
-                )
-            };
-            if let (Some(instance), Some(surface)) = (maybe_instance, maybe_surface) {
-                lustre_render(instance.clone(), surface.clone());
-            } else {
-                log_error("Vulkan instance or surface not available.");
-            }
-
-            lustre_window();
-        }
+    let (clock, world, game, generators) = {
+        let state = state.lock().unwrap();
+        (
+            state.clock.clone(),
+            state.world.clone(),
+            state.game.clone(),
+            state.generators.clone(),
+        )
+    };
+    render_button.connect_clicked(move |_| {
+        log_info("Launching render window...");
+        lustre_window_with(world.clone(), game.clone(), generators.clone());
     });
 
     project_popover.set_child(Some(&project_box));
     project_button.set_popover(Some(&project_popover));
 
+    // Simulation Button
+    let simulation_button = MenuButton::builder().label("Simulation").build();
+    simulation_button.add_css_class("menu-button");
+    let simulation_popover = Popover::new();
+    let simulation_box = GtkBox::new(Orientation::Vertical, 5);
+
+    // Pause / Resume button
+    let pause_button = Button::with_label("Pause / Resume");
+    simulation_box.append(&pause_button);
+    let clock_pause = clock.clone();
+    pause_button.connect_clicked(move |_| {
+        clock_pause.borrow_mut().toggle_pause();
+    });
+
+    // Step button, which runs a single tick while paused
+    let step_button = Button::with_label("Step");
+    simulation_box.append(&step_button);
+    let clock_step = clock.clone();
+    step_button.connect_clicked(move |_| {
+        clock_step.borrow_mut().step(1);
+    });
+
+    // Time scale buttons
+    for time_scale in [0.25, 0.5, 1.0, 2.0, 4.0] {
+        let speed_button = Button::with_label(&format!("Speed {}x", time_scale));
+        simulation_box.append(&speed_button);
+        let clock_speed = clock.clone();
+        speed_button.connect_clicked(move |_| {
+            log_info(&format!("Time scale {}x", time_scale));
+            clock_speed.borrow_mut().set_time_scale(time_scale);
+        });
+    }
+
+    simulation_popover.set_child(Some(&simulation_box));
+    simulation_button.set_popover(Some(&simulation_popover));
+
     // Add buttons to menu bar
     menu_bar.append(&file_button);
     menu_bar.append(&edit_button);
     menu_bar.append(&project_button);
+    menu_bar.append(&simulation_button);
 
     log_info("Menu bar created successfully.");
     menu_bar
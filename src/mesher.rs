@@ -0,0 +1,562 @@
+// Copyright 2025 Nicholas Jordan. All Rights Reserved.
+// github.com/cvusmo/lustre
+// src/mesher.rs
+
+use crate::block::{BlockId, BlockRegistry, AIR};
+use crate::chunk::{Chunk, CHUNK_SIZE};
+use crate::render::MainVertex;
+use crate::state::log_error;
+use crate::world::{BlockPos, ChunkPos, LodPos, World};
+
+use rapier3d::na::{Isometry3, Point3, Vector3};
+use std::collections::HashMap;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+// Chunk plus a one-block border on every side, so faces and AO can see neighbours.
+const PADDED_SIZE: usize = CHUNK_SIZE + 2;
+
+// Brightness for 0..=3 unoccluded neighbours around a vertex.
+const AO_BRIGHTNESS: [f32; 4] = [0.45, 0.65, 0.82, 1.0];
+
+/// Per-block data the mesher needs, copied out of the registry so it can cross threads.
+#[derive(Debug, Clone, Copy)]
+pub struct BlockMeshInfo {
+    /// Hides faces behind it and darkens corners next to it.
+    pub opaque: bool,
+    pub color: [f32; 3],
+}
+
+/// Mesh info for every registered block, indexed by block id.
+#[derive(Debug, Clone)]
+pub struct MeshPalette {
+    blocks: Vec<BlockMeshInfo>,
+}
+
+impl MeshPalette {
+    pub fn from_registry(registry: &BlockRegistry) -> Self {
+        Self {
+            blocks: registry
+                .iter()
+                .map(|block| BlockMeshInfo {
+                    opaque: block.id != AIR && !block.properties.transparent,
+                    color: block.properties.color,
+                })
+                .collect(),
+        }
+    }
+
+    // Unknown ids are treated like air.
+    fn get(&self, block: BlockId) -> BlockMeshInfo {
+        self.blocks
+            .get(block as usize)
+            .copied()
+            .unwrap_or(BlockMeshInfo {
+                opaque: false,
+                color: [0.0; 3],
+            })
+    }
+
+    fn is_opaque(&self, block: BlockId) -> bool {
+        self.get(block).opaque
+    }
+}
+
+/// Copy of a chunk's blocks plus a one-block border taken from its neighbours.
+///
+/// Taken on the thread that owns the world, then meshed on a worker.
+#[derive(Debug, Clone)]
+pub struct ChunkSample {
+    origin: BlockPos,
+    // World blocks per sampled block; above 1 for level-of-detail chunks.
+    scale: i32,
+    blocks: Vec<BlockId>,
+}
+
+impl ChunkSample {
+    /// Samples a chunk and its border from the world. Missing neighbours read as air.
+    pub fn from_world(world: &World, pos: ChunkPos) -> Self {
+        let origin = pos.origin();
+        let mut blocks = vec![AIR; PADDED_SIZE * PADDED_SIZE * PADDED_SIZE];
+        let chunk = world.chunk(pos);
+
+        for y in 0..PADDED_SIZE {
+            for z in 0..PADDED_SIZE {
+                for x in 0..PADDED_SIZE {
+                    let interior = (1..=CHUNK_SIZE).contains(&x)
+                        && (1..=CHUNK_SIZE).contains(&y)
+                        && (1..=CHUNK_SIZE).contains(&z);
+                    let block = match chunk {
+                        Some(chunk) if interior => chunk.get(x - 1, y - 1, z - 1),
+                        _ => {
+                            world.get_block(origin.offset(x as i32 - 1, y as i32 - 1, z as i32 - 1))
+                        }
+                    };
+                    blocks[Self::index(x, y, z)] = block;
+                }
+            }
+        }
+
+        Self {
+            origin,
+            scale: 1,
+            blocks,
+        }
+    }
+
+    /// Samples a standalone chunk whose blocks each cover `scale`³ world blocks from `origin`.
+    ///
+    /// The border repeats the chunk's edge blocks, so no faces are built along the edges.
+    /// Used for level-of-detail chunks, which have no loaded neighbours.
+    pub fn from_chunk(chunk: &Chunk, origin: BlockPos, scale: i32) -> Self {
+        let mut blocks = vec![AIR; PADDED_SIZE * PADDED_SIZE * PADDED_SIZE];
+        let clamp = |i: usize| i.clamp(1, CHUNK_SIZE) - 1;
+        for y in 0..PADDED_SIZE {
+            for z in 0..PADDED_SIZE {
+                for x in 0..PADDED_SIZE {
+                    blocks[Self::index(x, y, z)] = chunk.get(clamp(x), clamp(y), clamp(z));
+                }
+            }
+        }
+
+        Self {
+            origin,
+            scale,
+            blocks,
+        }
+    }
+
+    fn index(x: usize, y: usize, z: usize) -> usize {
+        x + z * PADDED_SIZE + y * PADDED_SIZE * PADDED_SIZE
+    }
+
+    // Block at a chunk-local position; -1 and CHUNK_SIZE address the border.
+    fn get(&self, pos: [i32; 3]) -> BlockId {
+        self.blocks[Self::index(
+            (pos[0] + 1) as usize,
+            (pos[1] + 1) as usize,
+            (pos[2] + 1) as usize,
+        )]
+    }
+}
+
+/// Triangle mesh for one chunk, in world coordinates.
+#[derive(Debug, Clone, Default)]
+pub struct ChunkMesh {
+    pub vertices: Vec<MainVertex>,
+    pub indices: Vec<u32>,
+}
+
+impl ChunkMesh {
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty()
+    }
+
+    /// Number of quads (two triangles each) in the mesh.
+    pub fn quad_count(&self) -> usize {
+        self.indices.len() / 6
+    }
+}
+
+// Faces are merged only when both the block and the corner occlusion match,
+// so merged quads keep correct ambient occlusion.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct FaceKey {
+    block: BlockId,
+    ao: [u8; 4],
+}
+
+// Occlusion level for one vertex: 0 is fully occluded, 3 is open.
+fn vertex_ao(side1: bool, side2: bool, corner: bool) -> u8 {
+    if side1 && side2 {
+        0
+    } else {
+        3 - (side1 as u8 + side2 as u8 + corner as u8)
+    }
+}
+
+/// Builds a greedy-meshed, face-culled mesh with per-vertex ambient occlusion.
+pub fn mesh_chunk(sample: &ChunkSample, palette: &MeshPalette) -> ChunkMesh {
+    let mut mesh = ChunkMesh::default();
+    let size = CHUNK_SIZE as i32;
+    let mut mask: Vec<Option<FaceKey>> = vec![None; CHUNK_SIZE * CHUNK_SIZE];
+
+    for axis in 0..3 {
+        // u and v span the face plane; u x v points along +axis.
+        let u_axis = (axis + 1) % 3;
+        let v_axis = (axis + 2) % 3;
+
+        for sign in [1i32, -1] {
+            for slice in 0..size {
+                // Collect the visible faces of this slice.
+                for v in 0..size {
+                    for u in 0..size {
+                        let mut cell = [0i32; 3];
+                        cell[axis] = slice;
+                        cell[u_axis] = u;
+                        cell[v_axis] = v;
+
+                        let block = sample.get(cell);
+                        let mut neighbour_cell = cell;
+                        neighbour_cell[axis] += sign;
+                        let neighbour = sample.get(neighbour_cell);
+
+                        let visible =
+                            block != AIR && !palette.is_opaque(neighbour) && neighbour != block;
+                        mask[(u + v * size) as usize] = visible.then(|| FaceKey {
+                            block,
+                            ao: face_ao(sample, palette, neighbour_cell, u_axis, v_axis),
+                        });
+                    }
+                }
+
+                // Merge equal faces into rectangles.
+                for v in 0..CHUNK_SIZE {
+                    let mut u = 0;
+                    while u < CHUNK_SIZE {
+                        let Some(key) = mask[u + v * CHUNK_SIZE] else {
+                            u += 1;
+                            continue;
+                        };
+
+                        let mut width = 1;
+                        while u + width < CHUNK_SIZE
+                            && mask[u + width + v * CHUNK_SIZE] == Some(key)
+                        {
+                            width += 1;
+                        }
+
+                        let mut height = 1;
+                        'grow: while v + height < CHUNK_SIZE {
+                            for du in 0..width {
+                                if mask[u + du + (v + height) * CHUNK_SIZE] != Some(key) {
+                                    break 'grow;
+                                }
+                            }
+                            height += 1;
+                        }
+
+                        for dv in 0..height {
+                            for du in 0..width {
+                                mask[u + du + (v + dv) * CHUNK_SIZE] = None;
+                            }
+                        }
+
+                        let mut corner = [0i32; 3];
+                        corner[axis] = slice + if sign > 0 { 1 } else { 0 };
+                        corner[u_axis] = u as i32;
+                        corner[v_axis] = v as i32;
+                        push_quad(
+                            &mut mesh,
+                            sample,
+                            palette,
+                            key,
+                            QuadShape {
+                                corner,
+                                axis,
+                                u_axis,
+                                v_axis,
+                                sign,
+                                width: width as i32,
+                                height: height as i32,
+                            },
+                        );
+
+                        u += width;
+                    }
+                }
+            }
+        }
+    }
+
+    mesh
+}
+
+// Occlusion for the four corners of a face, in quad order (0,0), (1,0), (1,1), (0,1).
+// `layer` is the air cell in front of the face.
+fn face_ao(
+    sample: &ChunkSample,
+    palette: &MeshPalette,
+    layer: [i32; 3],
+    u_axis: usize,
+    v_axis: usize,
+) -> [u8; 4] {
+    let occludes = |du: i32, dv: i32| {
+        let mut cell = layer;
+        cell[u_axis] += du;
+        cell[v_axis] += dv;
+        palette.is_opaque(sample.get(cell))
+    };
+
+    [(-1, -1), (1, -1), (1, 1), (-1, 1)]
+        .map(|(du, dv)| vertex_ao(occludes(du, 0), occludes(0, dv), occludes(du, dv)))
+}
+
+struct QuadShape {
+    corner: [i32; 3],
+    axis: usize,
+    u_axis: usize,
+    v_axis: usize,
+    sign: i32,
+    width: i32,
+    height: i32,
+}
+
+fn push_quad(
+    mesh: &mut ChunkMesh,
+    sample: &ChunkSample,
+    palette: &MeshPalette,
+    key: FaceKey,
+    quad: QuadShape,
+) {
+    let (origin, scale) = (sample.origin, sample.scale);
+    let color = palette.get(key.block).color;
+    let mut normal = [0.0; 3];
+    normal[quad.axis] = quad.sign as f32;
+
+    let base = mesh.vertices.len() as u32;
+    for (i, (du, dv)) in [(0, 0), (1, 0), (1, 1), (0, 1)].into_iter().enumerate() {
+        let mut position = quad.corner;
+        position[quad.u_axis] += du * quad.width;
+        position[quad.v_axis] += dv * quad.height;
+
+        mesh.vertices.push(MainVertex {
+            position: [
+                (origin.x + position[0] * scale) as f32,
+                (origin.y + position[1] * scale) as f32,
+                (origin.z + position[2] * scale) as f32,
+            ],
+            normal,
+            uv: [(du * quad.width) as f32, (dv * quad.height) as f32],
+            color,
+            ao: AO_BRIGHTNESS[key.ao[i] as usize],
+        });
+    }
+
+    // Split along the diagonal with the brighter ends so occlusion interpolates evenly.
+    let ao = key.ao;
+    let flip = ao[0] + ao[2] < ao[1] + ao[3];
+    let mut triangles = if flip {
+        [1, 2, 3, 1, 3, 0]
+    } else {
+        [0, 1, 2, 0, 2, 3]
+    };
+    // Keep counter-clockwise winding when seen from outside.
+    if quad.sign < 0 {
+        triangles.swap(1, 2);
+        triangles.swap(4, 5);
+    }
+    mesh.indices.extend(triangles.iter().map(|i| base + i));
+}
+
+// Thickness of the lines in a block outline, and how far they sit outside the block.
+const OUTLINE_WIDTH: f32 = 0.02;
+const OUTLINE_MARGIN: f32 = 0.005;
+const OUTLINE_COLOR: [f32; 3] = [0.05, 0.05, 0.05];
+
+/// A wireframe box around one block, drawn as twelve thin beams, for highlighting the
+/// targeted block.
+pub fn block_outline(pos: BlockPos) -> ChunkMesh {
+    let mut mesh = ChunkMesh::default();
+    let min = [pos.x as f32, pos.y as f32, pos.z as f32].map(|v| v - OUTLINE_MARGIN);
+    let size = 1.0 + 2.0 * OUTLINE_MARGIN;
+
+    for axis in 0..3 {
+        let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+        for corner in 0..4 {
+            let mut center = Vector3::from(min);
+            let mut half_extents = Vector3::repeat(OUTLINE_WIDTH / 2.0);
+            center[axis] += size / 2.0;
+            half_extents[axis] = size / 2.0;
+            for (other, side) in [(u, corner & 1), (v, corner >> 1)] {
+                center[other] += side as f32 * size;
+            }
+            push_box(
+                &mut mesh,
+                &Isometry3::translation(center.x, center.y, center.z),
+                &half_extents,
+                OUTLINE_COLOR,
+            );
+        }
+    }
+    mesh
+}
+
+/// Appends the six faces of a box with the given pose and half extents.
+pub fn push_box(
+    mesh: &mut ChunkMesh,
+    pose: &Isometry3<f32>,
+    half_extents: &Vector3<f32>,
+    color: [f32; 3],
+) {
+    for axis in 0..3 {
+        let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+        for sign in [-1.0, 1.0] {
+            let mut normal = Vector3::zeros();
+            normal[axis] = sign;
+            let base = mesh.vertices.len() as u32;
+            for (du, dv) in [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)] {
+                let mut corner = Point3::origin();
+                corner[axis] = sign * half_extents[axis];
+                corner[u] = du * half_extents[u];
+                corner[v] = dv * half_extents[v];
+                mesh.vertices.push(MainVertex {
+                    position: (pose * corner).into(),
+                    normal: (pose.rotation * normal).into(),
+                    uv: [(du + 1.0) / 2.0, (dv + 1.0) / 2.0],
+                    color,
+                    ao: 1.0,
+                });
+            }
+            mesh.indices
+                .extend([0, 1, 2, 0, 2, 3].iter().map(|i| base + i));
+        }
+    }
+}
+
+/// What a mesh belongs to: a loaded chunk or a distant level-of-detail region.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MeshKey {
+    Chunk(ChunkPos),
+    Lod(LodPos),
+}
+
+struct MeshJob {
+    key: MeshKey,
+    generation: u64,
+    sample: ChunkSample,
+    palette: Arc<MeshPalette>,
+}
+
+struct MeshResult {
+    key: MeshKey,
+    generation: u64,
+    mesh: ChunkMesh,
+}
+
+/// Meshes dirty chunks on a pool of background threads.
+pub struct ChunkMesher {
+    job_sender: Option<Sender<MeshJob>>,
+    result_receiver: Receiver<MeshResult>,
+    workers: Vec<thread::JoinHandle<()>>,
+    palette: Arc<MeshPalette>,
+    // Latest generation submitted per mesh; older results are dropped.
+    pending: HashMap<MeshKey, u64>,
+    next_generation: u64,
+}
+
+impl ChunkMesher {
+    /// Starts `threads` worker threads (at least one).
+    pub fn new(registry: &BlockRegistry, threads: usize) -> Self {
+        let (job_sender, job_receiver) = channel::<MeshJob>();
+        let (result_sender, result_receiver) = channel();
+        let job_receiver = Arc::new(Mutex::new(job_receiver));
+
+        let workers = (0..threads.max(1))
+            .map(|i| {
+                let job_receiver = Arc::clone(&job_receiver);
+                let result_sender = result_sender.clone();
+                thread::Builder::new()
+                    .name(format!("lustre-mesher-{}", i))
+                    .spawn(move || loop {
+                        let job = match job_receiver.lock().unwrap().recv() {
+                            Ok(job) => job,
+                            // The mesher was dropped.
+                            Err(_) => return,
+                        };
+                        let mesh = mesh_chunk(&job.sample, &job.palette);
+                        let result = MeshResult {
+                            key: job.key,
+                            generation: job.generation,
+                            mesh,
+                        };
+                        if result_sender.send(result).is_err() {
+                            return;
+                        }
+                    })
+                    .expect("failed to spawn mesher thread")
+            })
+            .collect();
+
+        Self {
+            job_sender: Some(job_sender),
+            result_receiver,
+            workers,
+            palette: Arc::new(MeshPalette::from_registry(registry)),
+            pending: HashMap::new(),
+            next_generation: 0,
+        }
+    }
+
+    /// Refreshes block colors and opacity after blocks were registered.
+    pub fn update_registry(&mut self, registry: &BlockRegistry) {
+        self.palette = Arc::new(MeshPalette::from_registry(registry));
+    }
+
+    /// Queues a chunk for meshing, superseding any earlier request for it.
+    pub fn schedule(&mut self, world: &World, pos: ChunkPos) {
+        self.schedule_sample(MeshKey::Chunk(pos), ChunkSample::from_world(world, pos));
+    }
+
+    /// Queues an already sampled chunk, superseding any earlier request for the same key.
+    pub fn schedule_sample(&mut self, key: MeshKey, sample: ChunkSample) {
+        let generation = self.next_generation;
+        self.next_generation += 1;
+        self.pending.insert(key, generation);
+
+        let job = MeshJob {
+            key,
+            generation,
+            sample,
+            palette: Arc::clone(&self.palette),
+        };
+        if let Some(sender) = &self.job_sender {
+            if sender.send(job).is_err() {
+                log_error("Mesher threads have stopped.");
+            }
+        }
+    }
+
+    /// Queues every dirty chunk and clears its dirty flag. Returns how many were queued.
+    pub fn schedule_dirty(&mut self, world: &mut World) -> usize {
+        let dirty = world.dirty_chunks();
+        for &pos in &dirty {
+            self.schedule(world, pos);
+            world.clear_dirty(pos);
+        }
+        dirty.len()
+    }
+
+    /// Drops a queued mesh, e.g. because its chunk was unloaded. Its result is discarded.
+    pub fn cancel(&mut self, key: MeshKey) {
+        self.pending.remove(&key);
+    }
+
+    /// Number of meshes queued or being built.
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Returns meshes finished since the last call, skipping superseded and cancelled ones.
+    pub fn poll(&mut self) -> Vec<(MeshKey, ChunkMesh)> {
+        let mut finished = Vec::new();
+        while let Ok(result) = self.result_receiver.try_recv() {
+            if self.pending.get(&result.key) == Some(&result.generation) {
+                self.pending.remove(&result.key);
+                finished.push((result.key, result.mesh));
+            }
+        }
+        finished
+    }
+}
+
+impl Drop for ChunkMesher {
+    fn drop(&mut self) {
+        // Closing the job channel stops the workers once they finish their current job.
+        self.job_sender.take();
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
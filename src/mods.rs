@@ -0,0 +1,817 @@
+// Copyright 2025 Nicholas Jordan. All Rights Reserved.
+// github.com/cvusmo/lustre
+// src/mods.rs
+
+use crate::diagnostics::{self, Diagnostic};
+use crate::events;
+use crate::hot_reload::carry_over_tables;
+use crate::scripting::{run_with_limits, ScriptLimits};
+use crate::settings;
+use crate::state::{log_error, log_info, log_warn};
+use crate::terrain;
+
+use mlua::prelude::*;
+use mlua::ChunkMode;
+use semver::{Version, VersionReq};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+// Manifest every mod directory contains.
+const MANIFEST_FILE: &str = "mod.toml";
+// Script a mod starts from unless its manifest names another.
+const ENTRY_FILE: &str = "main.lua";
+
+// Base functions every mod gets. Loading code, `require` and the garbage collector are left
+// out, as are `getmetatable` and `print`, which are replaced.
+const SAFE_FUNCTIONS: [&str; 16] = [
+    "assert",
+    "error",
+    "ipairs",
+    "next",
+    "pairs",
+    "pcall",
+    "rawequal",
+    "rawget",
+    "rawlen",
+    "rawset",
+    "select",
+    "setmetatable",
+    "tonumber",
+    "tostring",
+    "type",
+    "xpcall",
+];
+
+// Libraries every mod gets its own copy of, so one mod can't patch them for the others.
+const SAFE_LIBRARIES: [&str; 5] = ["coroutine", "math", "string", "table", "utf8"];
+
+// The parts of `os` that don't touch files, processes or the environment.
+const SAFE_OS_FUNCTIONS: [&str; 4] = ["clock", "date", "difftime", "time"];
+
+// Engine APIs registered in the global environment that mods may use.
+// `events` and `terrain` get per-mod tables instead.
+const ENGINE_GLOBALS: [&str; 4] = ["clock", "ecs", "print_message", "world"];
+
+/// Access a mod asks for beyond the sandbox, listed in its manifest's `capabilities`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Capability {
+    /// Read and write files inside the mod's own directory through the `fs` table.
+    Filesystem,
+    /// Reserved for the networking API; grants nothing until it exists.
+    Network,
+}
+
+fn default_entry() -> String {
+    ENTRY_FILE.to_string()
+}
+
+/// A mod's `mod.toml`.
+///
+/// ```toml
+/// id = "castles"
+/// version = "1.2.0"
+/// entry = "main.lua"
+/// capabilities = ["filesystem"]
+///
+/// [dependencies]
+/// stonework = "^2.1"
+///
+/// [optional_dependencies]
+/// banners = ">=0.3"
+///
+/// [conflicts]
+/// old_castles = "*"
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModManifest {
+    /// Unique name other mods refer to this one by.
+    pub id: String,
+    pub version: Version,
+    /// Script the mod starts from, relative to its directory.
+    #[serde(default = "default_entry")]
+    pub entry: String,
+    /// Mods that must be loaded first, with the versions this one works with.
+    #[serde(default)]
+    pub dependencies: BTreeMap<String, VersionReq>,
+    /// Mods loaded first when they're installed in a matching version.
+    #[serde(default)]
+    pub optional_dependencies: BTreeMap<String, VersionReq>,
+    /// Mods this one can't be loaded alongside.
+    #[serde(default)]
+    pub conflicts: BTreeMap<String, VersionReq>,
+    /// What the mod may do beyond the sandbox.
+    #[serde(default)]
+    pub capabilities: Vec<Capability>,
+}
+
+impl ModManifest {
+    /// A manifest with no dependencies, conflicts or capabilities.
+    pub fn new(id: &str, version: Version) -> Self {
+        Self {
+            id: id.to_string(),
+            version,
+            entry: default_entry(),
+            dependencies: BTreeMap::new(),
+            optional_dependencies: BTreeMap::new(),
+            conflicts: BTreeMap::new(),
+            capabilities: Vec::new(),
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let manifest: Self = toml::from_str(&fs::read_to_string(path)?)?;
+        let valid_id = !manifest.id.is_empty()
+            && manifest
+                .id
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+        if !valid_id {
+            return Err(format!("id '{}' must be letters, digits, '_' or '-'", manifest.id).into());
+        }
+        Ok(manifest)
+    }
+
+    pub fn allows(&self, capability: Capability) -> bool {
+        self.capabilities.contains(&capability)
+    }
+}
+
+/// Why a mod wasn't loaded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ModError {
+    InvalidManifest {
+        dir: PathBuf,
+        message: String,
+    },
+    DuplicateId {
+        id: String,
+        dirs: Vec<PathBuf>,
+    },
+    MissingDependency {
+        id: String,
+        dependency: String,
+        requirement: VersionReq,
+    },
+    IncompatibleDependency {
+        id: String,
+        dependency: String,
+        requirement: VersionReq,
+        found: Version,
+    },
+    Conflict {
+        id: String,
+        other: String,
+        version: Version,
+    },
+    /// Mod ids around a dependency cycle, starting and ending with the same id.
+    Cycle(Vec<String>),
+    DependencyFailed {
+        id: String,
+        dependency: String,
+    },
+}
+
+impl fmt::Display for ModError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ModError::InvalidManifest { dir, message } => {
+                write!(
+                    f,
+                    "mod in {} has an invalid {}: {}",
+                    dir.display(),
+                    MANIFEST_FILE,
+                    message
+                )
+            }
+            ModError::DuplicateId { id, dirs } => {
+                let dirs: Vec<String> = dirs.iter().map(|dir| dir.display().to_string()).collect();
+                write!(
+                    f,
+                    "mod '{}' is installed more than once: {}",
+                    id,
+                    dirs.join(", ")
+                )
+            }
+            ModError::MissingDependency {
+                id,
+                dependency,
+                requirement,
+            } => write!(
+                f,
+                "mod '{}' needs '{}' {}, which isn't installed",
+                id, dependency, requirement
+            ),
+            ModError::IncompatibleDependency {
+                id,
+                dependency,
+                requirement,
+                found,
+            } => write!(
+                f,
+                "mod '{}' needs '{}' {}, but {} is installed",
+                id, dependency, requirement, found
+            ),
+            ModError::Conflict { id, other, version } => {
+                write!(f, "mod '{}' conflicts with '{}' {}", id, other, version)
+            }
+            ModError::Cycle(ids) => {
+                write!(f, "mods depend on each other: {}", ids.join(" -> "))
+            }
+            ModError::DependencyFailed { id, dependency } => write!(
+                f,
+                "mod '{}' was skipped because '{}' couldn't be loaded",
+                id, dependency
+            ),
+        }
+    }
+}
+
+impl Error for ModError {}
+
+/// An installed mod that hasn't run yet.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModSource {
+    pub dir: PathBuf,
+    pub manifest: ModManifest,
+}
+
+/// Reads the manifest of every mod directory in `dir`. Directories without a valid
+/// manifest are returned as errors.
+pub fn discover_mods(dir: &Path) -> (Vec<ModSource>, Vec<ModError>) {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) => {
+            log_warn(&format!("No mods loaded from {}: {}", dir.display(), err));
+            return (Vec::new(), Vec::new());
+        }
+    };
+
+    let mut sources = Vec::new();
+    let mut errors = Vec::new();
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if !path.is_dir() {
+            if path.extension().and_then(|s| s.to_str()) == Some("lua") {
+                log_warn(&format!(
+                    "Ignoring {}: mods are directories with a {}",
+                    path.display(),
+                    MANIFEST_FILE
+                ));
+            }
+            continue;
+        }
+        let manifest_path = path.join(MANIFEST_FILE);
+        let manifest = if manifest_path.exists() {
+            ModManifest::load(&manifest_path).map_err(|err| err.to_string())
+        } else {
+            Err("file not found".to_string())
+        };
+        match manifest {
+            Ok(manifest) => sources.push(ModSource {
+                dir: path,
+                manifest,
+            }),
+            Err(message) => errors.push(ModError::InvalidManifest { dir: path, message }),
+        }
+    }
+    (sources, errors)
+}
+
+/// Orders mods so each loads after its dependencies, leaving out those that can't load.
+///
+/// The order only depends on the manifests: among mods whose dependencies are met, the
+/// one with the smallest id goes first. Mods with missing, incompatible or failed
+/// dependencies, conflicts, duplicate ids or cycles of required dependencies are returned
+/// as errors. Cycles through optional dependencies are broken instead.
+pub fn resolve_load_order(sources: Vec<ModSource>) -> (Vec<ModSource>, Vec<ModError>) {
+    let mut errors = Vec::new();
+    let mut failed = BTreeSet::new();
+
+    let mut by_id: BTreeMap<String, Vec<ModSource>> = BTreeMap::new();
+    for source in sources {
+        by_id
+            .entry(source.manifest.id.clone())
+            .or_default()
+            .push(source);
+    }
+    let mut mods: BTreeMap<String, ModSource> = BTreeMap::new();
+    for (id, mut copies) in by_id {
+        if copies.len() == 1 {
+            mods.insert(id, copies.remove(0));
+        } else {
+            let mut dirs: Vec<PathBuf> = copies.into_iter().map(|copy| copy.dir).collect();
+            dirs.sort();
+            errors.push(ModError::DuplicateId {
+                id: id.clone(),
+                dirs,
+            });
+            failed.insert(id);
+        }
+    }
+
+    // Missing and incompatible dependencies, and conflicts with installed mods.
+    for (id, source) in &mods {
+        let manifest = &source.manifest;
+        let error = manifest
+            .dependencies
+            .iter()
+            .find_map(|(dependency, requirement)| match mods.get(dependency) {
+                _ if failed.contains(dependency) => None,
+                None => Some(ModError::MissingDependency {
+                    id: id.clone(),
+                    dependency: dependency.clone(),
+                    requirement: requirement.clone(),
+                }),
+                Some(found) if !requirement.matches(&found.manifest.version) => {
+                    Some(ModError::IncompatibleDependency {
+                        id: id.clone(),
+                        dependency: dependency.clone(),
+                        requirement: requirement.clone(),
+                        found: found.manifest.version.clone(),
+                    })
+                }
+                Some(_) => None,
+            })
+            .or_else(|| {
+                manifest
+                    .conflicts
+                    .iter()
+                    .find_map(|(other, requirement)| match mods.get(other) {
+                        Some(found) if requirement.matches(&found.manifest.version) => {
+                            Some(ModError::Conflict {
+                                id: id.clone(),
+                                other: other.clone(),
+                                version: found.manifest.version.clone(),
+                            })
+                        }
+                        _ => None,
+                    })
+            });
+        if let Some(error) = error {
+            errors.push(error);
+            failed.insert(id.clone());
+        }
+    }
+
+    // Required dependencies, plus optional ones installed in a matching version, each with
+    // whether it's optional.
+    let mut after: BTreeMap<&str, Vec<(&str, bool)>> = BTreeMap::new();
+    for (id, source) in &mods {
+        let manifest = &source.manifest;
+        let mut dependencies: Vec<(&str, bool)> = manifest
+            .dependencies
+            .keys()
+            .map(|dependency| (dependency.as_str(), false))
+            .collect();
+        for (dependency, requirement) in &manifest.optional_dependencies {
+            match mods.get(dependency) {
+                Some(found) if requirement.matches(&found.manifest.version) => {
+                    dependencies.push((dependency, true));
+                }
+                Some(found) => log_warn(&format!(
+                    "Mod '{}' works with '{}' {}, but {} is installed; not loading after it",
+                    id, dependency, requirement, found.manifest.version
+                )),
+                None => (),
+            }
+        }
+        after.insert(id, dependencies);
+    }
+
+    let mut pending: BTreeSet<&str> = mods
+        .keys()
+        .map(String::as_str)
+        .filter(|id| !failed.contains(*id))
+        .collect();
+    let mut order = Vec::new();
+    while !pending.is_empty() {
+        // Mods whose required dependencies failed fail too.
+        while let Some((id, dependency)) = pending.iter().find_map(|id| {
+            mods[*id]
+                .manifest
+                .dependencies
+                .keys()
+                .find(|dependency| failed.contains(*dependency))
+                .map(|dependency| (*id, dependency.clone()))
+        }) {
+            errors.push(ModError::DependencyFailed {
+                id: id.to_string(),
+                dependency,
+            });
+            pending.remove(id);
+            failed.insert(id.to_string());
+        }
+
+        // Load whatever has its dependencies loaded, smallest id first.
+        while let Some(id) = pending.iter().copied().find(|id| {
+            after[id]
+                .iter()
+                .all(|(dependency, _)| !pending.contains(dependency))
+        }) {
+            pending.remove(id);
+            order.push(id.to_string());
+        }
+
+        // Everything left waits on a cycle. Find one, noting which of its edges are optional.
+        let Some(&start) = pending.first() else {
+            break;
+        };
+        let mut path: Vec<(&str, bool)> = Vec::new();
+        let mut current = start;
+        let cycle = loop {
+            let (next, optional) = after[current]
+                .iter()
+                .copied()
+                .find(|(dependency, _)| pending.contains(dependency))
+                .expect("a waiting mod has a waiting dependency");
+            path.push((current, optional));
+            if let Some(position) = path.iter().position(|(id, _)| *id == next) {
+                break path.split_off(position);
+            }
+            current = next;
+        };
+
+        // An optional dependency in the cycle just loses its ordering.
+        if let Some(position) = cycle.iter().position(|(_, optional)| *optional) {
+            let id = cycle[position].0;
+            let dependency = cycle[(position + 1) % cycle.len()].0;
+            log_warn(&format!(
+                "Mod '{}' optionally depends on '{}' in a cycle; not loading after it",
+                id, dependency
+            ));
+            if let Some(dependencies) = after.get_mut(id) {
+                dependencies.retain(|(other, optional)| !*optional || *other != dependency);
+            }
+            continue;
+        }
+
+        // Only required dependencies: report the cycle and drop its mods.
+        let mut cycle: Vec<String> = cycle.iter().map(|(id, _)| id.to_string()).collect();
+        cycle.push(cycle[0].clone());
+        for id in &cycle {
+            pending.remove(id.as_str());
+            failed.insert(id.clone());
+        }
+        errors.push(ModError::Cycle(cycle));
+    }
+
+    let order = order
+        .into_iter()
+        .filter_map(|id| mods.remove(&id))
+        .collect();
+    (order, errors)
+}
+
+/// Where mods are loaded from and what they may use, read from the `[mods]` table.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ModSettings {
+    /// Directory holding one directory per mod, each with a `mod.toml`.
+    pub dir: PathBuf,
+    /// Memory and instructions each call into a mod may use.
+    pub limits: ScriptLimits,
+}
+
+impl Default for ModSettings {
+    fn default() -> Self {
+        Self {
+            dir: PathBuf::from("mods"),
+            limits: ScriptLimits::default(),
+        }
+    }
+}
+
+impl ModSettings {
+    /// Reads the `[mods]` table of a config file.
+    pub fn load(path: &str) -> Self {
+        settings::load_section(path, "mods")
+    }
+}
+
+/// A mod that has been run.
+pub struct LoadedMod {
+    pub dir: PathBuf,
+    pub manifest: ModManifest,
+    /// The sandboxed globals the mod's scripts see.
+    pub env: LuaTable,
+}
+
+impl LoadedMod {
+    pub fn id(&self) -> &str {
+        &self.manifest.id
+    }
+}
+
+// Copies a table's fields into a new table.
+fn copy_table(lua: &Lua, table: &LuaTable) -> LuaResult<LuaTable> {
+    let copy = lua.create_table()?;
+    for pair in table.pairs::<LuaValue, LuaValue>() {
+        let (key, value) = pair?;
+        copy.set(key, value)?;
+    }
+    Ok(copy)
+}
+
+// Joins `path` to `root`, refusing paths that lead outside it.
+fn resolve_in(root: &Path, path: &str) -> LuaResult<PathBuf> {
+    let outside = || LuaError::runtime(format!("Path '{}' is outside the mod directory", path));
+    let relative = Path::new(path);
+    if relative
+        .components()
+        .any(|component| !matches!(component, Component::Normal(_) | Component::CurDir))
+    {
+        return Err(outside());
+    }
+
+    // Symlinks could still lead out, so check where the deepest existing part really is.
+    // The root is compared the same way, as mod directories are often relative.
+    let root = root.canonicalize().map_err(|_| outside())?;
+    let full = root.join(relative);
+    let existing = full
+        .ancestors()
+        .find(|ancestor| ancestor.exists())
+        .unwrap_or(&root);
+    match existing.canonicalize() {
+        Ok(real) if real.starts_with(&root) => Ok(full),
+        _ => Err(outside()),
+    }
+}
+
+// Builds the `fs` table, which reads and writes files under `root` only.
+fn create_filesystem_api(lua: &Lua, root: &Path) -> LuaResult<LuaTable> {
+    let root = root.canonicalize().map_err(LuaError::external)?;
+    let table = lua.create_table()?;
+
+    let dir = root.clone();
+    table.set(
+        "read",
+        lua.create_function(move |_, path: String| {
+            fs::read_to_string(resolve_in(&dir, &path)?).map_err(LuaError::external)
+        })?,
+    )?;
+    let dir = root.clone();
+    table.set(
+        "write",
+        lua.create_function(move |_, (path, contents): (String, LuaString)| {
+            let path = resolve_in(&dir, &path)?;
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent).map_err(LuaError::external)?;
+            }
+            fs::write(path, contents.as_bytes()).map_err(LuaError::external)
+        })?,
+    )?;
+    let dir = root.clone();
+    table.set(
+        "exists",
+        lua.create_function(move |_, path: String| Ok(resolve_in(&dir, &path)?.exists()))?,
+    )?;
+    table.set(
+        "list",
+        lua.create_function(move |_, path: Option<String>| {
+            let path = resolve_in(&root, path.as_deref().unwrap_or("."))?;
+            let mut names = Vec::new();
+            for entry in fs::read_dir(path).map_err(LuaError::external)? {
+                let entry = entry.map_err(LuaError::external)?;
+                names.push(entry.file_name().to_string_lossy().into_owned());
+            }
+            names.sort();
+            Ok(names)
+        })?,
+    )?;
+    Ok(table)
+}
+
+/// Creates the globals a mod's scripts run with.
+///
+/// Mods get the safe parts of the standard library, their own copies of the engine APIs, a
+/// `print` that logs under the mod's name, `events` and `terrain` tables whose handlers and
+/// generators run within `limits`, and whatever their manifest's capabilities grant.
+/// There is no `io`, `debug`, `package`, `require`, `load` or `dofile`, and strings' shared
+/// metatable is hidden.
+pub fn create_sandbox(
+    lua: &Lua,
+    name: &str,
+    dir: &Path,
+    manifest: &ModManifest,
+    limits: &ScriptLimits,
+) -> LuaResult<LuaTable> {
+    let globals = lua.globals();
+    let env = lua.create_table()?;
+
+    for function in SAFE_FUNCTIONS {
+        env.set(function, globals.get::<LuaValue>(function)?)?;
+    }
+    for library in SAFE_LIBRARIES {
+        env.set(library, copy_table(lua, &globals.get(library)?)?)?;
+    }
+    let os = globals.get::<LuaTable>("os")?;
+    let safe_os = lua.create_table()?;
+    for function in SAFE_OS_FUNCTIONS {
+        safe_os.set(function, os.get::<LuaValue>(function)?)?;
+    }
+    env.set("os", safe_os)?;
+
+    for api in ENGINE_GLOBALS {
+        match globals.get::<LuaValue>(api)? {
+            LuaValue::Table(table) => env.set(api, copy_table(lua, &table)?)?,
+            value => env.set(api, value)?,
+        }
+    }
+    if let Some(events) = events::create_mod_api(lua, name, limits)? {
+        env.set("events", events)?;
+    }
+    if let Some(terrain) = terrain::create_mod_api(lua, limits)? {
+        env.set("terrain", terrain)?;
+    }
+
+    // The string metatable is shared by every script, so don't hand it out.
+    let getmetatable = globals.get::<LuaFunction>("getmetatable")?;
+    env.set(
+        "getmetatable",
+        lua.create_function(move |_, value: LuaValue| match value {
+            LuaValue::String(_) => Ok(LuaValue::Nil),
+            value => getmetatable.call::<LuaValue>(value),
+        })?,
+    )?;
+
+    let prefix = format!("[{}]", name);
+    env.set(
+        "print",
+        lua.create_function(move |_, values: LuaVariadic<LuaValue>| {
+            let mut line = prefix.clone();
+            for value in values.iter() {
+                line.push(' ');
+                line.push_str(&value.to_string()?);
+            }
+            log_info(&line);
+            Ok(())
+        })?,
+    )?;
+
+    for capability in &manifest.capabilities {
+        match capability {
+            Capability::Filesystem => env.set("fs", create_filesystem_api(lua, dir)?)?,
+            Capability::Network => (),
+        }
+        log_info(&format!("Mod {} granted {:?}", name, capability));
+    }
+
+    env.set("_G", env.clone())?;
+    Ok(env)
+}
+
+// Compiles a mod's entry script to run with `env` as its globals.
+fn compile_entry(lua: &Lua, source: &ModSource, env: &LuaTable) -> LuaResult<LuaFunction> {
+    let entry = resolve_in(&source.dir, &source.manifest.entry)?;
+    let code = fs::read_to_string(&entry).map_err(LuaError::external)?;
+    lua.load(&code)
+        .set_name(format!("@{}", entry.display()))
+        .set_environment(env.clone())
+        // Precompiled chunks can break out of the sandbox.
+        .set_mode(ChunkMode::Text)
+        .into_function()
+}
+
+// Calls the global function `name` a mod defined, if it did.
+fn call_hook(
+    lua: &Lua,
+    env: &LuaTable,
+    name: &str,
+    arg: LuaValue,
+    limits: &ScriptLimits,
+) -> LuaResult<LuaValue> {
+    match env.get::<Option<LuaFunction>>(name)? {
+        Some(hook) => run_with_limits(lua, limits, || hook.call::<LuaValue>(arg)),
+        None => Ok(LuaValue::Nil),
+    }
+}
+
+/// Runs an installed mod's entry script in its own sandbox within `limits`, then its
+/// `on_load` function if it defines one.
+pub fn load_mod(lua: &Lua, source: &ModSource, limits: &ScriptLimits) -> LuaResult<LoadedMod> {
+    let manifest = &source.manifest;
+    let env = create_sandbox(lua, &manifest.id, &source.dir, manifest, limits)?;
+    let chunk = compile_entry(lua, source, &env)?;
+    run_with_limits(lua, limits, || chunk.call::<()>(()))?;
+    call_hook(lua, &env, "on_load", LuaValue::Nil, limits)?;
+
+    Ok(LoadedMod {
+        dir: source.dir.clone(),
+        manifest: manifest.clone(),
+        env,
+    })
+}
+
+/// Reloads a running mod from its directory in a fresh sandbox.
+///
+/// The old version's `on_unload()` runs first and whatever it returns is passed to the new
+/// version's `on_load(state)`, so a mod can carry its state across reloads. Global tables
+/// the new version defines take the place of the old ones with the same names, keeping
+/// their identity, so entity behaviours and other holders of those tables run the new
+/// functions. Event handlers the old version subscribed are dropped. If the new version
+/// doesn't compile or fails to run, the old one stays loaded.
+pub fn reload_mod(lua: &Lua, loaded: &mut LoadedMod, limits: &ScriptLimits) -> LuaResult<()> {
+    let manifest = ModManifest::load(&loaded.dir.join(MANIFEST_FILE))
+        .map_err(|err| LuaError::runtime(format!("Invalid {}: {}", MANIFEST_FILE, err)))?;
+    if manifest.id != loaded.manifest.id {
+        return Err(LuaError::runtime(format!(
+            "Mod id changed from '{}' to '{}'; restart to load it under the new id",
+            loaded.manifest.id, manifest.id
+        )));
+    }
+    let source = ModSource {
+        dir: loaded.dir.clone(),
+        manifest,
+    };
+    let env = create_sandbox(
+        lua,
+        &source.manifest.id,
+        &source.dir,
+        &source.manifest,
+        limits,
+    )?;
+    let chunk = compile_entry(lua, &source, &env)?;
+
+    let state =
+        call_hook(lua, &loaded.env, "on_unload", LuaValue::Nil, limits).unwrap_or_else(|err| {
+            log_warn(&format!("on_unload of mod {} failed: {}", loaded.id(), err));
+            LuaValue::Nil
+        });
+    // Event handlers are replaced by whatever the new version subscribes.
+    let bus = events::shared_bus(lua);
+    let handlers = bus
+        .as_ref()
+        .map(|bus| bus.borrow_mut().take_owned(loaded.id()))
+        .unwrap_or_default();
+    if let Err(err) = run_with_limits(lua, limits, || chunk.call::<()>(())) {
+        // Put the old version back the way it was.
+        if let Some(bus) = &bus {
+            let mut bus = bus.borrow_mut();
+            bus.take_owned(loaded.id());
+            bus.restore(handlers);
+        }
+        call_hook(lua, &loaded.env, "on_load", state, limits)?;
+        return Err(err);
+    }
+    carry_over_tables(&loaded.env, &env)?;
+    loaded.manifest = source.manifest;
+    loaded.env = env;
+    call_hook(lua, &loaded.env, "on_load", state, limits)?;
+    Ok(())
+}
+
+/// Loads every mod in the settings' directory, each after the mods it depends on. Mods
+/// that can't be loaded are reported and skipped, along with the mods that need them.
+pub fn load_mods(lua: &Lua, settings: &ModSettings) -> Vec<LoadedMod> {
+    let (sources, mut errors) = discover_mods(&settings.dir);
+    let (order, resolve_errors) = resolve_load_order(sources);
+    errors.extend(resolve_errors);
+    for err in &errors {
+        log_error(&format!("Mod not loaded: {}", err));
+        diagnostics::report(
+            lua,
+            Diagnostic::from_message(&format!("Mod not loaded: {}", err)),
+        );
+    }
+
+    let mut failed = BTreeSet::new();
+    let mut mods = Vec::new();
+    for source in order {
+        let id = &source.manifest.id;
+        if let Some(dependency) = source
+            .manifest
+            .dependencies
+            .keys()
+            .find(|dependency| failed.contains(*dependency))
+        {
+            let err = ModError::DependencyFailed {
+                id: id.clone(),
+                dependency: dependency.clone(),
+            };
+            log_error(&format!("Mod not loaded: {}", err));
+            diagnostics::report(
+                lua,
+                Diagnostic::from_message(&format!("Mod not loaded: {}", err)),
+            );
+            failed.insert(id.clone());
+            continue;
+        }
+        match load_mod(lua, &source, &settings.limits) {
+            Ok(loaded) => {
+                log_info(&format!("Loaded mod {} {}", id, source.manifest.version));
+                mods.push(loaded);
+            }
+            Err(err) => {
+                log_error(&format!("Failed to load mod {}: {}", id, err));
+                diagnostics::report(lua, Diagnostic::from_error(&err));
+                failed.insert(id.clone());
+            }
+        }
+    }
+    mods
+}
@@ -0,0 +1,307 @@
+// Copyright 2025 Nicholas Jordan. All Rights Reserved.
+// github.com/cvusmo/lustre
+// src/physics.rs
+
+use crate::block::BlockRegistry;
+use crate::chunk::{Chunk, CHUNK_SIZE};
+use crate::planet::Gravity;
+use crate::world::{ChunkPos, World};
+
+use rapier3d::na::{Isometry3, Point3, Vector3};
+use rapier3d::prelude::*;
+use std::collections::HashMap;
+
+/// Seconds simulated by one physics step.
+pub const FIXED_TIMESTEP: f32 = 1.0 / 60.0;
+
+/// An axis-aligned box of solid blocks inside a chunk, in local block coordinates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VoxelBox {
+    pub min: [usize; 3],
+    pub size: [usize; 3],
+}
+
+/// Covers the solid blocks of a chunk with as few boxes as a greedy merge finds.
+///
+/// Each box grows along x, then z, then y, over blocks no earlier box has taken.
+pub fn voxel_boxes(chunk: &Chunk, registry: &BlockRegistry) -> Vec<VoxelBox> {
+    let index = |x: usize, y: usize, z: usize| x + z * CHUNK_SIZE + y * CHUNK_SIZE * CHUNK_SIZE;
+    let mut open = vec![false; CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE];
+    for y in 0..CHUNK_SIZE {
+        for z in 0..CHUNK_SIZE {
+            for x in 0..CHUNK_SIZE {
+                open[index(x, y, z)] = registry.is_solid(chunk.get(x, y, z));
+            }
+        }
+    }
+
+    let mut boxes = Vec::new();
+    for y in 0..CHUNK_SIZE {
+        for z in 0..CHUNK_SIZE {
+            for x in 0..CHUNK_SIZE {
+                if !open[index(x, y, z)] {
+                    continue;
+                }
+
+                let mut width = 1;
+                while x + width < CHUNK_SIZE && open[index(x + width, y, z)] {
+                    width += 1;
+                }
+                let row_open = |y: usize, z: usize| (x..x + width).all(|x| open[index(x, y, z)]);
+                let mut depth = 1;
+                while z + depth < CHUNK_SIZE && row_open(y, z + depth) {
+                    depth += 1;
+                }
+                let mut height = 1;
+                while y + height < CHUNK_SIZE && (z..z + depth).all(|z| row_open(y + height, z)) {
+                    height += 1;
+                }
+
+                for by in y..y + height {
+                    for bz in z..z + depth {
+                        for bx in x..x + width {
+                            open[index(bx, by, bz)] = false;
+                        }
+                    }
+                }
+                boxes.push(VoxelBox {
+                    min: [x, y, z],
+                    size: [width, height, depth],
+                });
+            }
+        }
+    }
+    boxes
+}
+
+/// Builds a fixed compound collider for a chunk, or None if it has no solid blocks.
+pub fn chunk_collider(pos: ChunkPos, chunk: &Chunk, registry: &BlockRegistry) -> Option<Collider> {
+    let shapes: Vec<(Isometry3<f32>, SharedShape)> = voxel_boxes(chunk, registry)
+        .into_iter()
+        .map(|voxel_box| {
+            let half = voxel_box.size.map(|size| size as f32 / 2.0);
+            let center = Vector3::new(
+                voxel_box.min[0] as f32 + half[0],
+                voxel_box.min[1] as f32 + half[1],
+                voxel_box.min[2] as f32 + half[2],
+            );
+            (
+                Isometry3::translation(center.x, center.y, center.z),
+                SharedShape::cuboid(half[0], half[1], half[2]),
+            )
+        })
+        .collect();
+    if shapes.is_empty() {
+        return None;
+    }
+
+    let origin = pos.origin();
+    Some(
+        ColliderBuilder::compound(shapes)
+            .translation(vector![origin.x as f32, origin.y as f32, origin.z as f32])
+            .build(),
+    )
+}
+
+// Collider built for a chunk and the world revision it was built from.
+struct ChunkCollider {
+    handle: Option<ColliderHandle>,
+    revision: u64,
+}
+
+/// Owns the rapier pipeline and everything it simulates.
+///
+/// Steps at `FIXED_TIMESTEP` regardless of frame rate. Terrain is mirrored from a `World`
+/// by `sync_terrain`, one fixed compound collider per chunk.
+pub struct PhysicsWorld {
+    pub gravity: Gravity,
+    pipeline: PhysicsPipeline,
+    integration_parameters: IntegrationParameters,
+    islands: IslandManager,
+    broad_phase: DefaultBroadPhase,
+    narrow_phase: NarrowPhase,
+    bodies: RigidBodySet,
+    colliders: ColliderSet,
+    impulse_joints: ImpulseJointSet,
+    multibody_joints: MultibodyJointSet,
+    ccd_solver: CCDSolver,
+    query_pipeline: QueryPipeline,
+    chunks: HashMap<ChunkPos, ChunkCollider>,
+}
+
+impl Default for PhysicsWorld {
+    fn default() -> Self {
+        Self::new(Gravity::default())
+    }
+}
+
+impl PhysicsWorld {
+    pub fn new(gravity: Gravity) -> Self {
+        Self {
+            gravity,
+            pipeline: PhysicsPipeline::new(),
+            integration_parameters: IntegrationParameters {
+                dt: FIXED_TIMESTEP,
+                ..Default::default()
+            },
+            islands: IslandManager::new(),
+            broad_phase: DefaultBroadPhase::new(),
+            narrow_phase: NarrowPhase::new(),
+            bodies: RigidBodySet::new(),
+            colliders: ColliderSet::new(),
+            impulse_joints: ImpulseJointSet::new(),
+            multibody_joints: MultibodyJointSet::new(),
+            ccd_solver: CCDSolver::new(),
+            query_pipeline: QueryPipeline::new(),
+            chunks: HashMap::new(),
+        }
+    }
+
+    pub fn bodies(&self) -> &RigidBodySet {
+        &self.bodies
+    }
+
+    pub fn colliders(&self) -> &ColliderSet {
+        &self.colliders
+    }
+
+    /// Spatial queries (ray casts, shape casts) against the state after the last step.
+    pub fn query_pipeline(&self) -> &QueryPipeline {
+        &self.query_pipeline
+    }
+
+    pub fn body(&self, handle: RigidBodyHandle) -> Option<&RigidBody> {
+        self.bodies.get(handle)
+    }
+
+    pub fn body_mut(&mut self, handle: RigidBodyHandle) -> Option<&mut RigidBody> {
+        self.bodies.get_mut(handle)
+    }
+
+    /// Adds a body with one collider attached.
+    pub fn add_body(&mut self, body: RigidBody, collider: Collider) -> RigidBodyHandle {
+        let handle = self.bodies.insert(body);
+        self.colliders
+            .insert_with_parent(collider, handle, &mut self.bodies);
+        handle
+    }
+
+    /// Adds a dynamic box with its centre at `position`.
+    pub fn add_dynamic_box(
+        &mut self,
+        position: Point3<f32>,
+        half_extents: Vector3<f32>,
+    ) -> RigidBodyHandle {
+        let body = RigidBodyBuilder::dynamic()
+            .translation(position.coords)
+            .build();
+        let collider = ColliderBuilder::cuboid(half_extents.x, half_extents.y, half_extents.z);
+        self.add_body(body, collider.build())
+    }
+
+    /// Adds a dynamic ball with its centre at `position`.
+    pub fn add_dynamic_ball(&mut self, position: Point3<f32>, radius: f32) -> RigidBodyHandle {
+        let body = RigidBodyBuilder::dynamic()
+            .translation(position.coords)
+            .build();
+        self.add_body(body, ColliderBuilder::ball(radius).build())
+    }
+
+    /// Removes a body and its colliders.
+    pub fn remove_body(&mut self, handle: RigidBodyHandle) -> Option<RigidBody> {
+        self.bodies.remove(
+            handle,
+            &mut self.islands,
+            &mut self.colliders,
+            &mut self.impulse_joints,
+            &mut self.multibody_joints,
+            true,
+        )
+    }
+
+    /// Rebuilds colliders for chunks inserted or edited since the last sync and drops those
+    /// of unloaded chunks. Returns how many chunks changed.
+    pub fn sync_terrain(&mut self, world: &World) -> usize {
+        let mut changed = 0;
+
+        let unloaded: Vec<ChunkPos> = self
+            .chunks
+            .keys()
+            .filter(|pos| !world.is_loaded(**pos))
+            .copied()
+            .collect();
+        for pos in unloaded {
+            self.remove_chunk_collider(pos);
+            changed += 1;
+        }
+
+        for (&pos, chunk) in world.chunks() {
+            let revision = world.revision(pos);
+            if self
+                .chunks
+                .get(&pos)
+                .is_some_and(|collider| collider.revision == revision)
+            {
+                continue;
+            }
+            self.remove_chunk_collider(pos);
+            let handle = chunk_collider(pos, chunk, world.registry())
+                .map(|collider| self.colliders.insert(collider));
+            self.chunks.insert(pos, ChunkCollider { handle, revision });
+            changed += 1;
+        }
+
+        // Make new terrain visible to queries before the next step.
+        if changed > 0 {
+            self.query_pipeline.update(&self.colliders);
+        }
+        changed
+    }
+
+    fn remove_chunk_collider(&mut self, pos: ChunkPos) {
+        if let Some(ChunkCollider {
+            handle: Some(handle),
+            ..
+        }) = self.chunks.remove(&pos)
+        {
+            self.colliders
+                .remove(handle, &mut self.islands, &mut self.bodies, true);
+        }
+    }
+
+    /// Runs one fixed step of `FIXED_TIMESTEP` seconds; the game's `Clock` decides how many.
+    pub fn step(&mut self) {
+        let gravity = match self.gravity {
+            Gravity::Uniform(acceleration) => acceleration,
+            // Rapier only knows uniform gravity; radial gravity is applied per body.
+            Gravity::Radial { .. } => {
+                for (_, body) in self.bodies.iter_mut() {
+                    if body.is_dynamic() && body.is_enabled() && !body.is_sleeping() {
+                        let position = Point3::from(*body.translation());
+                        let acceleration = self.gravity.acceleration(&position);
+                        let velocity =
+                            body.linvel() + acceleration * body.gravity_scale() * FIXED_TIMESTEP;
+                        body.set_linvel(velocity, false);
+                    }
+                }
+                Vector3::zeros()
+            }
+        };
+
+        self.pipeline.step(
+            &gravity,
+            &self.integration_parameters,
+            &mut self.islands,
+            &mut self.broad_phase,
+            &mut self.narrow_phase,
+            &mut self.bodies,
+            &mut self.colliders,
+            &mut self.impulse_joints,
+            &mut self.multibody_joints,
+            &mut self.ccd_solver,
+            Some(&mut self.query_pipeline),
+            &(),
+            &(),
+        );
+    }
+}
@@ -0,0 +1,419 @@
+// Copyright 2025 Nicholas Jordan. All Rights Reserved.
+// github.com/cvusmo/lustre
+// src/planet.rs
+
+use crate::block::{BlockRegistry, AIR};
+use crate::chunk::{Chunk, CHUNK_SIZE};
+use crate::terrain::{Biome, DefaultGenerator, TerrainBlocks, TerrainGenerator, TerrainSettings};
+use crate::world::{BlockPos, ChunkPos};
+
+use rapier3d::na::{Point3, Vector3};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Standard gravity in metres (blocks) per second squared.
+pub const STANDARD_GRAVITY: f32 = 9.81;
+
+/// Gravity acting on cameras, rigid bodies and characters.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Gravity {
+    /// The same acceleration everywhere, for flat worlds.
+    Uniform(Vector3<f32>),
+    /// Pulls towards `center` with a constant `strength`.
+    Radial { center: Point3<f32>, strength: f32 },
+}
+
+impl Default for Gravity {
+    fn default() -> Self {
+        Gravity::Uniform(Vector3::new(0.0, -STANDARD_GRAVITY, 0.0))
+    }
+}
+
+impl Gravity {
+    /// Gravitational acceleration at a position.
+    pub fn acceleration(&self, position: &Point3<f32>) -> Vector3<f32> {
+        match self {
+            Gravity::Uniform(acceleration) => *acceleration,
+            Gravity::Radial { strength, .. } => -self.up(position) * *strength,
+        }
+    }
+
+    /// Unit vector opposite to gravity at a position.
+    pub fn up(&self, position: &Point3<f32>) -> Vector3<f32> {
+        match self {
+            Gravity::Uniform(acceleration) => (-acceleration)
+                .try_normalize(f32::EPSILON)
+                .unwrap_or_else(Vector3::y),
+            Gravity::Radial { center, .. } => (position - center)
+                .try_normalize(f32::EPSILON)
+                .unwrap_or_else(Vector3::y),
+        }
+    }
+}
+
+/// One of the six faces of the cube a planet is built on.
+///
+/// Every block belongs to the face its direction from the planet centre points into.
+/// Within a face, blocks stay axis-aligned and the face normal is "up" for building.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CubeFace {
+    PosX,
+    NegX,
+    PosY,
+    NegY,
+    PosZ,
+    NegZ,
+}
+
+impl CubeFace {
+    pub const ALL: [CubeFace; 6] = [
+        CubeFace::PosX,
+        CubeFace::NegX,
+        CubeFace::PosY,
+        CubeFace::NegY,
+        CubeFace::PosZ,
+        CubeFace::NegZ,
+    ];
+
+    /// The face a direction points into; the largest component wins.
+    pub fn from_direction(direction: &Vector3<f64>) -> Self {
+        let abs = direction.abs();
+        if abs.x >= abs.y && abs.x >= abs.z {
+            if direction.x >= 0.0 {
+                CubeFace::PosX
+            } else {
+                CubeFace::NegX
+            }
+        } else if abs.y >= abs.z {
+            if direction.y >= 0.0 {
+                CubeFace::PosY
+            } else {
+                CubeFace::NegY
+            }
+        } else if direction.z >= 0.0 {
+            CubeFace::PosZ
+        } else {
+            CubeFace::NegZ
+        }
+    }
+
+    /// Axis (0 = x, 1 = y, 2 = z) and sign of the outward normal.
+    pub fn axis(&self) -> (usize, i32) {
+        match self {
+            CubeFace::PosX => (0, 1),
+            CubeFace::NegX => (0, -1),
+            CubeFace::PosY => (1, 1),
+            CubeFace::NegY => (1, -1),
+            CubeFace::PosZ => (2, 1),
+            CubeFace::NegZ => (2, -1),
+        }
+    }
+
+    /// Outward normal as a block offset.
+    pub fn normal(&self) -> [i32; 3] {
+        let (axis, sign) = self.axis();
+        let mut normal = [0; 3];
+        normal[axis] = sign;
+        normal
+    }
+
+    /// The two axes spanning the face.
+    pub fn tangent_axes(&self) -> (usize, usize) {
+        let (axis, _) = self.axis();
+        ((axis + 1) % 3, (axis + 2) % 3)
+    }
+
+    /// Face coordinates in [-1, 1] of the point where a direction crosses the face's
+    /// plane of the unit cube.
+    pub fn face_coords(&self, direction: &Vector3<f64>) -> (f64, f64) {
+        let (axis, _) = self.axis();
+        let (u_axis, v_axis) = self.tangent_axes();
+        let distance = direction[axis].abs().max(f64::EPSILON);
+        (
+            (direction[u_axis] / distance).clamp(-1.0, 1.0),
+            (direction[v_axis] / distance).clamp(-1.0, 1.0),
+        )
+    }
+
+    /// Point on the unit sphere for face coordinates `u` and `v` in [-1, 1].
+    pub fn sphere_point(&self, u: f64, v: f64) -> Vector3<f64> {
+        let (axis, sign) = self.axis();
+        let (u_axis, v_axis) = self.tangent_axes();
+        let mut cube = Vector3::zeros();
+        cube[axis] = sign as f64;
+        cube[u_axis] = u.clamp(-1.0, 1.0);
+        cube[v_axis] = v.clamp(-1.0, 1.0);
+        cube_to_sphere(&cube)
+    }
+}
+
+/// Maps a point on the surface of the unit cube onto the unit sphere.
+///
+/// Spreads cells more evenly than normalizing, so face corners are not squeezed.
+pub fn cube_to_sphere(p: &Vector3<f64>) -> Vector3<f64> {
+    let (x2, y2, z2) = (p.x * p.x, p.y * p.y, p.z * p.z);
+    Vector3::new(
+        p.x * (1.0 - y2 / 2.0 - z2 / 2.0 + y2 * z2 / 3.0).sqrt(),
+        p.y * (1.0 - z2 / 2.0 - x2 / 2.0 + z2 * x2 / 3.0).sqrt(),
+        p.z * (1.0 - x2 / 2.0 - y2 / 2.0 + x2 * y2 / 3.0).sqrt(),
+    )
+}
+
+/// A planet centred on the world origin.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Planet {
+    /// Distance from the centre to sea level, in blocks.
+    pub radius: f32,
+    /// Gravitational acceleration at the surface.
+    pub surface_gravity: f32,
+}
+
+impl Default for Planet {
+    fn default() -> Self {
+        Self {
+            radius: 512.0,
+            surface_gravity: STANDARD_GRAVITY,
+        }
+    }
+}
+
+impl Planet {
+    pub fn gravity(&self) -> Gravity {
+        Gravity::Radial {
+            center: Point3::origin(),
+            strength: self.surface_gravity,
+        }
+    }
+
+    /// Unit vector pointing away from the planet centre.
+    pub fn up(&self, position: &Point3<f32>) -> Vector3<f32> {
+        self.gravity().up(position)
+    }
+
+    /// The cube face a block belongs to.
+    pub fn block_face(&self, pos: BlockPos) -> CubeFace {
+        let center = Vector3::new(pos.x as f64, pos.y as f64, pos.z as f64).add_scalar(0.5);
+        CubeFace::from_direction(&center)
+    }
+
+    /// Axis-aligned up for placing blocks at `pos`: the normal of its cube face.
+    pub fn block_up(&self, pos: BlockPos) -> [i32; 3] {
+        self.block_face(pos).normal()
+    }
+}
+
+// Key of a terrain column: its cube face and cell on that face.
+type ColumnKey = (CubeFace, i64, i64);
+
+// Surface of a terrain column, with its biome once a block near the surface needs it.
+struct Column {
+    direction: Vector3<f64>,
+    surface_radius: f64,
+    biome: Option<Biome>,
+}
+
+/// Terrain wrapped around a planet.
+///
+/// Uses the same settings as the flat generator, with heights measured along the radius
+/// from the planet's sea level and `bedrock_level` giving the depth of the core. Each cube
+/// face is divided into a grid of columns about a block wide at sea level, and every block
+/// in a column shares its surface height and biome, so terrain lines up with the face.
+#[derive(Clone)]
+pub struct PlanetGenerator {
+    planet: Planet,
+    terrain: DefaultGenerator,
+}
+
+impl PlanetGenerator {
+    pub fn new(seed: u64, planet: Planet, settings: TerrainSettings) -> Self {
+        Self {
+            planet,
+            terrain: DefaultGenerator::new(seed, settings),
+        }
+    }
+
+    pub fn planet(&self) -> &Planet {
+        &self.planet
+    }
+
+    fn settings(&self) -> &TerrainSettings {
+        self.terrain.settings()
+    }
+
+    fn sea_radius(&self) -> f64 {
+        self.planet.radius as f64 + self.settings().sea_level as f64
+    }
+
+    fn core_radius(&self) -> f64 {
+        self.planet.radius as f64 + self.settings().bedrock_level as f64
+    }
+
+    // Lowest and highest possible surface radius.
+    fn surface_range(&self) -> (f64, f64) {
+        let settings = self.settings();
+        let base = self.planet.radius as f64 + settings.base_height;
+        (
+            base - settings.height_variation,
+            base + settings.height_variation,
+        )
+    }
+
+    /// Distance from the centre to the top of the terrain along a unit direction.
+    pub fn surface_radius(&self, direction: &Vector3<f64>) -> f64 {
+        let settings = self.settings();
+        let p = direction * self.planet.radius as f64 / settings.height_scale;
+        let noise = self.terrain.height.fbm3(p.x, p.y, p.z, 5);
+        self.planet.radius as f64 + settings.base_height + noise * settings.height_variation
+    }
+
+    // The column a direction falls in when columns are `step` blocks wide, and the unit
+    // direction through the column's centre on the cube-sphere.
+    fn column(&self, direction: &Vector3<f64>, step: f64) -> (ColumnKey, Vector3<f64>) {
+        let face = CubeFace::from_direction(direction);
+        let (u, v) = face.face_coords(direction);
+        // A quarter of the circumference spans a face.
+        let cells = (std::f64::consts::FRAC_PI_2 * self.planet.radius as f64 / step)
+            .ceil()
+            .max(1.0);
+        let cell = |t: f64| ((t + 1.0) / 2.0 * cells).floor().min(cells - 1.0);
+        let center = |cell: f64| (cell + 0.5) / cells * 2.0 - 1.0;
+        let (cu, cv) = (cell(u), cell(v));
+        (
+            (face, cu as i64, cv as i64),
+            face.sphere_point(center(cu), center(cv)),
+        )
+    }
+
+    /// Biome along a unit direction. Poles are cold, the equator is warm.
+    pub fn biome(&self, direction: &Vector3<f64>) -> Biome {
+        let p = direction * self.planet.radius as f64 / self.settings().climate_scale;
+        let latitude = direction.y.abs();
+        let temperature = 0.75 * (1.0 - latitude)
+            + 0.25 * (self.terrain.temperature.fbm3(p.x, p.y, p.z, 3) + 1.0) * 0.5;
+        let humidity = (self.terrain.humidity.fbm3(p.x, p.y, p.z, 3) + 1.0) * 0.5;
+        Biome::from_climate(temperature, humidity)
+    }
+}
+
+impl PlanetGenerator {
+    // Fills a chunk whose blocks each cover `scale`³ world blocks from `origin`.
+    // Caves and ores are only carved at full detail.
+    fn generate_scaled(&self, origin: BlockPos, scale: i32, registry: &BlockRegistry) -> Chunk {
+        let blocks = TerrainBlocks::new(registry);
+        let detailed = scale == 1;
+        let step = scale as f64;
+        let half = CHUNK_SIZE as f64 * step / 2.0;
+        let chunk_center = Vector3::new(origin.x as f64, origin.y as f64, origin.z as f64)
+            .add_scalar(half)
+            .norm();
+        let half_diagonal = half * 3f64.sqrt();
+
+        let sea_radius = self.sea_radius();
+        let core_radius = self.core_radius();
+        let (min_surface, max_surface) = self.surface_range();
+
+        // Chunks entirely in space or entirely inside the core.
+        if chunk_center - half_diagonal > max_surface.max(sea_radius) {
+            return Chunk::new();
+        }
+        if chunk_center + half_diagonal <= core_radius {
+            return Chunk::filled(blocks.bedrock);
+        }
+
+        let mut columns: HashMap<ColumnKey, Column> = HashMap::new();
+        let mut chunk = Chunk::new();
+        for y in 0..CHUNK_SIZE {
+            for z in 0..CHUNK_SIZE {
+                for x in 0..CHUNK_SIZE {
+                    let (wx, wy, wz) = (
+                        origin.x + x as i32 * scale,
+                        origin.y + y as i32 * scale,
+                        origin.z + z as i32 * scale,
+                    );
+                    let p = Vector3::new(wx as f64, wy as f64, wz as f64).add_scalar(step / 2.0);
+                    let r = p.norm();
+
+                    let block = if r <= core_radius {
+                        blocks.bedrock
+                    } else if r > max_surface {
+                        if r <= sea_radius {
+                            blocks.water
+                        } else {
+                            AIR
+                        }
+                    } else {
+                        // Only blocks near the surface need their column.
+                        let column = if r < min_surface - step - 3.0 {
+                            None
+                        } else {
+                            let (key, direction) = self.column(&(p / r), step);
+                            Some(columns.entry(key).or_insert_with(|| Column {
+                                surface_radius: self.surface_radius(&direction),
+                                direction,
+                                biome: None,
+                            }))
+                        };
+                        let surface_radius = column
+                            .as_ref()
+                            .map_or(min_surface, |column| column.surface_radius);
+                        let depth = surface_radius - r;
+
+                        if depth < 0.0 {
+                            if r <= sea_radius {
+                                blocks.water
+                            } else {
+                                AIR
+                            }
+                        } else if detailed && depth > 3.0 && self.terrain.is_cave(wx, wy, wz) {
+                            AIR
+                        } else if let Some(column) = column.filter(|_| depth < step + 3.0) {
+                            let biome = *column
+                                .biome
+                                .get_or_insert_with(|| self.biome(&column.direction));
+                            let (surface, subsurface) =
+                                blocks.column(registry, biome, surface_radius <= sea_radius + 1.0);
+                            if depth < step {
+                                surface
+                            } else {
+                                subsurface
+                            }
+                        } else if detailed {
+                            let depth = (sea_radius - r) as i32;
+                            self.terrain
+                                .ore_at(wx, wy, wz, depth, registry)
+                                .unwrap_or(blocks.stone)
+                        } else {
+                            blocks.stone
+                        }
+                    };
+
+                    if block != AIR {
+                        chunk.set(x, y, z, block);
+                    }
+                }
+            }
+        }
+        chunk
+    }
+}
+
+impl TerrainGenerator for PlanetGenerator {
+    fn generate(&self, pos: ChunkPos, registry: &BlockRegistry) -> Chunk {
+        self.generate_scaled(pos.origin(), 1, registry)
+    }
+
+    fn share(&self) -> Option<Arc<dyn TerrainGenerator + Send + Sync>> {
+        Some(Arc::new(self.clone()))
+    }
+
+    fn generate_lod(
+        &self,
+        origin: BlockPos,
+        scale: i32,
+        registry: &BlockRegistry,
+    ) -> Option<Chunk> {
+        Some(self.generate_scaled(origin, scale, registry))
+    }
+}
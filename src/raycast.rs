@@ -0,0 +1,203 @@
+// Copyright 2025 Nicholas Jordan. All Rights Reserved.
+// github.com/cvusmo/lustre
+// src/raycast.rs
+
+use crate::block::{BlockId, AIR};
+use crate::scripting::vector_from_table;
+use crate::world::{BlockPos, World};
+
+use mlua::prelude::*;
+use rapier3d::na::{Point3, Vector3};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// How far away blocks can be mined or placed, in blocks.
+pub const REACH: f32 = 6.0;
+
+/// Longest ray scripts may cast, in blocks.
+pub const MAX_SCRIPT_DISTANCE: f32 = REACH * 64.0;
+
+// Rays are cut short here, well before f32 distances stop growing one block at a time.
+const MAX_RAY_LENGTH: f32 = 65536.0;
+
+/// The first block a ray entered.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RaycastHit {
+    pub block: BlockPos,
+    pub id: BlockId,
+    /// Outward normal of the face the ray entered through; zero if the ray started inside.
+    pub normal: [i32; 3],
+    /// Distance from the origin to where the ray entered the block.
+    pub distance: f32,
+}
+
+impl RaycastHit {
+    /// The block in front of the hit face, where a placed block goes.
+    pub fn adjacent(&self) -> BlockPos {
+        let [x, y, z] = self.normal;
+        self.block.offset(x, y, z)
+    }
+}
+
+/// Whether a ray should stop at a block: anything but air and fluids.
+pub fn is_targetable(world: &World, id: BlockId) -> bool {
+    id != AIR && !world.registry().properties(id).fluid
+}
+
+/// Casts a ray through the world and returns the first targetable block within
+/// `max_distance`.
+pub fn raycast(
+    world: &World,
+    origin: Point3<f32>,
+    direction: Vector3<f32>,
+    max_distance: f32,
+) -> Option<RaycastHit> {
+    raycast_with(world, origin, direction, max_distance, |id| {
+        is_targetable(world, id)
+    })
+}
+
+/// Casts a ray through the world and returns the first block `hits` accepts.
+///
+/// Walks the grid one block at a time (Amanatides & Woo), so every block the ray
+/// touches is visited in order and none are skipped at grazing angles. Rays stop after
+/// 65536 blocks whatever `max_distance` is.
+pub fn raycast_with(
+    world: &World,
+    origin: Point3<f32>,
+    direction: Vector3<f32>,
+    max_distance: f32,
+    hits: impl Fn(BlockId) -> bool,
+) -> Option<RaycastHit> {
+    let direction = direction.try_normalize(f32::EPSILON)?;
+    let mut block = [
+        origin.x.floor() as i32,
+        origin.y.floor() as i32,
+        origin.z.floor() as i32,
+    ];
+    let mut step = [0; 3];
+    // Distance along the ray to the next grid plane on each axis, and between planes.
+    let mut next = [f32::INFINITY; 3];
+    let mut delta = [f32::INFINITY; 3];
+    for axis in 0..3 {
+        let d = direction[axis];
+        if d > 0.0 {
+            step[axis] = 1;
+            delta[axis] = 1.0 / d;
+            next[axis] = (block[axis] as f32 + 1.0 - origin[axis]) / d;
+        } else if d < 0.0 {
+            step[axis] = -1;
+            delta[axis] = -1.0 / d;
+            next[axis] = (origin[axis] - block[axis] as f32) / -d;
+        }
+    }
+
+    // A ray crosses at most three grid planes per block travelled, so counting steps
+    // bounds the walk even for huge or infinite `max_distance`.
+    let max_steps = (max_distance.clamp(0.0, MAX_RAY_LENGTH) * 3.0) as usize + 3;
+    let mut distance = 0.0;
+    let mut normal = [0; 3];
+    for _ in 0..max_steps {
+        if distance > max_distance {
+            break;
+        }
+        let pos = BlockPos::new(block[0], block[1], block[2]);
+        let id = world.get_block(pos);
+        if hits(id) {
+            return Some(RaycastHit {
+                block: pos,
+                id,
+                normal,
+                distance,
+            });
+        }
+
+        let axis = if next[0] < next[1] && next[0] < next[2] {
+            0
+        } else if next[1] < next[2] {
+            1
+        } else {
+            2
+        };
+        distance = next[axis];
+        next[axis] += delta[axis];
+        block[axis] += step[axis];
+        normal = [0; 3];
+        normal[axis] = -step[axis];
+    }
+    None
+}
+
+/// Replaces the hit block with air unless it is unbreakable. Returns the block removed.
+pub fn mine_block(world: &mut World, hit: &RaycastHit) -> Option<BlockId> {
+    if !can_mine(world, hit) {
+        return None;
+    }
+    Some(world.set_block(hit.block, AIR))
+}
+
+/// Whether the hit block can be mined; unbreakable blocks have negative hardness.
+pub fn can_mine(world: &World, hit: &RaycastHit) -> bool {
+    world.registry().properties(hit.id).hardness >= 0.0
+}
+
+/// Places a block against the hit face if that space is air or fluid. Returns where.
+pub fn place_block(world: &mut World, hit: &RaycastHit, block: BlockId) -> Option<BlockPos> {
+    let pos = placement(world, hit)?;
+    world.set_block(pos, block);
+    Some(pos)
+}
+
+/// Where a block placed against the hit face would go, if that space is air or fluid.
+pub fn placement(world: &World, hit: &RaycastHit) -> Option<BlockPos> {
+    let pos = hit.adjacent();
+    if hit.normal == [0; 3] || is_targetable(world, world.get_block(pos)) {
+        return None;
+    }
+    Some(pos)
+}
+
+/// Adds `world.raycast` to the `world` table in Lua, creating the table if needed.
+///
+/// `world.raycast(origin, direction, max_distance)` takes `{x, y, z}` tables and returns
+/// nil or `{x, y, z, block, normal = {x, y, z}, distance}`. `max_distance` defaults to
+/// `REACH`, must be finite and is capped at `MAX_SCRIPT_DISTANCE`.
+pub fn register_lua_api(lua: &Lua, world: Rc<RefCell<World>>) -> LuaResult<()> {
+    let table = match lua.globals().get::<Option<LuaTable>>("world")? {
+        Some(table) => table,
+        None => lua.create_table()?,
+    };
+
+    let raycast_fn = lua.create_function(
+        move |lua, (origin, direction, max_distance): (LuaTable, LuaTable, Option<f32>)| {
+            let origin = Point3::from(vector_from_table(&origin)?);
+            let direction = vector_from_table(&direction)?;
+            let max_distance = max_distance.unwrap_or(REACH);
+            if !max_distance.is_finite() {
+                return Err(LuaError::runtime("max_distance must be a finite number"));
+            }
+            let max_distance = max_distance.min(MAX_SCRIPT_DISTANCE);
+            let world = world.borrow();
+            let Some(hit) = raycast(&world, origin, direction, max_distance) else {
+                return Ok(None);
+            };
+
+            let normal = lua.create_table()?;
+            normal.set("x", hit.normal[0])?;
+            normal.set("y", hit.normal[1])?;
+            normal.set("z", hit.normal[2])?;
+            let result = lua.create_table()?;
+            result.set("x", hit.block.x)?;
+            result.set("y", hit.block.y)?;
+            result.set("z", hit.block.z)?;
+            result.set("block", hit.id)?;
+            result.set("normal", normal)?;
+            result.set("distance", hit.distance)?;
+            Ok(Some(result))
+        },
+    )?;
+    table.set("raycast", raycast_fn)?;
+
+    lua.globals().set("world", table)?;
+    Ok(())
+}
@@ -0,0 +1,276 @@
+// Copyright 2025 Nicholas Jordan. All Rights Reserved.
+// github.com/cvusmo/lustre
+// src/region.rs
+
+use crate::block::{BlockRegistry, AIR};
+use crate::chunk::{Chunk, PackedArray, CHUNK_VOLUME};
+use crate::world::ChunkPos;
+
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+/// Edge length of a region in chunks.
+pub const REGION_SIZE: i32 = 16;
+
+/// Number of chunk slots in a region file.
+pub const REGION_VOLUME: usize = (REGION_SIZE * REGION_SIZE * REGION_SIZE) as usize;
+
+/// Current region file format. Files with another version are rejected.
+pub const REGION_VERSION: u32 = 1;
+
+const MAGIC: &[u8; 4] = b"LRGN";
+
+// Magic, version, then one index entry per chunk slot.
+const ENTRY_SIZE: usize = 12;
+const HEADER_SIZE: u64 = (8 + REGION_VOLUME * ENTRY_SIZE) as u64;
+
+// How a chunk's payload is stored; the first byte of every payload.
+const COMPRESSION_ZLIB: u8 = 1;
+
+// Most bytes a chunk's payload may decompress to, so a damaged file can't exhaust memory.
+const MAX_CHUNK_DATA: u64 = 16 << 20;
+
+/// Position of a region, in region units.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RegionPos {
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+}
+
+impl RegionPos {
+    /// The region containing a chunk.
+    pub fn of_chunk(pos: ChunkPos) -> Self {
+        Self {
+            x: pos.x.div_euclid(REGION_SIZE),
+            y: pos.y.div_euclid(REGION_SIZE),
+            z: pos.z.div_euclid(REGION_SIZE),
+        }
+    }
+
+    /// Slot of a chunk within its region's index.
+    pub fn slot(pos: ChunkPos) -> usize {
+        let (x, y, z) = (
+            pos.x.rem_euclid(REGION_SIZE),
+            pos.y.rem_euclid(REGION_SIZE),
+            pos.z.rem_euclid(REGION_SIZE),
+        );
+        (x + z * REGION_SIZE + y * REGION_SIZE * REGION_SIZE) as usize
+    }
+
+    /// File name of the region, e.g. `r.0.-1.2.lrg`.
+    pub fn file_name(&self) -> String {
+        format!("r.{}.{}.{}.lrg", self.x, self.y, self.z)
+    }
+}
+
+// Where a chunk's payload lives in the file. A length of zero means no chunk is stored.
+#[derive(Debug, Clone, Copy, Default)]
+struct IndexEntry {
+    offset: u64,
+    length: u32,
+}
+
+/// A file holding up to `REGION_VOLUME` compressed chunks.
+///
+/// The header holds a magic number, the format version and an index of where each chunk's
+/// payload is. Payloads that still fit are rewritten in place; larger ones are appended.
+pub struct RegionFile {
+    file: File,
+    index: Vec<IndexEntry>,
+    end: u64,
+}
+
+impl RegionFile {
+    /// Opens a region file, creating it with an empty index if it does not exist.
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+        let end = file.metadata()?.len();
+
+        if end == 0 {
+            let mut header = Vec::with_capacity(HEADER_SIZE as usize);
+            header.extend_from_slice(MAGIC);
+            header.extend_from_slice(&REGION_VERSION.to_le_bytes());
+            header.resize(HEADER_SIZE as usize, 0);
+            file.write_all(&header)?;
+            return Ok(Self {
+                file,
+                index: vec![IndexEntry::default(); REGION_VOLUME],
+                end: HEADER_SIZE,
+            });
+        }
+
+        let mut header = vec![0; HEADER_SIZE as usize];
+        file.read_exact(&mut header)?;
+        if &header[0..4] != MAGIC {
+            return Err(invalid_data("not a region file"));
+        }
+        let version = u32::from_le_bytes(header[4..8].try_into().unwrap());
+        if version != REGION_VERSION {
+            return Err(invalid_data(&format!(
+                "unsupported region version {} (expected {})",
+                version, REGION_VERSION
+            )));
+        }
+
+        let index = header[8..]
+            .chunks_exact(ENTRY_SIZE)
+            .map(|entry| IndexEntry {
+                offset: u64::from_le_bytes(entry[0..8].try_into().unwrap()),
+                length: u32::from_le_bytes(entry[8..12].try_into().unwrap()),
+            })
+            .collect();
+        Ok(Self { file, index, end })
+    }
+
+    /// Whether a chunk is stored.
+    pub fn contains(&self, pos: ChunkPos) -> bool {
+        self.index[RegionPos::slot(pos)].length > 0
+    }
+
+    /// Reads a stored chunk, mapping its saved block names to ids in `registry`.
+    pub fn read_chunk(
+        &mut self,
+        pos: ChunkPos,
+        registry: &BlockRegistry,
+    ) -> io::Result<Option<Chunk>> {
+        let entry = self.index[RegionPos::slot(pos)];
+        if entry.length == 0 {
+            return Ok(None);
+        }
+        if entry.offset.saturating_add(entry.length as u64) > self.end {
+            return Err(invalid_data("chunk lies past the end of the region file"));
+        }
+        let mut payload = vec![0; entry.length as usize];
+        self.file.seek(SeekFrom::Start(entry.offset))?;
+        self.file.read_exact(&mut payload)?;
+        decode_chunk(&payload, registry).map(Some)
+    }
+
+    /// Stores a chunk, replacing any previous copy.
+    pub fn write_chunk(
+        &mut self,
+        pos: ChunkPos,
+        chunk: &Chunk,
+        registry: &BlockRegistry,
+    ) -> io::Result<()> {
+        let payload = encode_chunk(chunk, registry)?;
+        let slot = RegionPos::slot(pos);
+        let old = self.index[slot];
+
+        let offset = if old.length as usize >= payload.len() {
+            old.offset
+        } else {
+            self.end
+        };
+        self.file.seek(SeekFrom::Start(offset))?;
+        self.file.write_all(&payload)?;
+        self.end = self.end.max(offset + payload.len() as u64);
+
+        let entry = IndexEntry {
+            offset,
+            length: payload.len() as u32,
+        };
+        let mut bytes = [0; ENTRY_SIZE];
+        bytes[0..8].copy_from_slice(&entry.offset.to_le_bytes());
+        bytes[8..12].copy_from_slice(&entry.length.to_le_bytes());
+        self.file
+            .seek(SeekFrom::Start(8 + (slot * ENTRY_SIZE) as u64))?;
+        self.file.write_all(&bytes)?;
+        self.index[slot] = entry;
+        Ok(())
+    }
+
+    /// Flushes written chunks to disk.
+    pub fn sync(&mut self) -> io::Result<()> {
+        self.file.sync_data()
+    }
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+// Palette as block names, so saves survive changes to registration order, then the
+// packed indices. Everything after the compression byte is zlib-compressed.
+fn encode_chunk(chunk: &Chunk, registry: &BlockRegistry) -> io::Result<Vec<u8>> {
+    let mut data = Vec::new();
+    data.extend_from_slice(&(chunk.palette().len() as u32).to_le_bytes());
+    for &id in chunk.palette() {
+        let name = registry.get(id).map_or("", |block| block.name.as_str());
+        data.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        data.extend_from_slice(name.as_bytes());
+    }
+    let indices = chunk.indices();
+    data.extend_from_slice(&indices.bits().to_le_bytes());
+    for word in indices.words() {
+        data.extend_from_slice(&word.to_le_bytes());
+    }
+
+    let mut encoder = ZlibEncoder::new(vec![COMPRESSION_ZLIB], Compression::default());
+    encoder.write_all(&data)?;
+    encoder.finish()
+}
+
+fn decode_chunk(payload: &[u8], registry: &BlockRegistry) -> io::Result<Chunk> {
+    let Some((&COMPRESSION_ZLIB, compressed)) = payload.split_first() else {
+        return Err(invalid_data("unknown chunk compression"));
+    };
+    let mut data = Vec::new();
+    ZlibDecoder::new(compressed)
+        .take(MAX_CHUNK_DATA + 1)
+        .read_to_end(&mut data)?;
+    if data.len() as u64 > MAX_CHUNK_DATA {
+        return Err(invalid_data("chunk data too large"));
+    }
+
+    let mut reader = data.as_slice();
+    // Every entry takes at least its two length bytes.
+    let palette_len = read_u32(&mut reader)? as usize;
+    if palette_len > CHUNK_VOLUME || palette_len > reader.len() / 2 {
+        return Err(invalid_data("corrupt chunk palette"));
+    }
+    let mut palette = Vec::with_capacity(palette_len);
+    for _ in 0..palette_len {
+        let len = read_u16(&mut reader)? as usize;
+        let name = take(&mut reader, len)?;
+        let name = std::str::from_utf8(name).map_err(|_| invalid_data("invalid block name"))?;
+        // Blocks removed since the chunk was saved turn into air.
+        palette.push(registry.id_of(name).unwrap_or(AIR));
+    }
+
+    let bits = read_u32(&mut reader)?;
+    let words = reader
+        .chunks_exact(8)
+        .map(|word| u64::from_le_bytes(word.try_into().unwrap()))
+        .collect();
+    PackedArray::from_raw(bits, CHUNK_VOLUME, words)
+        .and_then(|indices| Chunk::from_parts(palette, indices))
+        .ok_or_else(|| invalid_data("corrupt chunk data"))
+}
+
+fn take<'a>(reader: &mut &'a [u8], len: usize) -> io::Result<&'a [u8]> {
+    if reader.len() < len {
+        return Err(invalid_data("truncated chunk data"));
+    }
+    let (bytes, rest) = reader.split_at(len);
+    *reader = rest;
+    Ok(bytes)
+}
+
+fn read_u16(reader: &mut &[u8]) -> io::Result<u16> {
+    Ok(u16::from_le_bytes(take(reader, 2)?.try_into().unwrap()))
+}
+
+fn read_u32(reader: &mut &[u8]) -> io::Result<u32> {
+    Ok(u32::from_le_bytes(take(reader, 4)?.try_into().unwrap()))
+}
@@ -2,31 +2,41 @@
 // github.com/cvusmo/lustre
 // src/render.rs
 
+use std::collections::HashMap;
+use std::error::Error;
 use std::sync::Arc;
 
+use crate::camera::Camera;
+use crate::mesher::{block_outline, ChunkMesh, MeshKey};
 #[warn(unused_imports)]
 use crate::shaders::fs;
 use crate::shaders::vs;
-use crate::state::log_info;
+use crate::state::{log_error, log_info, log_warn};
+use crate::world::BlockPos;
 
-use image::{ImageBuffer, Rgba};
+use rapier3d::na::Matrix4;
+use vulkano::buffer::allocator::{SubbufferAllocator, SubbufferAllocatorCreateInfo};
 use vulkano::buffer::{Buffer, BufferContents, BufferCreateInfo, BufferUsage, Subbuffer};
 use vulkano::command_buffer::allocator::{
     StandardCommandBufferAllocator, StandardCommandBufferAllocatorCreateInfo,
 };
 use vulkano::command_buffer::{
-    AutoCommandBufferBuilder, CommandBufferUsage, CopyImageToBufferInfo, PrimaryAutoCommandBuffer,
-    RenderPassBeginInfo, SubpassBeginInfo,
+    AutoCommandBufferBuilder, CommandBufferUsage, PrimaryAutoCommandBuffer, RenderPassBeginInfo,
+    SubpassBeginInfo,
 };
+use vulkano::descriptor_set::allocator::StandardDescriptorSetAllocator;
+use vulkano::descriptor_set::{DescriptorSet, WriteDescriptorSet};
 use vulkano::device::physical::{PhysicalDevice, PhysicalDeviceType};
 use vulkano::device::{
     Device, DeviceCreateInfo, DeviceExtensions, Queue, QueueCreateInfo, QueueFlags,
 };
+use vulkano::format::Format;
 use vulkano::image::view::ImageView;
-use vulkano::image::{Image, ImageUsage};
+use vulkano::image::{Image, ImageCreateInfo, ImageType, ImageUsage};
 use vulkano::instance::Instance;
 use vulkano::memory::allocator::{AllocationCreateInfo, MemoryTypeFilter, StandardMemoryAllocator};
 use vulkano::pipeline::graphics::color_blend::{ColorBlendAttachmentState, ColorBlendState};
+use vulkano::pipeline::graphics::depth_stencil::{DepthState, DepthStencilState};
 use vulkano::pipeline::graphics::input_assembly::InputAssemblyState;
 use vulkano::pipeline::graphics::multisample::MultisampleState;
 use vulkano::pipeline::graphics::rasterization::RasterizationState;
@@ -34,28 +44,103 @@ use vulkano::pipeline::graphics::vertex_input::{Vertex, VertexDefinition};
 use vulkano::pipeline::graphics::viewport::{Viewport, ViewportState};
 use vulkano::pipeline::graphics::GraphicsPipelineCreateInfo;
 use vulkano::pipeline::layout::PipelineDescriptorSetLayoutCreateInfo;
-use vulkano::pipeline::{GraphicsPipeline, PipelineLayout, PipelineShaderStageCreateInfo};
+use vulkano::pipeline::{
+    DynamicState, GraphicsPipeline, Pipeline, PipelineBindPoint, PipelineLayout,
+    PipelineShaderStageCreateInfo,
+};
 use vulkano::render_pass::{Framebuffer, FramebufferCreateInfo, RenderPass, Subpass};
 use vulkano::shader::ShaderModule;
 use vulkano::swapchain::{
     PresentMode, Surface, Swapchain, SwapchainCreateInfo, SwapchainPresentInfo,
 };
 use vulkano::sync::{self, GpuFuture};
+use vulkano::{Validated, VulkanError};
+
+/// Format of the depth attachment shared by the window and offscreen render passes.
+pub const DEPTH_FORMAT: Format = Format::D16_UNORM;
 
 // Create Vertices
-#[derive(BufferContents, Vertex)]
+#[derive(BufferContents, Vertex, Clone, Copy, Debug, PartialEq)]
 #[repr(C)]
-struct MainVertex {
+pub struct MainVertex {
+    #[format(R32G32B32_SFLOAT)]
+    pub position: [f32; 3],
+    #[format(R32G32B32_SFLOAT)]
+    pub normal: [f32; 3],
     #[format(R32G32_SFLOAT)]
-    position: [f32; 2],
+    pub uv: [f32; 2],
+    #[format(R32G32B32_SFLOAT)]
+    pub color: [f32; 3],
+    /// Ambient occlusion brightness, 1.0 when unoccluded.
+    #[format(R32_SFLOAT)]
+    pub ao: f32,
+}
+
+/// Vertex and index buffers of one mesh on the GPU.
+#[derive(Clone)]
+pub struct GpuMesh {
+    pub vertex_buffer: Subbuffer<[MainVertex]>,
+    pub index_buffer: Subbuffer<[u32]>,
+}
+
+impl GpuMesh {
+    /// Uploads an indexed triangle list. Both slices must be non-empty.
+    pub fn upload(
+        memory_allocator: Arc<StandardMemoryAllocator>,
+        vertices: &[MainVertex],
+        indices: &[u32],
+    ) -> Result<Self, Box<dyn Error>> {
+        let allocation_info = AllocationCreateInfo {
+            memory_type_filter: MemoryTypeFilter::PREFER_DEVICE
+                | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
+            ..Default::default()
+        };
+
+        let vertex_buffer = Buffer::from_iter(
+            memory_allocator.clone(),
+            BufferCreateInfo {
+                usage: BufferUsage::VERTEX_BUFFER,
+                ..Default::default()
+            },
+            allocation_info.clone(),
+            vertices.iter().copied(),
+        )?;
+        let index_buffer = Buffer::from_iter(
+            memory_allocator,
+            BufferCreateInfo {
+                usage: BufferUsage::INDEX_BUFFER,
+                ..Default::default()
+            },
+            allocation_info,
+            indices.iter().copied(),
+        )?;
+
+        Ok(Self {
+            vertex_buffer,
+            index_buffer,
+        })
+    }
+
+    pub fn index_count(&self) -> u32 {
+        self.index_buffer.len() as u32
+    }
+}
+
+/// Builds the camera uniform block from view and projection matrices.
+pub fn camera_uniform(view: &Matrix4<f32>, proj: &Matrix4<f32>) -> vs::Camera {
+    vs::Camera {
+        view: (*view).into(),
+        proj: (*proj).into(),
+    }
 }
 
 // Get Physical Device
+// Without a surface, any graphics-capable queue family is accepted (headless rendering).
 pub fn get_physical_device(
     instance: &Arc<Instance>,
-    surface: &Arc<Surface>,
+    surface: Option<&Arc<Surface>>,
     device_extensions: &DeviceExtensions,
-) -> (Arc<PhysicalDevice>, u32) {
+) -> Option<(Arc<PhysicalDevice>, u32)> {
     instance
         .enumerate_physical_devices()
         .expect("failed to enumerate physical devices")
@@ -66,7 +151,9 @@ pub fn get_physical_device(
                 .enumerate()
                 .position(|(i, q)| {
                     q.queue_flags.contains(QueueFlags::GRAPHICS)
-                        && p.surface_support(i as u32, surface).unwrap_or(false)
+                        && surface.is_none_or(|surface| {
+                            p.surface_support(i as u32, surface).unwrap_or(false)
+                        })
                 })
                 .map(|q| (p, q as u32))
         })
@@ -77,34 +164,58 @@ pub fn get_physical_device(
             PhysicalDeviceType::Cpu => 3,
             _ => 4,
         })
-        .expect("no device available")
 }
 
 // Get Render Pass
-fn get_render_pass(device: Arc<Device>, swapchain: Arc<Swapchain>) -> Arc<RenderPass> {
+pub(crate) fn get_render_pass(device: Arc<Device>, format: Format) -> Arc<RenderPass> {
     vulkano::single_pass_renderpass!(
         device,
         attachments: {
             color: {
-                format: swapchain.image_format(), // Use format as swapchain
+                format: format, // Swapchain format, or the offscreen image format
                 samples: 1,
                 load_op: Clear,
                 store_op: Store,
             },
+            depth_stencil: {
+                format: DEPTH_FORMAT,
+                samples: 1,
+                load_op: Clear,
+                store_op: DontCare,
+            },
         },
         pass: {
             color: [color],
-            depth_stencil: {},
+            depth_stencil: {depth_stencil},
         },
     )
     .unwrap()
 }
 
 // Get Framebuffers
-fn get_framebuffers(
+// All framebuffers share one depth buffer sized to the color images.
+pub(crate) fn get_framebuffers(
     swapchain_images: &[Arc<Image>],
     render_pass: Arc<RenderPass>,
+    memory_allocator: Arc<StandardMemoryAllocator>,
 ) -> Vec<Arc<Framebuffer>> {
+    let extent = swapchain_images[0].extent();
+    let depth_buffer = ImageView::new_default(
+        Image::new(
+            memory_allocator,
+            ImageCreateInfo {
+                image_type: ImageType::Dim2d,
+                format: DEPTH_FORMAT,
+                extent: [extent[0], extent[1], 1],
+                usage: ImageUsage::DEPTH_STENCIL_ATTACHMENT | ImageUsage::TRANSIENT_ATTACHMENT,
+                ..Default::default()
+            },
+            AllocationCreateInfo::default(),
+        )
+        .unwrap(),
+    )
+    .unwrap();
+
     swapchain_images
         .iter()
         .map(|image| {
@@ -112,7 +223,7 @@ fn get_framebuffers(
             Framebuffer::new(
                 render_pass.clone(),
                 FramebufferCreateInfo {
-                    attachments: vec![view],
+                    attachments: vec![view, depth_buffer.clone()],
                     ..Default::default()
                 },
             )
@@ -122,12 +233,11 @@ fn get_framebuffers(
 }
 
 // Get graphic pipeline
-fn get_graphic_pipeline(
+pub(crate) fn get_graphic_pipeline(
     device: Arc<Device>,
     vs: Arc<ShaderModule>,
     fs: Arc<ShaderModule>,
     render_pass: Arc<RenderPass>,
-    viewport: Viewport,
 ) -> Arc<GraphicsPipeline> {
     let vs_entry = vs.entry_point("main").unwrap();
     let fs_entry = fs.entry_point("main").unwrap();
@@ -156,16 +266,19 @@ fn get_graphic_pipeline(
             stages: stages.into_iter().collect(),
             vertex_input_state: Some(vertex_input_state),
             input_assembly_state: Some(InputAssemblyState::default()),
-            viewport_state: Some(ViewportState {
-                viewports: [viewport].into_iter().collect(),
+            // The viewport is set per frame so the pipeline survives swapchain resizes.
+            viewport_state: Some(ViewportState::default()),
+            rasterization_state: Some(RasterizationState::default()),
+            depth_stencil_state: Some(DepthStencilState {
+                depth: Some(DepthState::simple()),
                 ..Default::default()
             }),
-            rasterization_state: Some(RasterizationState::default()),
             multisample_state: Some(MultisampleState::default()),
             color_blend_state: Some(ColorBlendState::with_attachment_states(
                 subpass.num_color_attachments(),
                 ColorBlendAttachmentState::default(),
             )),
+            dynamic_state: [DynamicState::Viewport].into_iter().collect(),
             subpass: Some(subpass.into()),
             ..GraphicsPipelineCreateInfo::layout(layout)
         },
@@ -173,221 +286,512 @@ fn get_graphic_pipeline(
     .unwrap()
 }
 
-// Get command buffers
-fn get_command_buffers(
-    command_buffer_allocator: Arc<StandardCommandBufferAllocator>,
-    queue: &Arc<Queue>,
+// Get the descriptor set binding the camera uniform buffer
+pub(crate) fn get_camera_set(
+    descriptor_set_allocator: Arc<StandardDescriptorSetAllocator>,
     graphic_pipeline: &Arc<GraphicsPipeline>,
-    framebuffer: &[Arc<Framebuffer>],
-    vertex_buffer: &Subbuffer<[MainVertex]>,
-) -> Vec<Arc<PrimaryAutoCommandBuffer>> {
-    framebuffer
-        .iter()
-        .map(|framebuffer| {
-            // Create a new command buffer builder for each framebuffer.
-            let mut builder = AutoCommandBufferBuilder::primary(
-                command_buffer_allocator.clone(),
-                queue.queue_family_index(),
-                CommandBufferUsage::MultipleSubmit,
+    camera_buffer: Subbuffer<vs::Camera>,
+) -> Arc<DescriptorSet> {
+    let layout = graphic_pipeline.layout().set_layouts()[0].clone();
+    DescriptorSet::new(
+        descriptor_set_allocator,
+        layout,
+        [WriteDescriptorSet::buffer(0, camera_buffer)],
+        [],
+    )
+    .unwrap()
+}
+
+// Record the scene's render pass into the given builder
+pub(crate) fn record_scene<'a>(
+    builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+    graphic_pipeline: &Arc<GraphicsPipeline>,
+    framebuffer: &Arc<Framebuffer>,
+    viewport: &Viewport,
+    camera_set: Arc<DescriptorSet>,
+    meshes: impl IntoIterator<Item = &'a GpuMesh>,
+) {
+    let mut render_pass_info = RenderPassBeginInfo::framebuffer(framebuffer.clone());
+    render_pass_info.clear_values = vec![Some([0.0, 0.0, 1.0, 1.0].into()), Some(1.0.into())];
+
+    let subpass_info = SubpassBeginInfo::default();
+
+    // Record commands:
+    unsafe {
+        builder
+            .begin_render_pass(render_pass_info, subpass_info)
+            .unwrap()
+            .set_viewport(0, [viewport.clone()].into_iter().collect())
+            .unwrap()
+            .bind_pipeline_graphics(graphic_pipeline.clone())
+            .unwrap()
+            .bind_descriptor_sets(
+                PipelineBindPoint::Graphics,
+                graphic_pipeline.layout().clone(),
+                0,
+                camera_set,
             )
             .unwrap();
 
-            let mut render_pass_info = RenderPassBeginInfo::framebuffer(framebuffer.clone());
-            render_pass_info.clear_values = vec![Some([0.0, 0.0, 1.0, 1.0].into())];
-
-            let subpass_info = SubpassBeginInfo::default();
-
-            // Record commands:
-            unsafe {
-                builder
-                    .begin_render_pass(render_pass_info, subpass_info)
-                    .unwrap()
-                    .bind_pipeline_graphics(graphic_pipeline.clone())
-                    .unwrap()
-                    .bind_vertex_buffers(0, vertex_buffer.clone())
-                    .unwrap()
-                    .draw(vertex_buffer.len() as u32, 1, 0, 0)
-                    .unwrap()
-                    .end_render_pass(Default::default())
-                    .unwrap();
-            }
-            builder.build().unwrap()
-        })
-        .collect::<Vec<_>>()
+        for mesh in meshes {
+            builder
+                .bind_vertex_buffers(0, mesh.vertex_buffer.clone())
+                .unwrap()
+                .bind_index_buffer(mesh.index_buffer.clone())
+                .unwrap()
+                .draw_indexed(mesh.index_count(), 1, 0, 0, 0)
+                .unwrap();
+        }
+
+        builder.end_render_pass(Default::default()).unwrap();
+    }
 }
 
-// Render function
-pub fn lustre_render(instance: Arc<Instance>, surface: Arc<Surface>) {
-    // Define required device extensions.
-    let device_extensions = DeviceExtensions {
-        khr_swapchain: true,
-        ..DeviceExtensions::empty()
-    };
-
-    // Select a physical device.
-    let (physical_device, _) = get_physical_device(&instance, &surface, &device_extensions);
-
-    log_info("Physical device is: {},");
-
-    // Choose a graphics queue family.
-    let queue_family_index = physical_device
-        .queue_family_properties()
-        .iter()
-        .enumerate()
-        .position(|(_, q)| q.queue_flags.contains(QueueFlags::GRAPHICS))
-        .expect("couldn't find a graphics queue family") as u32;
-
-    // Create the logical device and retrieve the queue.
-    let (device, mut queues) = Device::new(
-        physical_device.clone(),
-        DeviceCreateInfo {
-            queue_create_infos: vec![QueueCreateInfo {
-                queue_family_index,
-                ..Default::default()
-            }],
-            enabled_extensions: device_extensions,
-            ..Default::default()
-        },
+// Record a one-shot command buffer drawing into the given framebuffer
+fn get_command_buffer<'a>(
+    command_buffer_allocator: Arc<StandardCommandBufferAllocator>,
+    queue: &Arc<Queue>,
+    graphic_pipeline: &Arc<GraphicsPipeline>,
+    framebuffer: &Arc<Framebuffer>,
+    viewport: &Viewport,
+    camera_set: Arc<DescriptorSet>,
+    meshes: impl IntoIterator<Item = &'a GpuMesh>,
+) -> Arc<PrimaryAutoCommandBuffer> {
+    let mut builder = AutoCommandBufferBuilder::primary(
+        command_buffer_allocator,
+        queue.queue_family_index(),
+        CommandBufferUsage::OneTimeSubmit,
     )
-    .expect("failed to create device");
-    let queue = queues.next().unwrap();
+    .unwrap();
 
-    // Create a memory allocator.
-    let memory_allocator = Arc::new(StandardMemoryAllocator::new_default(device.clone()));
+    record_scene(
+        &mut builder,
+        graphic_pipeline,
+        framebuffer,
+        viewport,
+        camera_set,
+        meshes,
+    );
 
-    // Create the buffer information
-    let buf = Buffer::from_iter(
-        memory_allocator.clone(),
-        BufferCreateInfo {
-            usage: BufferUsage::TRANSFER_DST,
-            ..Default::default()
-        },
-        AllocationCreateInfo {
-            memory_type_filter: MemoryTypeFilter::PREFER_HOST
-                | MemoryTypeFilter::HOST_RANDOM_ACCESS,
-            ..Default::default()
-        },
-        (0..1024 * 1024 * 4).map(|_| 0u8),
-    )
-    .expect("failed to create buffer");
+    builder.build().unwrap()
+}
 
-    // Create the swapchain.
-    let (format, _colorspace) = physical_device
-        .surface_formats(&surface, Default::default())
-        .unwrap()[0];
+/// Long-lived Vulkan state for the render window.
+///
+/// Created once when the window is resumed; each redraw only records and submits a frame.
+pub struct Renderer {
+    device: Arc<Device>,
+    queue: Arc<Queue>,
+    memory_allocator: Arc<StandardMemoryAllocator>,
+    command_buffer_allocator: Arc<StandardCommandBufferAllocator>,
+    descriptor_set_allocator: Arc<StandardDescriptorSetAllocator>,
+    uniform_buffer_allocator: SubbufferAllocator,
+    swapchain: Arc<Swapchain>,
+    render_pass: Arc<RenderPass>,
+    framebuffers: Vec<Arc<Framebuffer>>,
+    viewport: Viewport,
+    graphic_pipeline: Arc<GraphicsPipeline>,
+    meshes: HashMap<MeshKey, GpuMesh>,
+    // Game entities, rebuilt every frame.
+    entities: Option<GpuMesh>,
+    // Outline around the targeted block.
+    highlight: Option<(BlockPos, GpuMesh)>,
+    camera: Camera,
+    window_extent: [u32; 2],
+    recreate_swapchain: bool,
+    previous_frame_end: Option<Box<dyn GpuFuture>>,
+}
 
-    let caps = physical_device
-        .surface_capabilities(&surface, Default::default())
-        .expect("failed to get surface capabilities");
-    let image_extent = caps.current_extent.unwrap_or([1024, 1024]);
+impl Renderer {
+    /// Builds the device, swapchain, render pass and pipeline for the given surface.
+    ///
+    /// `window_extent` is the window's inner size in pixels, used when the surface
+    /// does not dictate its own extent (e.g. on Wayland).
+    pub fn new(instance: Arc<Instance>, surface: Arc<Surface>, window_extent: [u32; 2]) -> Self {
+        // Define required device extensions.
+        let device_extensions = DeviceExtensions {
+            khr_swapchain: true,
+            ..DeviceExtensions::empty()
+        };
+
+        // Select a physical device and a graphics queue family that can present.
+        let (physical_device, queue_family_index) =
+            get_physical_device(&instance, Some(&surface), &device_extensions)
+                .expect("no device available");
+
+        log_info(&format!(
+            "Physical device is: {}",
+            physical_device.properties().device_name
+        ));
+
+        // Create the logical device and retrieve the queue.
+        let (device, mut queues) = Device::new(
+            physical_device.clone(),
+            DeviceCreateInfo {
+                queue_create_infos: vec![QueueCreateInfo {
+                    queue_family_index,
+                    ..Default::default()
+                }],
+                enabled_extensions: device_extensions,
+                ..Default::default()
+            },
+        )
+        .expect("failed to create device");
+        let queue = queues.next().unwrap();
+
+        // Create the allocators.
+        let memory_allocator = Arc::new(StandardMemoryAllocator::new_default(device.clone()));
+        let command_buffer_allocator = Arc::new(StandardCommandBufferAllocator::new(
+            device.clone(),
+            StandardCommandBufferAllocatorCreateInfo::default(),
+        ));
+        let descriptor_set_allocator = Arc::new(StandardDescriptorSetAllocator::new(
+            device.clone(),
+            Default::default(),
+        ));
+        let uniform_buffer_allocator = SubbufferAllocator::new(
+            memory_allocator.clone(),
+            SubbufferAllocatorCreateInfo {
+                buffer_usage: BufferUsage::UNIFORM_BUFFER,
+                memory_type_filter: MemoryTypeFilter::PREFER_DEVICE
+                    | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
+                ..Default::default()
+            },
+        );
+
+        // Create the swapchain.
+        let (format, _colorspace) = physical_device
+            .surface_formats(&surface, Default::default())
+            .unwrap()[0];
+
+        let caps = physical_device
+            .surface_capabilities(&surface, Default::default())
+            .expect("failed to get surface capabilities");
+        let image_extent = caps.current_extent.unwrap_or(window_extent);
+
+        let (swapchain, swapchain_images) = Swapchain::new(
+            device.clone(),
+            surface.clone(),
+            SwapchainCreateInfo {
+                min_image_count: caps.min_image_count,
+                image_format: format,
+                image_extent,
+                image_usage: ImageUsage::COLOR_ATTACHMENT,
+                composite_alpha: caps
+                    .supported_composite_alpha
+                    .into_iter()
+                    .next()
+                    .expect("no supported composite alpha"),
+                present_mode: PresentMode::Fifo,
+                ..Default::default()
+            },
+        )
+        .expect("failed to create swapchain");
+
+        // Single Render Pass && Framebuffers
+        let render_pass = get_render_pass(device.clone(), swapchain.image_format());
+        let framebuffers = get_framebuffers(
+            &swapchain_images,
+            render_pass.clone(),
+            memory_allocator.clone(),
+        );
+
+        // Create viewport
+        let viewport = get_viewport(image_extent);
+
+        let vs_module = vs::load(device.clone()).expect("failed to load vertex shader.");
+        let fs_module = fs::load(device.clone()).expect("failed to load fragment shader.");
+
+        // Create pipeline
+        let graphic_pipeline =
+            get_graphic_pipeline(device.clone(), vs_module, fs_module, render_pass.clone());
+
+        let previous_frame_end = Some(sync::now(device.clone()).boxed());
+
+        Self {
+            device,
+            queue,
+            memory_allocator,
+            command_buffer_allocator,
+            descriptor_set_allocator,
+            uniform_buffer_allocator,
+            swapchain,
+            render_pass,
+            framebuffers,
+            viewport,
+            graphic_pipeline,
+            meshes: HashMap::new(),
+            entities: None,
+            highlight: None,
+            camera: Camera::default(),
+            window_extent,
+            recreate_swapchain: false,
+            previous_frame_end,
+        }
+    }
+
+    pub fn device(&self) -> &Arc<Device> {
+        &self.device
+    }
+
+    pub fn queue(&self) -> &Arc<Queue> {
+        &self.queue
+    }
+
+    pub fn memory_allocator(&self) -> &Arc<StandardMemoryAllocator> {
+        &self.memory_allocator
+    }
+
+    pub fn camera(&self) -> &Camera {
+        &self.camera
+    }
+
+    pub fn camera_mut(&mut self) -> &mut Camera {
+        &mut self.camera
+    }
+
+    /// Width over height of the swapchain images.
+    pub fn aspect_ratio(&self) -> f32 {
+        let extent = self.swapchain.image_extent();
+        extent[0] as f32 / extent[1] as f32
+    }
+
+    /// Replaces the GPU mesh drawn for a chunk or LOD region. An empty mesh removes it.
+    pub fn upload_mesh(&mut self, key: MeshKey, mesh: &ChunkMesh) {
+        if mesh.is_empty() {
+            self.meshes.remove(&key);
+            return;
+        }
+        match GpuMesh::upload(self.memory_allocator.clone(), &mesh.vertices, &mesh.indices) {
+            Ok(gpu_mesh) => {
+                self.meshes.insert(key, gpu_mesh);
+            }
+            Err(err) => log_error(&format!("Failed to upload mesh for {:?}: {}", key, err)),
+        }
+    }
+
+    /// Stops drawing a mesh, e.g. after its chunk was unloaded.
+    pub fn remove_mesh(&mut self, key: MeshKey) {
+        self.meshes.remove(&key);
+    }
+
+    /// Replaces the mesh drawn for game entities. An empty mesh draws none.
+    pub fn set_entity_mesh(&mut self, mesh: &ChunkMesh) {
+        self.entities = None;
+        if mesh.is_empty() {
+            return;
+        }
+        match GpuMesh::upload(self.memory_allocator.clone(), &mesh.vertices, &mesh.indices) {
+            Ok(gpu_mesh) => self.entities = Some(gpu_mesh),
+            Err(err) => log_error(&format!("Failed to upload entity mesh: {}", err)),
+        }
+    }
+
+    /// Outlines a block, or clears the outline with None.
+    pub fn set_highlight(&mut self, pos: Option<BlockPos>) {
+        if self.highlight.as_ref().map(|(current, _)| *current) == pos {
+            return;
+        }
+        self.highlight = pos.and_then(|pos| {
+            let outline = block_outline(pos);
+            match GpuMesh::upload(
+                self.memory_allocator.clone(),
+                &outline.vertices,
+                &outline.indices,
+            ) {
+                Ok(mesh) => Some((pos, mesh)),
+                Err(err) => {
+                    log_error(&format!("Failed to upload block outline: {}", err));
+                    None
+                }
+            }
+        });
+    }
+
+    pub fn mesh_count(&self) -> usize {
+        self.meshes.len()
+    }
+
+    /// Marks the swapchain for recreation after the window was resized.
+    pub fn resize(&mut self, window_extent: [u32; 2]) {
+        self.window_extent = window_extent;
+        self.recreate_swapchain = true;
+    }
+
+    /// Records and submits a single frame, then presents it.
+    ///
+    /// Does nothing while the window is minimized (zero-sized).
+    pub fn render(&mut self) {
+        if self.window_extent.contains(&0) {
+            return;
+        }
+
+        // Release resources held by frames the GPU has already finished.
+        if let Some(previous_frame_end) = self.previous_frame_end.as_mut() {
+            previous_frame_end.cleanup_finished();
+        }
+
+        if self.recreate_swapchain && !self.recreate_swapchain() {
+            return;
+        }
+
+        // Acquire swapchain image
+        let (image_index, suboptimal, acquire_future) =
+            match vulkano::swapchain::acquire_next_image(self.swapchain.clone(), None)
+                .map_err(Validated::unwrap)
+            {
+                Ok(result) => result,
+                Err(VulkanError::OutOfDate) => {
+                    self.recreate_swapchain = true;
+                    return;
+                }
+                Err(err) => panic!("failed to acquire next image: {}", err),
+            };
+
+        // The image is still presentable, but the swapchain no longer matches the surface.
+        if suboptimal {
+            self.recreate_swapchain = true;
+        }
+
+        // Upload this frame's camera matrices.
+        let aspect = self.aspect_ratio();
+        let camera_buffer = self.uniform_buffer_allocator.allocate_sized().unwrap();
+        *camera_buffer.write().unwrap() = camera_uniform(
+            &self.camera.view_matrix(),
+            &self.camera.projection_matrix(aspect),
+        );
+        let camera_set = get_camera_set(
+            self.descriptor_set_allocator.clone(),
+            &self.graphic_pipeline,
+            camera_buffer,
+        );
+
+        let command_buffer = get_command_buffer(
+            self.command_buffer_allocator.clone(),
+            &self.queue,
+            &self.graphic_pipeline,
+            &self.framebuffers[image_index as usize],
+            &self.viewport,
+            camera_set,
+            self.meshes
+                .values()
+                .chain(&self.entities)
+                .chain(self.highlight.as_ref().map(|(_, mesh)| mesh)),
+        );
+
+        // Submit the command buffer and present.
+        let future = self
+            .previous_frame_end
+            .take()
+            .unwrap()
+            .join(acquire_future)
+            .then_execute(self.queue.clone(), command_buffer)
+            .unwrap()
+            .then_swapchain_present(
+                self.queue.clone(),
+                SwapchainPresentInfo::swapchain_image_index(self.swapchain.clone(), image_index),
+            )
+            .then_signal_fence_and_flush();
 
-    let (swapchain, swapchain_images) = Swapchain::new(
-        device.clone(),
-        surface.clone(),
-        SwapchainCreateInfo {
-            min_image_count: caps.min_image_count,
-            image_format: format, // Store this format to use later
+        match future.map_err(Validated::unwrap) {
+            Ok(future) => self.previous_frame_end = Some(future.boxed()),
+            Err(VulkanError::OutOfDate) => {
+                self.recreate_swapchain = true;
+                self.previous_frame_end = Some(sync::now(self.device.clone()).boxed());
+            }
+            Err(err) => {
+                log_error(&format!("Failed to flush frame: {}", err));
+                self.previous_frame_end = Some(sync::now(self.device.clone()).boxed());
+            }
+        }
+    }
+
+    // Rebuilds the swapchain, framebuffers and viewport for the current window extent.
+    // Returns false if the surface cannot be rendered to right now.
+    fn recreate_swapchain(&mut self) -> bool {
+        // The window can report sizes the surface doesn't allow, e.g. mid-resize.
+        let image_extent = match self
+            .device
+            .physical_device()
+            .surface_capabilities(self.swapchain.surface(), Default::default())
+        {
+            Ok(caps) => [0, 1].map(|axis| {
+                self.window_extent[axis]
+                    .clamp(caps.min_image_extent[axis], caps.max_image_extent[axis])
+            }),
+            Err(err) => {
+                log_warn(&format!("Failed to get surface capabilities: {}", err));
+                self.window_extent
+            }
+        };
+        let (swapchain, swapchain_images) = match self.swapchain.recreate(SwapchainCreateInfo {
             image_extent,
-            image_usage: ImageUsage::COLOR_ATTACHMENT,
-            present_mode: PresentMode::Fifo,
-            ..Default::default()
-        },
-    )
-    .expect("failed to create swapchain");
+            ..self.swapchain.create_info()
+        }) {
+            Ok(result) => result,
+            // The surface extent changed again before we got here; retry next frame.
+            Err(Validated::ValidationError(err)) => {
+                log_warn(&format!("Swapchain recreation deferred: {}", err));
+                return false;
+            }
+            Err(err) => panic!("failed to recreate swapchain: {}", err),
+        };
+
+        log_info(&format!(
+            "Swapchain recreated at {}x{}",
+            swapchain.image_extent()[0],
+            swapchain.image_extent()[1]
+        ));
+
+        self.viewport = get_viewport(swapchain.image_extent());
+        self.framebuffers = get_framebuffers(
+            &swapchain_images,
+            self.render_pass.clone(),
+            self.memory_allocator.clone(),
+        );
+        self.swapchain = swapchain;
+        self.recreate_swapchain = false;
+        true
+    }
+}
+
+// Get a viewport covering the whole image
+pub(crate) fn get_viewport(extent: [u32; 2]) -> Viewport {
+    Viewport {
+        offset: [0.0, 0.0],
+        extent: [extent[0] as f32, extent[1] as f32],
+        depth_range: 0.0..=1.0,
+    }
+}
+
+// Create the triangle mesh
+pub(crate) fn get_triangle_mesh(memory_allocator: Arc<StandardMemoryAllocator>) -> GpuMesh {
+    let normal = [0.0, 0.0, -1.0];
+    let color = [1.0, 0.0, 0.0];
 
     let vertex1 = MainVertex {
-        position: [-0.5, -0.5],
+        position: [-0.5, -0.5, 0.0],
+        normal,
+        uv: [0.0, 0.0],
+        color,
+        ao: 1.0,
     };
 
     let vertex2 = MainVertex {
-        position: [0.0, 0.5],
+        position: [0.0, 0.5, 0.0],
+        normal,
+        uv: [0.5, 1.0],
+        color,
+        ao: 1.0,
     };
 
     let vertex3 = MainVertex {
-        position: [0.5, -0.25],
-    };
-
-    // Triangle
-    let vertex_buffer = Buffer::from_iter(
-        memory_allocator.clone(),
-        BufferCreateInfo {
-            usage: BufferUsage::VERTEX_BUFFER,
-            ..Default::default()
-        },
-        AllocationCreateInfo {
-            memory_type_filter: MemoryTypeFilter::PREFER_DEVICE
-                | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
-            ..Default::default()
-        },
-        vec![vertex1, vertex2, vertex3].into_iter(),
-    )
-    .unwrap();
-
-    // let images = vec![swapchain_images.clone()];
-
-    // Acquire swapchain image and present it
-    let (image_index, suboptimal, acquire_future) =
-        vulkano::swapchain::acquire_next_image(swapchain.clone(), None)
-            .expect("failed to acquire next image.");
-
-    // Single Render Pass && Swapchain Creation
-    let render_pass = get_render_pass(device.clone(), swapchain.clone());
-
-    // Creating Framebuffers
-    let framebuffer = get_framebuffers(&swapchain_images, render_pass.clone());
-
-    // Create viewport
-    let viewport = Viewport {
-        offset: [0.0, 0.0],
-        extent: [1024.0, 1024.0],
-        depth_range: 0.0..=1.0,
+        position: [0.5, -0.25, 0.0],
+        normal,
+        uv: [1.0, 0.0],
+        color,
+        ao: 1.0,
     };
 
-    let vs_module = vs::load(device.clone()).expect("failed to load vertex shader.");
-    let fs_module = fs::load(device.clone()).expect("failed to laod fragment shader.");
-
-    // Create pipeline
-    let graphic_pipeline = get_graphic_pipeline(
-        device.clone(),
-        vs_module,
-        fs_module,
-        render_pass.clone(),
-        viewport,
-    );
-
-    let command_buffer_allocator = Arc::new(StandardCommandBufferAllocator::new(
-        device.clone(),
-        StandardCommandBufferAllocatorCreateInfo::default(),
-    ));
-
-    // Get command buffers
-    let command_buffers = get_command_buffers(
-        command_buffer_allocator,
-        &queue,
-        &graphic_pipeline,
-        &framebuffer,
-        &vertex_buffer,
-    );
-
-    let command_buffer = command_buffers[0].clone();
-
-    // Submit the command buffer.
-    //let future = sync::now(device)
-    let future = acquire_future
-        .then_execute(queue.clone(), command_buffer)
-        .unwrap()
-        .then_swapchain_present(
-            queue.clone(),
-            SwapchainPresentInfo::swapchain_image_index(swapchain.clone(), image_index),
-        )
-        .then_signal_fence_and_flush()
-        .unwrap();
-    future.wait(None).unwrap();
-
-    // Generate image
-    let content = buf.read().unwrap();
-    let image = ImageBuffer::<Rgba<u8>, _>::from_raw(1024, 1024, &content[..]).unwrap();
-    image.save("image.png").unwrap();
-
-    println!("Everything succeeded!");
+    GpuMesh::upload(memory_allocator, &[vertex1, vertex2, vertex3], &[0, 1, 2]).unwrap()
 }
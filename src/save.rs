@@ -0,0 +1,162 @@
+// Copyright 2025 Nicholas Jordan. All Rights Reserved.
+// github.com/cvusmo/lustre
+// src/save.rs
+
+use crate::chunk::Chunk;
+use crate::planet::Planet;
+use crate::region::{RegionFile, RegionPos};
+use crate::state::log_info;
+use crate::terrain::DEFAULT_GENERATOR;
+use crate::world::{ChunkPos, World};
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Current world save format. Saves with another version are rejected.
+pub const SAVE_VERSION: u32 = 1;
+
+const METADATA_FILE: &str = "world.toml";
+const REGION_DIR: &str = "regions";
+
+/// Everything about a saved world that is not stored in its chunks.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WorldMetadata {
+    pub version: u32,
+    pub seed: u64,
+    /// Where players start, in world coordinates.
+    pub spawn: [f32; 3],
+    /// Seconds of game time elapsed.
+    pub game_time: f64,
+    /// Name the world's terrain generator was registered under.
+    pub generator: String,
+    pub planet: Option<Planet>,
+}
+
+impl Default for WorldMetadata {
+    fn default() -> Self {
+        Self {
+            version: SAVE_VERSION,
+            seed: 0,
+            spawn: [0.0; 3],
+            game_time: 0.0,
+            generator: DEFAULT_GENERATOR.to_string(),
+            planet: None,
+        }
+    }
+}
+
+/// A world saved in a directory: `world.toml` for metadata plus region files of chunks.
+///
+/// Region files are opened the first time one of their chunks is read or written.
+pub struct WorldSave {
+    dir: PathBuf,
+    regions: HashMap<RegionPos, RegionFile>,
+}
+
+impl WorldSave {
+    /// Opens a save directory, creating it if needed.
+    pub fn open(dir: impl AsRef<Path>) -> Result<Self, Box<dyn Error>> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(dir.join(REGION_DIR))?;
+        Ok(Self {
+            dir,
+            regions: HashMap::new(),
+        })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Reads the world metadata, or None for a new save.
+    pub fn load_metadata(&self) -> Result<Option<WorldMetadata>, Box<dyn Error>> {
+        let path = self.dir.join(METADATA_FILE);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let metadata: WorldMetadata = toml::from_str(&fs::read_to_string(&path)?)?;
+        if metadata.version != SAVE_VERSION {
+            return Err(format!(
+                "unsupported save version {} in {} (expected {})",
+                metadata.version,
+                path.display(),
+                SAVE_VERSION
+            )
+            .into());
+        }
+        Ok(Some(metadata))
+    }
+
+    pub fn save_metadata(&self, metadata: &WorldMetadata) -> Result<(), Box<dyn Error>> {
+        let metadata = WorldMetadata {
+            version: SAVE_VERSION,
+            ..metadata.clone()
+        };
+        fs::write(self.dir.join(METADATA_FILE), toml::to_string(&metadata)?)?;
+        Ok(())
+    }
+
+    fn region(&mut self, pos: ChunkPos) -> Result<&mut RegionFile, Box<dyn Error>> {
+        let region = RegionPos::of_chunk(pos);
+        if !self.regions.contains_key(&region) {
+            let path = self.dir.join(REGION_DIR).join(region.file_name());
+            let file = RegionFile::open(&path)
+                .map_err(|err| format!("failed to open {}: {}", path.display(), err))?;
+            self.regions.insert(region, file);
+        }
+        Ok(self.regions.get_mut(&region).unwrap())
+    }
+
+    /// Reads a saved chunk, or None if it was never saved and should be generated.
+    pub fn load_chunk(
+        &mut self,
+        world: &World,
+        pos: ChunkPos,
+    ) -> Result<Option<Chunk>, Box<dyn Error>> {
+        let region = RegionPos::of_chunk(pos);
+        let path = self.dir.join(REGION_DIR).join(region.file_name());
+        // Avoid creating empty region files just to find nothing in them.
+        if !self.regions.contains_key(&region) && !path.exists() {
+            return Ok(None);
+        }
+        Ok(self.region(pos)?.read_chunk(pos, world.registry())?)
+    }
+
+    /// Writes one chunk.
+    pub fn save_chunk(
+        &mut self,
+        world: &World,
+        pos: ChunkPos,
+        chunk: &Chunk,
+    ) -> Result<(), Box<dyn Error>> {
+        Ok(self
+            .region(pos)?
+            .write_chunk(pos, chunk, world.registry())?)
+    }
+
+    /// Writes every chunk edited since it was last saved and returns how many were written.
+    pub fn save_unsaved(&mut self, world: &mut World) -> Result<usize, Box<dyn Error>> {
+        let unsaved = world.unsaved_chunks();
+        for &pos in &unsaved {
+            if let Some(chunk) = world.chunk(pos) {
+                self.save_chunk(world, pos, chunk)?;
+            }
+            world.mark_saved(pos);
+        }
+        for region in self.regions.values_mut() {
+            region.sync()?;
+        }
+        if !unsaved.is_empty() {
+            log_info(&format!(
+                "Saved {} chunks to {}",
+                unsaved.len(),
+                self.dir.display()
+            ));
+        }
+        Ok(unsaved.len())
+    }
+}
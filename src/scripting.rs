@@ -0,0 +1,502 @@
+// Copyright 2025 Nicholas Jordan. All Rights Reserved.
+// github.com/cvusmo/lustre
+// src/scripting.rs
+
+use crate::diagnostics::{self, Diagnostic};
+use crate::events::{self, emit, Event};
+use crate::game::{Despawn, Player, Renderable, TickDelta, Transform, Velocity};
+use crate::state::log_error;
+
+use mlua::prelude::*;
+use rapier3d::na::{Point3, Quaternion, UnitQuaternion, Vector3};
+use serde::{Deserialize, Serialize};
+use specs::prelude::*;
+use specs::World as EcsWorld;
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+// Instructions between checks of the instruction limit.
+const INSTRUCTION_CHECK_INTERVAL: u32 = 1000;
+
+/// What a single call into Lua may use before it's stopped with an error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScriptLimits {
+    /// Bytes the call may allocate on top of what Lua already holds; 0 means no limit.
+    pub memory: usize,
+    /// VM instructions the call may run; 0 means no limit.
+    pub instructions: u64,
+}
+
+impl Default for ScriptLimits {
+    fn default() -> Self {
+        Self {
+            memory: 64 << 20,
+            instructions: 50_000_000,
+        }
+    }
+}
+
+// Lua app data marking that a limited call is running.
+struct LimitedCall;
+
+/// Runs `f`, which calls into `lua`, failing it once it allocates or runs more than
+/// `limits` allows. Stops runaway loops and allocations from freezing the engine.
+///
+/// Inside another limited call, such as an event handler run by a mod's script, `f` counts
+/// against the outer call's limits instead.
+pub fn run_with_limits<R>(
+    lua: &Lua,
+    limits: &ScriptLimits,
+    f: impl FnOnce() -> LuaResult<R>,
+) -> LuaResult<R> {
+    // Setting a hook here would replace the outer call's and lift its limit when removed.
+    if lua.app_data_ref::<LimitedCall>().is_some() {
+        return f();
+    }
+    // Not every Lua build can limit memory; run without a memory limit there.
+    let previous_memory_limit = match limits.memory {
+        0 => None,
+        memory => lua
+            .set_memory_limit(lua.used_memory().saturating_add(memory))
+            .ok(),
+    };
+    if limits.instructions > 0 {
+        let budget = limits.instructions;
+        let executed = Cell::new(0u64);
+        let triggers = LuaHookTriggers::new().every_nth_instruction(INSTRUCTION_CHECK_INTERVAL);
+        lua.set_hook(triggers, move |_, _| {
+            executed.set(executed.get() + INSTRUCTION_CHECK_INTERVAL as u64);
+            if executed.get() > budget {
+                Err(LuaError::runtime(format!(
+                    "Script ran more than {} instructions",
+                    budget
+                )))
+            } else {
+                Ok(LuaVmState::Continue)
+            }
+        });
+    }
+
+    lua.set_app_data(LimitedCall);
+    let result = f();
+    lua.remove_app_data::<LimitedCall>();
+
+    if limits.instructions > 0 {
+        lua.remove_hook();
+    }
+    if let Some(previous) = previous_memory_limit {
+        let _ = lua.set_memory_limit(previous);
+    }
+    result
+}
+
+/// A Lua behaviour attached to an entity: a table whose `on_update(self, dt)` runs every
+/// tick. The table's `entity` field is set to the entity it's attached to.
+pub struct Script(pub LuaRegistryKey);
+
+impl Component for Script {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// An entity as Lua sees it. Read its index with `entity.id`; two handles to the same
+/// entity compare equal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LuaEntity(pub Entity);
+
+impl LuaUserData for LuaEntity {
+    fn add_fields<F: LuaUserDataFields<Self>>(fields: &mut F) {
+        fields.add_field_method_get("id", |_, this| Ok(this.0.id()));
+    }
+
+    fn add_methods<M: LuaUserDataMethods<Self>>(methods: &mut M) {
+        methods.add_meta_method(LuaMetaMethod::Eq, |_, this, other: LuaEntity| {
+            Ok(*this == other)
+        });
+        methods.add_meta_method(LuaMetaMethod::ToString, |_, this, ()| {
+            Ok(format!(
+                "entity {} (generation {})",
+                this.0.id(),
+                this.0.gen().id()
+            ))
+        });
+    }
+}
+
+impl FromLua for LuaEntity {
+    fn from_lua(value: LuaValue, _: &Lua) -> LuaResult<Self> {
+        match value {
+            LuaValue::UserData(data) => Ok(*data.borrow::<Self>()?),
+            _ => Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "entity".to_string(),
+                message: None,
+            }),
+        }
+    }
+}
+
+/// Runs `on_update` on every scripted entity.
+///
+/// Lua can't leave the thread that created it, so add this with
+/// `DispatcherBuilder::add_thread_local`; it then runs after the parallel systems. It must
+/// share its Lua with the one passed to [`register_lua_api`]. Each `on_update` call is
+/// held to the runner's [`ScriptLimits`].
+pub struct ScriptRunner {
+    lua: Lua,
+    limits: ScriptLimits,
+}
+
+impl ScriptRunner {
+    pub fn new(lua: Lua) -> Self {
+        Self::with_limits(lua, ScriptLimits::default())
+    }
+
+    pub fn with_limits(lua: Lua, limits: ScriptLimits) -> Self {
+        Self { lua, limits }
+    }
+}
+
+impl<'a> RunNow<'a> for ScriptRunner {
+    fn run_now(&mut self, world: &'a EcsWorld) {
+        let dt = world.read_resource::<TickDelta>().0;
+        // Release the storages first; scripts read and write them through the `ecs` table.
+        let behaviours: Vec<LuaTable> = {
+            let (entities, scripts, despawn) =
+                world.system_data::<(Entities, ReadStorage<Script>, ReadStorage<Despawn>)>();
+            (&entities, &scripts, !&despawn)
+                .join()
+                .filter_map(|(_, script, ())| self.lua.registry_value(&script.0).ok())
+                .collect()
+        };
+
+        for behaviour in behaviours {
+            let Ok(Some(on_update)) = behaviour.get::<Option<LuaFunction>>("on_update") else {
+                continue;
+            };
+            // One broken script shouldn't stop the others.
+            let result = run_with_limits(&self.lua, &self.limits, || {
+                on_update.call::<()>((&behaviour, dt))
+            });
+            if let Err(err) = result {
+                log_error(&format!("Entity script failed: {}", err));
+                diagnostics::report(&self.lua, Diagnostic::from_error(&err));
+            }
+        }
+    }
+
+    fn setup(&mut self, world: &mut EcsWorld) {
+        world.register::<Script>();
+    }
+}
+
+/// Reads `{ x = ..., y = ..., z = ... }`.
+pub(crate) fn vector_from_table(table: &LuaTable) -> LuaResult<Vector3<f32>> {
+    Ok(Vector3::new(
+        table.get("x")?,
+        table.get("y")?,
+        table.get("z")?,
+    ))
+}
+
+/// Builds `{ x = ..., y = ..., z = ... }`.
+pub(crate) fn vector_to_table(lua: &Lua, vector: &Vector3<f32>) -> LuaResult<LuaTable> {
+    let table = lua.create_table()?;
+    table.set("x", vector.x)?;
+    table.set("y", vector.y)?;
+    table.set("z", vector.z)?;
+    Ok(table)
+}
+
+// Reads an optional vector field, defaulting to zero.
+fn vector_field(table: &LuaTable, key: &str) -> LuaResult<Vector3<f32>> {
+    match table.get::<Option<LuaTable>>(key)? {
+        Some(vector) => vector_from_table(&vector),
+        None => Ok(Vector3::zeros()),
+    }
+}
+
+fn transform_to_table(lua: &Lua, transform: &Transform) -> LuaResult<LuaTable> {
+    let rotation = lua.create_table()?;
+    rotation.set("x", transform.rotation.i)?;
+    rotation.set("y", transform.rotation.j)?;
+    rotation.set("z", transform.rotation.k)?;
+    rotation.set("w", transform.rotation.w)?;
+    let table = lua.create_table()?;
+    table.set(
+        "position",
+        vector_to_table(lua, &transform.position.coords)?,
+    )?;
+    table.set("rotation", rotation)?;
+    Ok(table)
+}
+
+fn transform_from_table(table: &LuaTable) -> LuaResult<Transform> {
+    let rotation = match table.get::<Option<LuaTable>>("rotation")? {
+        Some(rotation) => UnitQuaternion::from_quaternion(Quaternion::new(
+            rotation.get("w")?,
+            rotation.get("x")?,
+            rotation.get("y")?,
+            rotation.get("z")?,
+        )),
+        None => UnitQuaternion::identity(),
+    };
+    Ok(Transform {
+        position: Point3::from(vector_field(table, "position")?),
+        rotation,
+    })
+}
+
+fn velocity_to_table(lua: &Lua, velocity: &Velocity) -> LuaResult<LuaTable> {
+    let table = lua.create_table()?;
+    table.set("linear", vector_to_table(lua, &velocity.linear)?)?;
+    table.set("angular", vector_to_table(lua, &velocity.angular)?)?;
+    Ok(table)
+}
+
+fn velocity_from_table(table: &LuaTable) -> LuaResult<Velocity> {
+    Ok(Velocity {
+        linear: vector_field(table, "linear")?,
+        angular: vector_field(table, "angular")?,
+    })
+}
+
+fn renderable_to_table(lua: &Lua, renderable: &Renderable) -> LuaResult<LuaTable> {
+    let table = lua.create_table()?;
+    table.set(
+        "half_extents",
+        vector_to_table(lua, &renderable.half_extents)?,
+    )?;
+    table.set("color", renderable.color)?;
+    Ok(table)
+}
+
+fn renderable_from_table(table: &LuaTable) -> LuaResult<Renderable> {
+    Ok(Renderable {
+        half_extents: vector_from_table(&table.get("half_extents")?)?,
+        color: table.get::<Option<[f32; 3]>>("color")?.unwrap_or([1.0; 3]),
+    })
+}
+
+fn unknown_component(name: &str) -> LuaError {
+    LuaError::runtime(format!("Unknown component '{}'", name))
+}
+
+// Inserts `component`, or removes the component when it's `None`.
+fn write_component<C: Component>(
+    world: &EcsWorld,
+    entity: Entity,
+    component: Option<C>,
+) -> LuaResult<()> {
+    let mut storage = world.write_storage::<C>();
+    match component {
+        Some(component) => storage
+            .insert(entity, component)
+            .map(|_| ())
+            .map_err(|_| LuaError::runtime("Entity is no longer alive")),
+        None => {
+            storage.remove(entity);
+            Ok(())
+        }
+    }
+}
+
+fn has_component(world: &EcsWorld, entity: Entity, name: &str) -> LuaResult<bool> {
+    Ok(match name {
+        "transform" => world.read_storage::<Transform>().contains(entity),
+        "velocity" => world.read_storage::<Velocity>().contains(entity),
+        "renderable" => world.read_storage::<Renderable>().contains(entity),
+        "player" => world.read_storage::<Player>().contains(entity),
+        "script" => world.read_storage::<Script>().contains(entity),
+        _ => return Err(unknown_component(name)),
+    })
+}
+
+fn get_component(lua: &Lua, world: &EcsWorld, entity: Entity, name: &str) -> LuaResult<LuaValue> {
+    match name {
+        "transform" => world
+            .read_storage::<Transform>()
+            .get(entity)
+            .map(|transform| transform_to_table(lua, transform))
+            .transpose()?
+            .into_lua(lua),
+        "velocity" => world
+            .read_storage::<Velocity>()
+            .get(entity)
+            .map(|velocity| velocity_to_table(lua, velocity))
+            .transpose()?
+            .into_lua(lua),
+        "renderable" => world
+            .read_storage::<Renderable>()
+            .get(entity)
+            .map(|renderable| renderable_to_table(lua, renderable))
+            .transpose()?
+            .into_lua(lua),
+        "player" => world
+            .read_storage::<Player>()
+            .contains(entity)
+            .then_some(true)
+            .into_lua(lua),
+        "script" => world
+            .read_storage::<Script>()
+            .get(entity)
+            .map(|script| lua.registry_value::<LuaTable>(&script.0))
+            .transpose()?
+            .into_lua(lua),
+        _ => Err(unknown_component(name)),
+    }
+}
+
+fn set_component(
+    lua: &Lua,
+    world: &EcsWorld,
+    entity: Entity,
+    name: &str,
+    value: LuaValue,
+) -> LuaResult<()> {
+    if name == "player" {
+        let player = lua.unpack::<Option<bool>>(value)?.unwrap_or(false);
+        return write_component(world, entity, player.then_some(Player));
+    }
+
+    let table = lua.unpack::<Option<LuaTable>>(value)?;
+    match name {
+        "transform" => {
+            let transform = table.as_ref().map(transform_from_table).transpose()?;
+            write_component(world, entity, transform)
+        }
+        "velocity" => {
+            let velocity = table.as_ref().map(velocity_from_table).transpose()?;
+            write_component(world, entity, velocity)
+        }
+        "renderable" => {
+            let renderable = table.as_ref().map(renderable_from_table).transpose()?;
+            write_component(world, entity, renderable)
+        }
+        "script" => {
+            let script = match table {
+                Some(behaviour) => {
+                    behaviour.set("entity", LuaEntity(entity))?;
+                    Some(Script(lua.create_registry_value(behaviour)?))
+                }
+                None => None,
+            };
+            write_component(world, entity, script)
+        }
+        _ => Err(unknown_component(name)),
+    }
+}
+
+// Entities marked for removal count as gone.
+fn is_alive(world: &EcsWorld, entity: Entity) -> bool {
+    world.entities().is_alive(entity) && !world.read_storage::<Despawn>().contains(entity)
+}
+
+fn check_alive(world: &EcsWorld, entity: Entity) -> LuaResult<()> {
+    if is_alive(world, entity) {
+        Ok(())
+    } else {
+        Err(LuaError::runtime("Entity is no longer alive"))
+    }
+}
+
+/// Registers the `ecs` table with Lua.
+///
+/// `ecs.spawn(components)` creates an entity from an optional table of components, emitting
+/// `entity_spawned`, and
+/// `ecs.despawn(entity)` removes it at the start of the next tick. `ecs.get(entity, name)`
+/// and `ecs.set(entity, name, value)` read and write one component, with nil meaning
+/// absent; `ecs.query(name, ...)` lists the entities that have every named component.
+///
+/// Components are `transform = {position = {x, y, z}, rotation = {x, y, z, w}}`,
+/// `velocity = {linear = {x, y, z}, angular = {x, y, z}}`,
+/// `renderable = {half_extents = {x, y, z}, color = {r, g, b}}`, `player = true` and
+/// `script`, a table whose `on_update(self, dt)` the [`ScriptRunner`] calls every tick.
+pub fn register_lua_api(lua: &Lua, world: Rc<RefCell<EcsWorld>>) -> LuaResult<()> {
+    world.borrow_mut().register::<Script>();
+    let table = lua.create_table()?;
+
+    let shared = world.clone();
+    table.set(
+        "spawn",
+        lua.create_function(move |lua, components: Option<LuaTable>| {
+            let entity = {
+                let world = shared.borrow();
+                let entity = world.entities().create();
+                for pair in components.iter().flat_map(|components| components.pairs()) {
+                    let (name, value): (String, LuaValue) = pair?;
+                    if let Err(err) = set_component(lua, &world, entity, &name, value) {
+                        let _ = world.entities().delete(entity);
+                        return Err(err);
+                    }
+                }
+                entity
+            };
+            if let Some(bus) = events::shared_bus(lua) {
+                emit(&bus, &Event::EntitySpawned { entity });
+            }
+            Ok(LuaEntity(entity))
+        })?,
+    )?;
+
+    let shared = world.clone();
+    table.set(
+        "despawn",
+        lua.create_function(move |_, entity: LuaEntity| {
+            let world = shared.borrow();
+            if is_alive(&world, entity.0) {
+                write_component(&world, entity.0, Some(Despawn))?;
+            }
+            Ok(())
+        })?,
+    )?;
+
+    let shared = world.clone();
+    table.set(
+        "is_alive",
+        lua.create_function(move |_, entity: LuaEntity| Ok(is_alive(&shared.borrow(), entity.0)))?,
+    )?;
+
+    let shared = world.clone();
+    table.set(
+        "get",
+        lua.create_function(move |lua, (entity, name): (LuaEntity, String)| {
+            let world = shared.borrow();
+            check_alive(&world, entity.0)?;
+            get_component(lua, &world, entity.0, &name)
+        })?,
+    )?;
+
+    let shared = world.clone();
+    table.set(
+        "set",
+        lua.create_function(
+            move |lua, (entity, name, value): (LuaEntity, String, LuaValue)| {
+                let world = shared.borrow();
+                check_alive(&world, entity.0)?;
+                set_component(lua, &world, entity.0, &name, value)
+            },
+        )?,
+    )?;
+
+    table.set(
+        "query",
+        lua.create_function(move |_, names: LuaVariadic<String>| {
+            let world = world.borrow();
+            let (entities, despawn) = world.system_data::<(Entities, ReadStorage<Despawn>)>();
+            let mut found = Vec::new();
+            'entities: for (entity, ()) in (&entities, !&despawn).join() {
+                for name in names.iter() {
+                    if !has_component(&world, entity, name)? {
+                        continue 'entities;
+                    }
+                }
+                found.push(LuaEntity(entity));
+            }
+            Ok(found)
+        })?,
+    )?;
+
+    lua.globals().set("ecs", table)?;
+    Ok(())
+}
@@ -0,0 +1,31 @@
+// Copyright 2025 Nicholas Jordan. All Rights Reserved.
+// github.com/cvusmo/lustre
+// src/settings.rs
+
+use crate::state::{log_info, log_warn};
+
+use serde::de::DeserializeOwned;
+
+/// Config file the engine reads its settings tables from, relative to the working directory.
+pub const SETTINGS_PATH: &str = "lustre.toml";
+
+/// Loads the `[table]` table from a config file, falling back to defaults when the file or
+/// table is missing or invalid.
+pub fn load_section<T: DeserializeOwned + Default>(path: &str, table: &str) -> T {
+    let settings = config::Config::builder()
+        .add_source(config::File::with_name(path).required(false))
+        .build()
+        .and_then(|config| config.get::<T>(table));
+
+    match settings {
+        Ok(settings) => {
+            log_info(&format!("Loaded {} settings from {}", table, path));
+            settings
+        }
+        Err(config::ConfigError::NotFound(_)) => T::default(),
+        Err(err) => {
+            log_warn(&format!("Invalid {} settings in {}: {}", table, path, err));
+            T::default()
+        }
+    }
+}
@@ -2,16 +2,29 @@
 // github.com/cvusmo/lustre
 // src/state.rs
 
+use crate::block::BlockRegistry;
+use crate::clock::Clock;
+use crate::diagnostics::Diagnostic;
+use crate::events::EventBus;
+use crate::game::GameLoop;
+use crate::mods::{LoadedMod, ModSettings};
+use crate::physics::FIXED_TIMESTEP;
+use crate::scripting::ScriptRunner;
+use crate::settings::SETTINGS_PATH;
+use crate::terrain::GeneratorRegistry;
+use crate::world::World;
 use fern::Dispatch;
 use gtk4::prelude::*;
 use gtk4::Box as GtkBox;
-use gtk4::{DrawingArea, TextView};
+use gtk4::{DrawingArea, ListBox, TextView};
 use mlua::prelude::*;
 use once_cell::sync::OnceCell;
 use std::{
+    cell::RefCell,
     error::Error,
     fs::File,
     path::PathBuf,
+    rc::Rc,
     sync::{Arc, Mutex},
 };
 use vulkano::instance::Instance;
@@ -27,21 +40,66 @@ pub struct AppState {
     pub vulkan_instance: Option<Arc<Instance>>,
     pub vulkan_surface: Option<Arc<Surface>>,
     pub text_view: Option<TextView>,
+    /// Errors from the last script run and those mods, entity scripts and event handlers
+    /// raised since, listed in `diagnostics_list`.
+    pub diagnostics: Vec<Diagnostic>,
+    pub diagnostics_list: Option<ListBox>,
     pub lua: Arc<Mutex<Lua>>,
+    pub generators: Rc<RefCell<GeneratorRegistry>>,
+    /// Voxel world scripts read and edit and the render window plays.
+    pub world: Rc<RefCell<World>>,
+    /// Simulation clock shared by the editor, scripts and the render window.
+    pub clock: Rc<RefCell<Clock>>,
+    /// Entities scripts spawn and the render window plays, running on `clock`.
+    pub game: Rc<RefCell<GameLoop>>,
+    /// Engine and script events, shared by `game`, the render window and scripts.
+    pub events: Rc<RefCell<EventBus>>,
+    /// Mods loaded into `lua`, each with its own sandboxed globals.
+    pub mods: Vec<LoadedMod>,
+    /// Where mods are loaded from and the limits they and entity scripts run within.
+    pub mod_settings: ModSettings,
     pub is_modified: bool,
 }
 
 impl Default for AppState {
     fn default() -> Self {
+        Self::new(ModSettings::default())
+    }
+}
+
+impl AppState {
+    /// Creates a state whose entity scripts run within `mod_settings.limits`.
+    pub fn new(mod_settings: ModSettings) -> Self {
+        let lua = Lua::new();
+        let clock = Rc::new(RefCell::new(Clock::new(FIXED_TIMESTEP)));
+        // Entity scripts run in the same Lua as the editor's scripts, within the limits mods
+        // get since mods attach them too.
+        let limits = mod_settings.limits;
+        let mut game = GameLoop::with_systems(|builder| {
+            builder.add_thread_local(ScriptRunner::with_limits(lua.clone(), limits))
+        });
+        game.set_clock(clock.clone());
+        let events = Rc::new(RefCell::new(EventBus::new()));
+        game.set_events(events.clone());
+
         Self {
             project_path: None,
             project_area: None,
             vulkan_area: None,
             vulkan_instance: None,
             vulkan_surface: None,
-            lua: Arc::new(Mutex::new(Lua::new())),
+            lua: Arc::new(Mutex::new(lua)),
+            generators: Rc::new(RefCell::new(GeneratorRegistry::new())),
+            world: Rc::new(RefCell::new(World::new(BlockRegistry::with_defaults()))),
+            clock,
+            game: Rc::new(RefCell::new(game)),
+            events,
+            mods: Vec::new(),
+            mod_settings,
             is_modified: false,
             text_view: None,
+            diagnostics: Vec::new(),
+            diagnostics_list: None,
         }
     }
 }
@@ -78,9 +136,9 @@ pub fn initialize_state(
     Ok(())
 }
 
-/// Creates the initial application state.
+/// Creates the initial application state, with mod settings read from the config file.
 pub fn create_state() -> Arc<Mutex<AppState>> {
-    Arc::new(Mutex::new(AppState::default()))
+    Arc::new(Mutex::new(AppState::new(ModSettings::load(SETTINGS_PATH))))
 }
 
 /// Logs an informational message.
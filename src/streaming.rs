@@ -0,0 +1,520 @@
+// Copyright 2025 Nicholas Jordan. All Rights Reserved.
+// github.com/cvusmo/lustre
+// src/streaming.rs
+
+use crate::block::BlockRegistry;
+use crate::camera::Frustum;
+use crate::chunk::{Chunk, CHUNK_SIZE};
+use crate::mesher::{ChunkMesher, ChunkSample, MeshKey};
+use crate::save::WorldSave;
+use crate::settings;
+use crate::state::{log_error, log_warn};
+use crate::terrain::TerrainGenerator;
+use crate::world::{BlockPos, ChunkPos, LodPos, World, MAX_LOD_LEVEL};
+
+use rapier3d::na::{Point3, Vector3};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Largest `view_distance` the settings accept.
+pub const MAX_VIEW_DISTANCE: i32 = 64;
+
+// Generation jobs queued per worker thread. Kept low so jobs are picked in priority order
+// again after the viewer moves.
+const JOBS_PER_WORKER: usize = 2;
+
+/// How much of the world is kept loaded around the viewer, read from the `[streaming]` table.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StreamingSettings {
+    /// Radius in chunks kept loaded at full detail, from 1 to `MAX_VIEW_DISTANCE`.
+    pub view_distance: i32,
+    /// Coarser levels drawn beyond the full-detail radius, at most `MAX_LOD_LEVEL`. Each
+    /// doubles cell size and range.
+    pub lod_levels: u8,
+    /// Milliseconds per frame spent generating chunks. At least one job runs every frame.
+    pub frame_budget_ms: f32,
+    /// Priority multiplier for regions outside the view frustum; higher loads them later.
+    pub offscreen_penalty: f32,
+}
+
+impl Default for StreamingSettings {
+    fn default() -> Self {
+        Self {
+            view_distance: 4,
+            lod_levels: 4,
+            frame_budget_ms: 4.0,
+            offscreen_penalty: 4.0,
+        }
+    }
+}
+
+impl StreamingSettings {
+    /// Reads the `[streaming]` table of a config file.
+    pub fn load(path: &str) -> Self {
+        settings::load_section::<Self>(path, "streaming").clamped()
+    }
+
+    /// The settings with `view_distance` and `lod_levels` brought into range.
+    pub fn clamped(self) -> Self {
+        let view_distance = self.view_distance.clamp(1, MAX_VIEW_DISTANCE);
+        let lod_levels = self.lod_levels.min(MAX_LOD_LEVEL);
+        if view_distance != self.view_distance || lod_levels != self.lod_levels {
+            log_warn(&format!(
+                "Streaming view_distance must be 1 to {} and lod_levels at most {}",
+                MAX_VIEW_DISTANCE, MAX_LOD_LEVEL
+            ));
+        }
+        Self {
+            view_distance,
+            lod_levels,
+            ..self
+        }
+    }
+
+    /// Distance in blocks covered by the coarsest level.
+    pub fn range(&self) -> f32 {
+        (self.view_distance << self.lod_levels) as f32 * CHUNK_SIZE as f32
+    }
+}
+
+// Generates a cell: its chunk at full detail, or a coarse chunk for LOD cells.
+fn generate_cell(
+    generator: &dyn TerrainGenerator,
+    registry: &BlockRegistry,
+    cell: LodPos,
+) -> Option<Chunk> {
+    if cell.level == 0 {
+        Some(generator.generate(cell.min_chunk(), registry))
+    } else {
+        generator.generate_lod(cell.origin(), cell.scale(), registry)
+    }
+}
+
+// Generates cells on a pool of background threads.
+struct GenerationWorkers {
+    job_sender: Option<Sender<LodPos>>,
+    result_receiver: Receiver<(LodPos, Option<Chunk>)>,
+    workers: Vec<thread::JoinHandle<()>>,
+    // Cells sent to the workers and not received back yet.
+    in_flight: HashSet<LodPos>,
+}
+
+impl GenerationWorkers {
+    fn new(
+        generator: Arc<dyn TerrainGenerator + Send + Sync>,
+        registry: Arc<BlockRegistry>,
+        threads: usize,
+    ) -> Self {
+        let (job_sender, job_receiver) = channel::<LodPos>();
+        let (result_sender, result_receiver) = channel();
+        let job_receiver = Arc::new(Mutex::new(job_receiver));
+
+        let workers = (0..threads.max(1))
+            .map(|i| {
+                let job_receiver = Arc::clone(&job_receiver);
+                let result_sender = result_sender.clone();
+                let generator = Arc::clone(&generator);
+                let registry = Arc::clone(&registry);
+                thread::Builder::new()
+                    .name(format!("lustre-generator-{}", i))
+                    .spawn(move || loop {
+                        let cell = match job_receiver.lock().unwrap().recv() {
+                            Ok(cell) => cell,
+                            // The streamer was dropped.
+                            Err(_) => return,
+                        };
+                        let chunk = generate_cell(generator.as_ref(), &registry, cell);
+                        if result_sender.send((cell, chunk)).is_err() {
+                            return;
+                        }
+                    })
+                    .expect("failed to spawn generator thread")
+            })
+            .collect();
+
+        Self {
+            job_sender: Some(job_sender),
+            result_receiver,
+            workers,
+            in_flight: HashSet::new(),
+        }
+    }
+
+    fn is_full(&self) -> bool {
+        self.in_flight.len() >= self.workers.len() * JOBS_PER_WORKER
+    }
+
+    fn send(&mut self, cell: LodPos) {
+        if let Some(sender) = &self.job_sender {
+            if sender.send(cell).is_err() {
+                log_error("Generator threads have stopped.");
+                return;
+            }
+        }
+        self.in_flight.insert(cell);
+    }
+
+    fn poll(&mut self) -> Vec<(LodPos, Option<Chunk>)> {
+        let finished: Vec<_> = self.result_receiver.try_iter().collect();
+        for (cell, _) in &finished {
+            self.in_flight.remove(cell);
+        }
+        finished
+    }
+}
+
+impl Drop for GenerationWorkers {
+    fn drop(&mut self) {
+        // Closing the job channel stops the workers once they finish their current job.
+        self.job_sender.take();
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+/// What changed during a streaming update.
+#[derive(Debug, Default)]
+pub struct StreamingUpdate {
+    /// Chunks streamed in, whether loaded from the save or generated.
+    pub loaded: Vec<ChunkPos>,
+    /// Chunks removed from the world. Edited ones were already written to the save, if any.
+    pub unloaded: Vec<(ChunkPos, Chunk)>,
+    /// Meshes that should no longer be drawn.
+    pub removed_meshes: Vec<MeshKey>,
+}
+
+/// Loads, generates and unloads chunks around a viewer.
+///
+/// The area around the viewer is split like an octree: cells close to the viewer are split
+/// into full-detail chunks, distant ones are drawn as a single coarse level-of-detail chunk.
+/// Missing cells are queued nearest first, favouring those in view, and loaded from the save
+/// or generated within a per-frame time budget. Generators that can be shared across threads
+/// run on worker threads once `start_workers` is called; others, like Lua generators, run on
+/// the calling thread. Meshing is handed to the background `ChunkMesher`.
+pub struct ChunkStreamer {
+    settings: StreamingSettings,
+    // Cells that should be loaded for the current viewer chunk.
+    wanted: HashSet<LodPos>,
+    viewer_chunk: Option<ChunkPos>,
+    // Chunks generated by the streamer, including empty ones that are not in the world.
+    loaded: HashSet<ChunkPos>,
+    // LOD cells generated and sent for meshing.
+    lods: HashSet<LodPos>,
+    // Where chunks are loaded from before generating and saved to when unloaded.
+    save: Option<WorldSave>,
+    workers: Option<GenerationWorkers>,
+}
+
+impl ChunkStreamer {
+    pub fn new(settings: StreamingSettings) -> Self {
+        Self {
+            settings: settings.clamped(),
+            wanted: HashSet::new(),
+            viewer_chunk: None,
+            loaded: HashSet::new(),
+            lods: HashSet::new(),
+            save: None,
+            workers: None,
+        }
+    }
+
+    /// Moves generation onto `threads` worker threads, if `generator` can be shared with
+    /// them. Returns whether it could. Blocks registered afterwards are unknown to the
+    /// workers' copy of `registry`.
+    pub fn start_workers(
+        &mut self,
+        generator: &dyn TerrainGenerator,
+        registry: &BlockRegistry,
+        threads: usize,
+    ) -> bool {
+        let Some(generator) = generator.share() else {
+            return false;
+        };
+        self.workers = Some(GenerationWorkers::new(
+            generator,
+            Arc::new(registry.clone()),
+            threads,
+        ));
+        true
+    }
+
+    /// Loads saved chunks from `save` on demand and writes edited chunks back as they unload.
+    pub fn set_save(&mut self, save: Option<WorldSave>) {
+        self.save = save;
+    }
+
+    pub fn save_mut(&mut self) -> Option<&mut WorldSave> {
+        self.save.as_mut()
+    }
+
+    pub fn settings(&self) -> &StreamingSettings {
+        &self.settings
+    }
+
+    /// Cells selected for the current viewer position.
+    pub fn wanted(&self) -> impl Iterator<Item = &LodPos> {
+        self.wanted.iter()
+    }
+
+    /// Number of cells still waiting to be generated.
+    pub fn queued(&self) -> usize {
+        self.wanted
+            .iter()
+            .filter(|cell| !self.is_done(cell))
+            .count()
+    }
+
+    /// Streams the world towards `viewer`. Newly generated chunks are marked dirty and picked
+    /// up by `ChunkMesher::schedule_dirty`; LOD cells are sent to `mesher` directly.
+    ///
+    /// After `start_workers`, cells are generated by the workers' copy of the generator and
+    /// `generator` is not used.
+    pub fn update(
+        &mut self,
+        world: &mut World,
+        generator: &dyn TerrainGenerator,
+        mesher: &mut ChunkMesher,
+        viewer: Point3<f32>,
+        frustum: Option<&Frustum>,
+    ) -> StreamingUpdate {
+        let mut update = StreamingUpdate::default();
+
+        let viewer_chunk = BlockPos::new(
+            viewer.x.floor() as i32,
+            viewer.y.floor() as i32,
+            viewer.z.floor() as i32,
+        )
+        .chunk();
+        if self.viewer_chunk != Some(viewer_chunk) {
+            self.viewer_chunk = Some(viewer_chunk);
+            self.wanted = self.select(&viewer);
+            self.unload(world, mesher, &mut update);
+        }
+
+        // Cells finished by the workers, unless the viewer moved away from them.
+        let finished = self.workers.as_mut().map(|workers| workers.poll());
+        for (cell, chunk) in finished.into_iter().flatten() {
+            if self.wanted.contains(&cell) {
+                self.finish(world, mesher, cell, chunk, &mut update);
+            }
+        }
+
+        // Missing cells, nearest and visible first.
+        let in_flight = self.workers.as_ref().map(|workers| &workers.in_flight);
+        let mut queue: Vec<(f32, LodPos)> = self
+            .wanted
+            .iter()
+            .filter(|cell| !self.is_done(cell))
+            .filter(|cell| in_flight.is_none_or(|in_flight| !in_flight.contains(cell)))
+            .map(|cell| (self.priority(cell, &viewer, frustum), *cell))
+            .collect();
+        queue.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        let budget = Duration::from_secs_f32(self.settings.frame_budget_ms.max(0.0) / 1000.0);
+        let start = Instant::now();
+        for (processed, (_, cell)) in queue.iter().enumerate() {
+            if processed > 0 && start.elapsed() >= budget {
+                break;
+            }
+            if self
+                .workers
+                .as_ref()
+                .is_some_and(GenerationWorkers::is_full)
+            {
+                break;
+            }
+            self.load(world, generator, mesher, *cell, &mut update);
+        }
+
+        // Coarse meshes that were split or left behind are kept until everything that
+        // replaces them is generated, so no holes open up in the meantime.
+        if self.wanted.iter().all(|cell| self.is_done(cell)) {
+            let stale: Vec<LodPos> = self.lods.difference(&self.wanted).copied().collect();
+            for cell in stale {
+                self.lods.remove(&cell);
+                mesher.cancel(MeshKey::Lod(cell));
+                update.removed_meshes.push(MeshKey::Lod(cell));
+            }
+        }
+        update
+    }
+
+    fn is_done(&self, cell: &LodPos) -> bool {
+        if cell.level == 0 {
+            self.loaded.contains(&cell.min_chunk())
+        } else {
+            self.lods.contains(cell)
+        }
+    }
+
+    // Picks the cells to load around a viewer.
+    fn select(&self, viewer: &Point3<f32>) -> HashSet<LodPos> {
+        let top = self.settings.lod_levels;
+        let view_distance = self.settings.view_distance.max(1);
+        let center = LodPos::of_chunk(self.viewer_chunk.unwrap_or(ChunkPos::new(0, 0, 0)), top);
+        let reach = view_distance + 1;
+
+        let mut wanted = HashSet::new();
+        for x in -reach..=reach {
+            for y in -reach..=reach {
+                for z in -reach..=reach {
+                    let cell = LodPos::new(top, center.x + x, center.y + y, center.z + z);
+                    let range = (view_distance << top) as f32;
+                    if cell_distance(&cell, viewer) < range {
+                        self.subdivide(cell, viewer, &mut wanted);
+                    }
+                }
+            }
+        }
+        wanted
+    }
+
+    // Splits a cell while the viewer is close enough for the next level to be worth it.
+    fn subdivide(&self, cell: LodPos, viewer: &Point3<f32>, wanted: &mut HashSet<LodPos>) {
+        let split_range = if cell.level == 0 {
+            0.0
+        } else {
+            (self.settings.view_distance << (cell.level - 1)) as f32
+        };
+        if cell_distance(&cell, viewer) < split_range {
+            for child in cell.children() {
+                self.subdivide(child, viewer, wanted);
+            }
+        } else {
+            wanted.insert(cell);
+        }
+    }
+
+    // Drops chunks outside the wanted cells.
+    fn unload(
+        &mut self,
+        world: &mut World,
+        mesher: &mut ChunkMesher,
+        update: &mut StreamingUpdate,
+    ) {
+        let wanted = &self.wanted;
+        let is_wanted = |pos: &ChunkPos| wanted.contains(&LodPos::of_chunk(*pos, 0));
+        self.loaded.retain(is_wanted);
+
+        let stale: Vec<ChunkPos> = world
+            .chunks()
+            .map(|(pos, _)| *pos)
+            .filter(|pos| !is_wanted(pos))
+            .collect();
+        for pos in stale {
+            if let (Some(save), Some(chunk)) = (self.save.as_mut(), world.chunk(pos)) {
+                if world.is_unsaved(pos) {
+                    match save.save_chunk(world, pos, chunk) {
+                        Ok(()) => world.mark_saved(pos),
+                        Err(err) => log_error(&format!("Failed to save chunk {:?}: {}", pos, err)),
+                    }
+                }
+            }
+            if let Some(chunk) = world.remove_chunk(pos) {
+                update.unloaded.push((pos, chunk));
+            }
+            mesher.cancel(MeshKey::Chunk(pos));
+            update.removed_meshes.push(MeshKey::Chunk(pos));
+        }
+    }
+
+    // Loads a cell from the save, or generates it here or on a worker.
+    fn load(
+        &mut self,
+        world: &mut World,
+        generator: &dyn TerrainGenerator,
+        mesher: &mut ChunkMesher,
+        cell: LodPos,
+        update: &mut StreamingUpdate,
+    ) {
+        if cell.level == 0 {
+            let pos = cell.min_chunk();
+            if world.is_loaded(pos) {
+                self.loaded.insert(pos);
+                return;
+            }
+            let saved = self.save.as_mut().and_then(|save| {
+                save.load_chunk(world, pos).unwrap_or_else(|err| {
+                    log_error(&format!("Failed to load chunk {:?}: {}", pos, err));
+                    None
+                })
+            });
+            if let Some(chunk) = saved {
+                self.finish(world, mesher, cell, Some(chunk), update);
+                return;
+            }
+        }
+
+        match self.workers.as_mut() {
+            Some(workers) => workers.send(cell),
+            None => {
+                let chunk = generate_cell(generator, world.registry(), cell);
+                self.finish(world, mesher, cell, chunk, update);
+            }
+        }
+    }
+
+    // Adds a loaded or generated cell to the world, or its coarse chunk to the mesher.
+    fn finish(
+        &mut self,
+        world: &mut World,
+        mesher: &mut ChunkMesher,
+        cell: LodPos,
+        chunk: Option<Chunk>,
+        update: &mut StreamingUpdate,
+    ) {
+        if cell.level == 0 {
+            let pos = cell.min_chunk();
+            // Scripts may have created the chunk while it was generating.
+            if !world.is_loaded(pos) {
+                if let Some(chunk) = chunk.filter(|chunk| !chunk.is_empty()) {
+                    world.insert_chunk(pos, chunk);
+                }
+                update.loaded.push(pos);
+            }
+            self.loaded.insert(pos);
+            return;
+        }
+
+        if let Some(chunk) = chunk.filter(|chunk| !chunk.is_empty()) {
+            let origin = cell.origin();
+            let sample = ChunkSample::from_chunk(&chunk, origin, cell.scale());
+            mesher.schedule_sample(MeshKey::Lod(cell), sample);
+        }
+        self.lods.insert(cell);
+    }
+
+    fn priority(&self, cell: &LodPos, viewer: &Point3<f32>, frustum: Option<&Frustum>) -> f32 {
+        let distance = cell_distance(cell, viewer);
+        let (min, max) = cell_bounds(cell);
+        match frustum {
+            Some(frustum) if !frustum.intersects_aabb(&min, &max) => {
+                (distance + 1.0) * self.settings.offscreen_penalty
+            }
+            _ => distance,
+        }
+    }
+}
+
+// World-space corners of a cell.
+fn cell_bounds(cell: &LodPos) -> (Point3<f32>, Point3<f32>) {
+    let origin = cell.origin();
+    let min = Point3::new(origin.x as f32, origin.y as f32, origin.z as f32);
+    let size = (cell.size_in_chunks() * CHUNK_SIZE as i32) as f32;
+    (min, min + Vector3::repeat(size))
+}
+
+// Distance from a point to the nearest point of a cell, in chunks.
+fn cell_distance(cell: &LodPos, point: &Point3<f32>) -> f32 {
+    let (min, max) = cell_bounds(cell);
+    let nearest = point
+        .coords
+        .zip_zip_map(&min.coords, &max.coords, |p, lo, hi| p.clamp(lo, hi));
+    (nearest - point.coords).norm() / CHUNK_SIZE as f32
+}
@@ -0,0 +1,725 @@
+// Copyright 2025 Nicholas Jordan. All Rights Reserved.
+// github.com/cvusmo/lustre
+// src/terrain.rs
+
+use crate::block::{BlockId, BlockRegistry, AIR};
+use crate::chunk::{Chunk, CHUNK_SIZE};
+use crate::scripting::{run_with_limits, ScriptLimits};
+use crate::state::{log_error, log_info};
+use crate::world::{BlockPos, ChunkPos};
+
+use mlua::prelude::*;
+use mlua::{UserData, UserDataMethods, WeakLua};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::sync::Arc;
+
+/// Name of the built-in generator.
+pub const DEFAULT_GENERATOR: &str = "lustre:default";
+
+// Mixed into the world seed so each noise layer gets its own permutation.
+const SEED_STEP: u64 = 0x9E37_79B9_7F4A_7C15;
+
+// Derives an independent seed for noise layer `layer`.
+fn layer_seed(seed: u64, layer: u64) -> u64 {
+    seed.wrapping_add(SEED_STEP.wrapping_mul(layer + 1))
+}
+
+/// Seeded gradient (Perlin) noise. The same seed always produces the same values.
+#[derive(Debug, Clone)]
+pub struct Perlin {
+    perm: [u8; 512],
+}
+
+impl Perlin {
+    pub fn new(seed: u64) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut table: Vec<u8> = (0..=255).collect();
+        table.shuffle(&mut rng);
+
+        let mut perm = [0; 512];
+        for (i, value) in perm.iter_mut().enumerate() {
+            *value = table[i & 255];
+        }
+        Self { perm }
+    }
+
+    fn hash(&self, i: usize) -> usize {
+        self.perm[i] as usize
+    }
+
+    /// 2D noise, roughly in [-1, 1].
+    pub fn get2(&self, x: f64, y: f64) -> f64 {
+        let (xi, xf) = split(x);
+        let (yi, yf) = split(y);
+        let (u, v) = (fade(xf), fade(yf));
+
+        let a = self.hash(xi) + yi;
+        let b = self.hash(xi + 1) + yi;
+
+        let x1 = lerp(
+            u,
+            grad2(self.hash(a), xf, yf),
+            grad2(self.hash(b), xf - 1.0, yf),
+        );
+        let x2 = lerp(
+            u,
+            grad2(self.hash(a + 1), xf, yf - 1.0),
+            grad2(self.hash(b + 1), xf - 1.0, yf - 1.0),
+        );
+        lerp(v, x1, x2)
+    }
+
+    /// 3D noise, roughly in [-1, 1].
+    pub fn get3(&self, x: f64, y: f64, z: f64) -> f64 {
+        let (xi, xf) = split(x);
+        let (yi, yf) = split(y);
+        let (zi, zf) = split(z);
+        let (u, v, w) = (fade(xf), fade(yf), fade(zf));
+
+        let a = self.hash(xi) + yi;
+        let aa = self.hash(a) + zi;
+        let ab = self.hash(a + 1) + zi;
+        let b = self.hash(xi + 1) + yi;
+        let ba = self.hash(b) + zi;
+        let bb = self.hash(b + 1) + zi;
+
+        let corner = |h: usize, dx: f64, dy: f64, dz: f64| grad3(self.hash(h), dx, dy, dz);
+        lerp(
+            w,
+            lerp(
+                v,
+                lerp(u, corner(aa, xf, yf, zf), corner(ba, xf - 1.0, yf, zf)),
+                lerp(
+                    u,
+                    corner(ab, xf, yf - 1.0, zf),
+                    corner(bb, xf - 1.0, yf - 1.0, zf),
+                ),
+            ),
+            lerp(
+                v,
+                lerp(
+                    u,
+                    corner(aa + 1, xf, yf, zf - 1.0),
+                    corner(ba + 1, xf - 1.0, yf, zf - 1.0),
+                ),
+                lerp(
+                    u,
+                    corner(ab + 1, xf, yf - 1.0, zf - 1.0),
+                    corner(bb + 1, xf - 1.0, yf - 1.0, zf - 1.0),
+                ),
+            ),
+        )
+    }
+
+    /// Fractal 2D noise: `octaves` layers, each at double frequency and half amplitude.
+    pub fn fbm2(&self, x: f64, y: f64, octaves: u32) -> f64 {
+        fbm(octaves, |frequency| self.get2(x * frequency, y * frequency))
+    }
+
+    /// Fractal 3D noise: `octaves` layers, each at double frequency and half amplitude.
+    pub fn fbm3(&self, x: f64, y: f64, z: f64, octaves: u32) -> f64 {
+        fbm(octaves, |frequency| {
+            self.get3(x * frequency, y * frequency, z * frequency)
+        })
+    }
+}
+
+// Sums octaves and normalizes the result back to the single-octave range.
+fn fbm(octaves: u32, sample: impl Fn(f64) -> f64) -> f64 {
+    let mut total = 0.0;
+    let mut amplitude = 1.0;
+    let mut frequency = 1.0;
+    let mut max = 0.0;
+    for _ in 0..octaves.max(1) {
+        total += sample(frequency) * amplitude;
+        max += amplitude;
+        amplitude *= 0.5;
+        frequency *= 2.0;
+    }
+    total / max
+}
+
+// Lattice cell (wrapped to the permutation table) and offset inside it.
+fn split(value: f64) -> (usize, f64) {
+    let floor = value.floor();
+    ((floor as i64 & 255) as usize, value - floor)
+}
+
+fn fade(t: f64) -> f64 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn lerp(t: f64, a: f64, b: f64) -> f64 {
+    a + t * (b - a)
+}
+
+fn grad2(hash: usize, x: f64, y: f64) -> f64 {
+    // Eight directions; scaled so the output spans about [-1, 1].
+    let value = match hash & 7 {
+        0 => x + y,
+        1 => x - y,
+        2 => -x + y,
+        3 => -x - y,
+        4 => x,
+        5 => -x,
+        6 => y,
+        _ => -y,
+    };
+    value * std::f64::consts::FRAC_1_SQRT_2
+}
+
+fn grad3(hash: usize, x: f64, y: f64, z: f64) -> f64 {
+    let h = hash & 15;
+    let u = if h < 8 { x } else { y };
+    let v = if h < 4 {
+        y
+    } else if h == 12 || h == 14 {
+        x
+    } else {
+        z
+    };
+    (if h & 1 == 0 { u } else { -u }) + (if h & 2 == 0 { v } else { -v })
+}
+
+/// Surface type of a column, chosen from its temperature and humidity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Biome {
+    Tundra,
+    Plains,
+    Forest,
+    Savanna,
+    Desert,
+}
+
+impl Biome {
+    /// Picks a biome for a temperature and humidity, both in [0, 1].
+    pub fn from_climate(temperature: f64, humidity: f64) -> Self {
+        if temperature < 0.3 {
+            Biome::Tundra
+        } else if temperature > 0.65 && humidity < 0.4 {
+            Biome::Desert
+        } else if humidity > 0.6 {
+            Biome::Forest
+        } else if temperature > 0.65 {
+            Biome::Savanna
+        } else {
+            Biome::Plains
+        }
+    }
+
+    /// Top block of a column above sea level.
+    pub fn surface_block(&self) -> &'static str {
+        match self {
+            Biome::Tundra => "lustre:snow",
+            Biome::Desert => "lustre:sand",
+            Biome::Plains | Biome::Forest | Biome::Savanna => "lustre:grass",
+        }
+    }
+
+    /// Blocks between the surface and stone.
+    pub fn subsurface_block(&self) -> &'static str {
+        match self {
+            Biome::Desert => "lustre:sand",
+            _ => "lustre:dirt",
+        }
+    }
+}
+
+/// Produces chunks for a world. Must be deterministic for a given seed and position.
+pub trait TerrainGenerator {
+    fn generate(&self, pos: ChunkPos, registry: &BlockRegistry) -> Chunk;
+
+    /// A copy that can generate on worker threads. Generators tied to the thread they were
+    /// made on, like Lua ones, return `None` and generate on the thread streaming the world.
+    fn share(&self) -> Option<Arc<dyn TerrainGenerator + Send + Sync>> {
+        None
+    }
+
+    /// Coarse chunk for distant terrain where each block stands for `scale`³ world blocks
+    /// starting at `origin`. Generators without level-of-detail support return `None`.
+    fn generate_lod(
+        &self,
+        _origin: BlockPos,
+        _scale: i32,
+        _registry: &BlockRegistry,
+    ) -> Option<Chunk> {
+        None
+    }
+}
+
+/// Shape parameters for the built-in generator.
+#[derive(Debug, Clone)]
+pub struct TerrainSettings {
+    pub sea_level: i32,
+    /// Average surface height.
+    pub base_height: f64,
+    /// Maximum distance of the surface above or below `base_height`.
+    pub height_variation: f64,
+    /// Horizontal scale of hills in blocks.
+    pub height_scale: f64,
+    /// Horizontal scale of biomes in blocks.
+    pub climate_scale: f64,
+    /// Higher values carve wider and more frequent caves; 0 disables them.
+    pub cave_density: f64,
+    /// Top of the bedrock floor.
+    pub bedrock_level: i32,
+}
+
+impl Default for TerrainSettings {
+    fn default() -> Self {
+        Self {
+            sea_level: 0,
+            base_height: 8.0,
+            height_variation: 48.0,
+            height_scale: 256.0,
+            climate_scale: 1024.0,
+            cave_density: 0.08,
+            bedrock_level: -128,
+        }
+    }
+}
+
+// An ore and where it appears.
+#[derive(Clone)]
+struct OreLayer {
+    name: &'static str,
+    noise: Perlin,
+    // Ore only appears at least this far below sea level.
+    min_depth: i32,
+    // Higher is rarer.
+    threshold: f64,
+}
+
+// Block ids looked up once per chunk.
+pub(crate) struct TerrainBlocks {
+    pub(crate) stone: BlockId,
+    pub(crate) water: BlockId,
+    pub(crate) bedrock: BlockId,
+    pub(crate) sand: BlockId,
+}
+
+impl TerrainBlocks {
+    pub(crate) fn new(registry: &BlockRegistry) -> Self {
+        let id = |name| registry.id_of(name).unwrap_or(AIR);
+        Self {
+            stone: id("lustre:stone"),
+            water: id("lustre:water"),
+            bedrock: id("lustre:bedrock"),
+            sand: id("lustre:sand"),
+        }
+    }
+
+    // Surface and subsurface blocks of a column.
+    pub(crate) fn column(
+        &self,
+        registry: &BlockRegistry,
+        biome: Biome,
+        at_sea_level: bool,
+    ) -> (BlockId, BlockId) {
+        // Beaches and sea floors are sand, except in the cold.
+        if at_sea_level && biome != Biome::Tundra {
+            return (self.sand, self.sand);
+        }
+        let id = |name| registry.id_of(name).unwrap_or(self.stone);
+        (id(biome.surface_block()), id(biome.subsurface_block()))
+    }
+}
+
+/// Layered-noise terrain with biomes, caves and ore veins.
+#[derive(Clone)]
+pub struct DefaultGenerator {
+    settings: TerrainSettings,
+    // Shared with the planet generator, which samples them in 3D.
+    pub(crate) height: Perlin,
+    pub(crate) temperature: Perlin,
+    pub(crate) humidity: Perlin,
+    caves: Perlin,
+    ores: Vec<OreLayer>,
+}
+
+impl DefaultGenerator {
+    pub fn new(seed: u64, settings: TerrainSettings) -> Self {
+        let ore = |layer, name, min_depth, threshold| OreLayer {
+            name,
+            noise: Perlin::new(layer_seed(seed, layer)),
+            min_depth,
+            threshold,
+        };
+
+        Self {
+            height: Perlin::new(layer_seed(seed, 0)),
+            temperature: Perlin::new(layer_seed(seed, 1)),
+            humidity: Perlin::new(layer_seed(seed, 2)),
+            caves: Perlin::new(layer_seed(seed, 3)),
+            ores: vec![
+                ore(4, "lustre:coal_ore", 0, 0.55),
+                ore(5, "lustre:iron_ore", 16, 0.6),
+                ore(6, "lustre:gold_ore", 48, 0.65),
+            ],
+            settings,
+        }
+    }
+
+    pub fn settings(&self) -> &TerrainSettings {
+        &self.settings
+    }
+
+    /// Height of the top solid block of a column.
+    pub fn surface_height(&self, x: i32, z: i32) -> i32 {
+        let scale = self.settings.height_scale;
+        let noise = self.height.fbm2(x as f64 / scale, z as f64 / scale, 5);
+        (self.settings.base_height + noise * self.settings.height_variation).floor() as i32
+    }
+
+    /// Biome of a column.
+    pub fn biome(&self, x: i32, z: i32) -> Biome {
+        let scale = self.settings.climate_scale;
+        let (x, z) = (x as f64 / scale, z as f64 / scale);
+        // Map noise to [0, 1].
+        let temperature = (self.temperature.fbm2(x, z, 3) + 1.0) * 0.5;
+        let humidity = (self.humidity.fbm2(x, z, 3) + 1.0) * 0.5;
+        Biome::from_climate(temperature, humidity)
+    }
+
+    // Whether an underground block is carved out by a cave.
+    pub(crate) fn is_cave(&self, x: i32, y: i32, z: i32) -> bool {
+        // Two bands of 3D noise near zero intersect in winding tunnels.
+        let (x, y, z) = (x as f64 / 48.0, y as f64 / 32.0, z as f64 / 48.0);
+        let a = self.caves.get3(x, y, z);
+        let b = self.caves.get3(x + 100.0, y + 100.0, z + 100.0);
+        a * a + b * b < self.settings.cave_density * 0.1
+    }
+
+    // The ore at a stone block `depth` blocks below sea level, if any.
+    pub(crate) fn ore_at(
+        &self,
+        x: i32,
+        y: i32,
+        z: i32,
+        depth: i32,
+        registry: &BlockRegistry,
+    ) -> Option<BlockId> {
+        let (fx, fy, fz) = (x as f64 / 6.0, y as f64 / 6.0, z as f64 / 6.0);
+        self.ores
+            .iter()
+            .filter(|ore| depth >= ore.min_depth)
+            .find(|ore| ore.noise.get3(fx, fy, fz) > ore.threshold)
+            .and_then(|ore| registry.id_of(ore.name))
+    }
+}
+
+impl DefaultGenerator {
+    // Fills a chunk whose blocks each cover `scale`³ world blocks from `origin`.
+    // Caves and ores are too small to show at coarser scales and are skipped there.
+    fn generate_scaled(&self, origin: BlockPos, scale: i32, registry: &BlockRegistry) -> Chunk {
+        let blocks = TerrainBlocks::new(registry);
+        let size = CHUNK_SIZE as i32;
+        let sea_level = self.settings.sea_level;
+        let detailed = scale == 1;
+
+        // Column heights and biomes for the whole chunk.
+        let mut heights = [0; CHUNK_SIZE * CHUNK_SIZE];
+        let mut biomes = [Biome::Plains; CHUNK_SIZE * CHUNK_SIZE];
+        for z in 0..size {
+            for x in 0..size {
+                let column = (x + z * size) as usize;
+                let (wx, wz) = (origin.x + x * scale, origin.z + z * scale);
+                heights[column] = self.surface_height(wx, wz);
+                biomes[column] = self.biome(wx, wz);
+            }
+        }
+
+        // Nothing but air above the terrain and the sea.
+        let top = *heights.iter().max().unwrap();
+        if origin.y > top.max(sea_level) {
+            return Chunk::new();
+        }
+
+        let mut chunk = Chunk::new();
+        for z in 0..size {
+            for x in 0..size {
+                let column = (x + z * size) as usize;
+                let height = heights[column];
+                let biome = biomes[column];
+                let (surface, subsurface) = blocks.column(registry, biome, height <= sea_level + 1);
+
+                let (wx, wz) = (origin.x + x * scale, origin.z + z * scale);
+                for y in 0..size {
+                    let wy = origin.y + y * scale;
+                    let depth = height - wy;
+                    let block = if wy <= self.settings.bedrock_level {
+                        blocks.bedrock
+                    } else if depth < 0 {
+                        if wy <= sea_level {
+                            blocks.water
+                        } else {
+                            AIR
+                        }
+                    } else if detailed && depth > 3 && self.is_cave(wx, wy, wz) {
+                        // Caves stay a few blocks under the surface.
+                        AIR
+                    } else if depth < scale {
+                        surface
+                    } else if depth < scale + 3 {
+                        subsurface
+                    } else if detailed {
+                        self.ore_at(wx, wy, wz, sea_level - wy, registry)
+                            .unwrap_or(blocks.stone)
+                    } else {
+                        blocks.stone
+                    };
+
+                    if block != AIR {
+                        chunk.set(x as usize, y as usize, z as usize, block);
+                    }
+                }
+            }
+        }
+        chunk
+    }
+}
+
+impl TerrainGenerator for DefaultGenerator {
+    fn generate(&self, pos: ChunkPos, registry: &BlockRegistry) -> Chunk {
+        self.generate_scaled(pos.origin(), 1, registry)
+    }
+
+    fn share(&self) -> Option<Arc<dyn TerrainGenerator + Send + Sync>> {
+        Some(Arc::new(self.clone()))
+    }
+
+    fn generate_lod(
+        &self,
+        origin: BlockPos,
+        scale: i32,
+        registry: &BlockRegistry,
+    ) -> Option<Chunk> {
+        Some(self.generate_scaled(origin, scale, registry))
+    }
+}
+
+/// Creates a generator for a world seed.
+pub type GeneratorFactory = Box<dyn Fn(u64) -> Box<dyn TerrainGenerator>>;
+
+/// Terrain generators by name, so mods can add their own.
+pub struct GeneratorRegistry {
+    factories: HashMap<String, GeneratorFactory>,
+}
+
+impl Default for GeneratorRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GeneratorRegistry {
+    /// Creates a registry containing the built-in generator.
+    pub fn new() -> Self {
+        let mut registry = Self {
+            factories: HashMap::new(),
+        };
+        registry.register(DEFAULT_GENERATOR, |seed| {
+            Box::new(DefaultGenerator::new(seed, TerrainSettings::default()))
+        });
+        registry
+    }
+
+    /// Registers a generator, replacing any with the same name.
+    pub fn register(
+        &mut self,
+        name: &str,
+        factory: impl Fn(u64) -> Box<dyn TerrainGenerator> + 'static,
+    ) {
+        if self
+            .factories
+            .insert(name.to_string(), Box::new(factory))
+            .is_some()
+        {
+            log_info(&format!("Replaced terrain generator {}", name));
+        }
+    }
+
+    /// Creates the named generator for a world seed.
+    pub fn create(&self, name: &str, seed: u64) -> Option<Box<dyn TerrainGenerator>> {
+        self.factories.get(name).map(|factory| factory(seed))
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.factories.contains_key(name)
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.factories.keys().map(String::as_str)
+    }
+}
+
+// Chunk handed to Lua generators to fill in.
+struct LuaChunk(Chunk);
+
+impl UserData for LuaChunk {
+    fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
+        methods.add_method("get", |_, this, (x, y, z): (usize, usize, usize)| {
+            check_local(x, y, z)?;
+            Ok(this.0.get(x, y, z))
+        });
+        methods.add_method_mut(
+            "set",
+            |_, this, (x, y, z, block): (usize, usize, usize, BlockId)| {
+                check_local(x, y, z)?;
+                this.0.set(x, y, z, block);
+                Ok(())
+            },
+        );
+        methods.add_method_mut("fill", |_, this, block: BlockId| {
+            this.0 = Chunk::filled(block);
+            Ok(())
+        });
+    }
+}
+
+fn check_local(x: usize, y: usize, z: usize) -> LuaResult<()> {
+    if x < CHUNK_SIZE && y < CHUNK_SIZE && z < CHUNK_SIZE {
+        Ok(())
+    } else {
+        Err(LuaError::runtime(format!(
+            "block ({}, {}, {}) is outside the chunk",
+            x, y, z
+        )))
+    }
+}
+
+impl UserData for Perlin {
+    fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
+        methods.add_method("get2", |_, this, (x, y): (f64, f64)| Ok(this.get2(x, y)));
+        methods.add_method("get3", |_, this, (x, y, z): (f64, f64, f64)| {
+            Ok(this.get3(x, y, z))
+        });
+        methods.add_method(
+            "fbm2",
+            |_, this, (x, y, octaves): (f64, f64, Option<u32>)| {
+                Ok(this.fbm2(x, y, octaves.unwrap_or(4)))
+            },
+        );
+        methods.add_method(
+            "fbm3",
+            |_, this, (x, y, z, octaves): (f64, f64, f64, Option<u32>)| {
+                Ok(this.fbm3(x, y, z, octaves.unwrap_or(4)))
+            },
+        );
+    }
+}
+
+// A generator implemented by a Lua function `function(chunk, ctx)`.
+struct LuaGenerator {
+    // Weak so the generator registry does not keep the Lua state alive.
+    lua: WeakLua,
+    function: LuaFunction,
+    seed: u64,
+    // What each call of a mod's generator may use.
+    limits: Option<ScriptLimits>,
+}
+
+impl LuaGenerator {
+    fn try_generate(&self, pos: ChunkPos, registry: &BlockRegistry) -> LuaResult<Chunk> {
+        let lua = self
+            .lua
+            .try_upgrade()
+            .ok_or_else(|| LuaError::runtime("Lua state was closed"))?;
+
+        let blocks = lua.create_table()?;
+        for block in registry.iter() {
+            blocks.set(block.name.as_str(), block.id)?;
+        }
+
+        let ctx = lua.create_table()?;
+        ctx.set("x", pos.x)?;
+        ctx.set("y", pos.y)?;
+        ctx.set("z", pos.z)?;
+        ctx.set("size", CHUNK_SIZE)?;
+        // Lua integers are signed; the bits are what matter for seeding.
+        ctx.set("seed", self.seed as i64)?;
+        ctx.set("blocks", blocks)?;
+
+        let chunk = lua.create_userdata(LuaChunk(Chunk::new()))?;
+        let args = (chunk.clone(), ctx);
+        match &self.limits {
+            Some(limits) => run_with_limits(&lua, limits, || self.function.call::<()>(args))?,
+            None => self.function.call::<()>(args)?,
+        }
+        Ok(chunk.take::<LuaChunk>()?.0)
+    }
+}
+
+impl TerrainGenerator for LuaGenerator {
+    fn generate(&self, pos: ChunkPos, registry: &BlockRegistry) -> Chunk {
+        self.try_generate(pos, registry).unwrap_or_else(|err| {
+            log_error(&format!(
+                "Lua terrain generator failed at {:?}: {}",
+                pos, err
+            ));
+            Chunk::new()
+        })
+    }
+}
+
+/// Registers the `terrain` table with Lua.
+///
+/// `terrain.register_generator(name, function(chunk, ctx) ... end)` adds a generator.
+/// `ctx` holds the chunk position (`x`, `y`, `z`), `size`, `seed` and a `blocks`
+/// table of block ids by name; the function fills `chunk` with `chunk:set(x, y, z, id)`
+/// or `chunk:fill(id)`. `terrain.noise(seed)` returns seeded noise with
+/// `get2`, `get3`, `fbm2` and `fbm3` methods.
+///
+/// Lua generators run on the thread that owns the Lua state, so the registry is not shared
+/// across threads.
+pub fn register_lua_api(lua: &Lua, generators: Rc<RefCell<GeneratorRegistry>>) -> LuaResult<()> {
+    lua.set_app_data(generators.clone());
+    lua.globals()
+        .set("terrain", create_table(lua, generators, None)?)?;
+    Ok(())
+}
+
+/// Creates the `terrain` table for a mod, whose generators run within `limits`. Returns
+/// `None` if no generator registry is registered.
+pub fn create_mod_api(lua: &Lua, limits: &ScriptLimits) -> LuaResult<Option<LuaTable>> {
+    let generators = lua
+        .app_data_ref::<Rc<RefCell<GeneratorRegistry>>>()
+        .map(|generators| generators.clone());
+    generators
+        .map(|generators| create_table(lua, generators, Some(*limits)))
+        .transpose()
+}
+
+// Builds a `terrain` table whose generators run within `limits`.
+fn create_table(
+    lua: &Lua,
+    generators: Rc<RefCell<GeneratorRegistry>>,
+    limits: Option<ScriptLimits>,
+) -> LuaResult<LuaTable> {
+    let terrain = lua.create_table()?;
+
+    let register_generator =
+        lua.create_function(move |lua, (name, function): (String, LuaFunction)| {
+            let weak = lua.weak();
+            generators.borrow_mut().register(&name, move |seed| {
+                Box::new(LuaGenerator {
+                    lua: weak.clone(),
+                    function: function.clone(),
+                    seed,
+                    limits,
+                })
+            });
+            log_info(&format!("Lua registered terrain generator {}", name));
+            Ok(())
+        })?;
+    terrain.set("register_generator", register_generator)?;
+
+    let noise = lua.create_function(|_, seed: i64| Ok(Perlin::new(seed as u64)))?;
+    terrain.set("noise", noise)?;
+    Ok(terrain)
+}
@@ -4,19 +4,24 @@
 // src/modules/engine/gui/utils.rs
 // github.com/cvusmo/gameengine
 
-use crate::state::{log_error, log_info, AppState};
+use crate::diagnostics::{chunk_name, run_chunk, Diagnostic};
+use crate::events;
+use crate::state::{log_error, log_info, log_warn, AppState};
 use glib::source::timeout_add_local;
 use gtk4::prelude::*;
 use gtk4::WrapMode::Word;
 use gtk4::{
     Application, ApplicationWindow, Box as GtkBox, ButtonsType, FileChooserAction,
-    FileChooserDialog, MessageDialog, MessageType, PolicyType::Automatic, ResponseType,
-    ScrolledWindow, TextBuffer, TextView,
+    FileChooserDialog, Label, ListBox, MessageDialog, MessageType, PolicyType::Automatic,
+    ResponseType, ScrolledWindow, TextBuffer, TextView,
 };
 use std::fs;
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
+// Buffer tag marking the line a diagnostic points at.
+const ERROR_LINE_TAG: &str = "error-line";
+
 // Create text editor
 pub fn create_text_editor(content: &str, state: &Arc<Mutex<AppState>>) -> ScrolledWindow {
     // Create TextBuffer
@@ -262,19 +267,171 @@ pub fn handle_exit(state: Arc<Mutex<AppState>>, app: &Application) {
     //}
 }
 
-// Helper function to execute lua script
-pub fn execute_lua_script(state: &Arc<Mutex<AppState>>, script_content: &str) {
+/// Name the script in the editor is run under: its file path, or `editor` if it has none.
+pub fn editor_chunk_name(state: &AppState) -> String {
+    match state.project_path {
+        Some(ref path) => chunk_name(path),
+        None => "=editor".to_string(),
+    }
+}
+
+// Removes the error highlight from the whole buffer.
+fn clear_error_line(buffer: &TextBuffer) {
+    if let Some(tag) = buffer.tag_table().lookup(ERROR_LINE_TAG) {
+        buffer.remove_tag(&tag, &buffer.start_iter(), &buffer.end_iter());
+    }
+}
+
+/// Moves the editor's cursor to a 1-based line and highlights it.
+pub fn jump_to_line(text_view: &TextView, line: u32) {
+    let buffer = text_view.buffer();
+    let Some(mut start) = buffer.iter_at_line(line.saturating_sub(1) as i32) else {
+        log_warn(&format!("Line {} is past the end of the script", line));
+        return;
+    };
+    let mut end = start;
+    end.forward_to_line_end();
+
+    clear_error_line(&buffer);
+    let tag = buffer.tag_table().lookup(ERROR_LINE_TAG).or_else(|| {
+        buffer.create_tag(
+            Some(ERROR_LINE_TAG),
+            &[("paragraph-background", &"rgba(220, 50, 47, 0.3)")],
+        )
+    });
+    if let Some(tag) = tag {
+        buffer.apply_tag(&tag, &start, &end);
+    }
+    buffer.place_cursor(&start);
+    text_view.scroll_to_iter(&mut start, 0.1, false, 0.0, 0.0);
+    text_view.grab_focus();
+}
+
+/// Creates the list script errors are shown in. Clicking one jumps to its line in the
+/// editor.
+pub fn create_diagnostics_panel(state: &Arc<Mutex<AppState>>) -> ScrolledWindow {
+    let list = ListBox::new();
+    list.add_css_class("diagnostics");
+
+    let state_clone = Arc::clone(state);
+    list.connect_row_activated(move |_, row| {
+        let (diagnostic, text_view, chunk) = {
+            let state_lock = state_clone.lock().unwrap();
+            (
+                state_lock.diagnostics.get(row.index() as usize).cloned(),
+                state_lock.text_view.clone(),
+                editor_chunk_name(&state_lock),
+            )
+        };
+        let (Some(diagnostic), Some(text_view)) = (diagnostic, text_view) else {
+            return;
+        };
+        match diagnostic.line {
+            Some(line) if diagnostic.is_in(&chunk) => jump_to_line(&text_view, line),
+            Some(_) => log_warn(&format!("{} is not in the open script", diagnostic.chunk)),
+            None => (),
+        }
+    });
+
+    {
+        let mut state_lock = state.lock().unwrap();
+        state_lock.diagnostics_list = Some(list.clone());
+    }
+
+    let scrolled_window = ScrolledWindow::new();
+    scrolled_window.set_vexpand(true);
+    scrolled_window.set_hexpand(true);
+    scrolled_window.set_min_content_height(120);
+    scrolled_window.set_child(Some(&list));
+    scrolled_window.set_policy(Automatic, Automatic);
+    scrolled_window
+}
+
+/// Replaces the errors in the diagnostics panel and clears the editor's error highlight.
+pub fn show_diagnostics(state: &Arc<Mutex<AppState>>, diagnostics: Vec<Diagnostic>) {
+    let text_view = {
+        let mut state_lock = state.lock().unwrap();
+        state_lock.diagnostics = diagnostics;
+        state_lock.text_view.clone()
+    };
+
+    if let Some(text_view) = text_view {
+        clear_error_line(&text_view.buffer());
+    }
+    refresh_diagnostics_list(state);
+}
+
+/// Adds errors scripts raised outside the editor to the diagnostics panel, skipping ones
+/// already listed.
+pub fn add_diagnostics(state: &Arc<Mutex<AppState>>, diagnostics: Vec<Diagnostic>) {
+    {
+        let mut state_lock = state.lock().unwrap();
+        for diagnostic in diagnostics {
+            if !state_lock.diagnostics.contains(&diagnostic) {
+                state_lock.diagnostics.push(diagnostic);
+            }
+        }
+    }
+    refresh_diagnostics_list(state);
+}
+
+// Lists the state's diagnostics in the panel.
+fn refresh_diagnostics_list(state: &Arc<Mutex<AppState>>) {
+    let (list, diagnostics) = {
+        let state_lock = state.lock().unwrap();
+        (
+            state_lock.diagnostics_list.clone(),
+            state_lock.diagnostics.clone(),
+        )
+    };
+    let Some(list) = list else {
+        return;
+    };
+    while let Some(child) = list.first_child() {
+        list.remove(&child);
+    }
+    for diagnostic in &diagnostics {
+        let label = Label::new(Some(&diagnostic.to_string()));
+        label.set_xalign(0.0);
+        label.set_wrap(true);
+        label.set_selectable(false);
+        if let Some(ref traceback) = diagnostic.traceback {
+            label.set_tooltip_text(Some(traceback));
+        }
+        list.append(&label);
+    }
+}
+
+/// Runs a Lua script under `chunk_name`, showing any error in the diagnostics panel.
+pub fn execute_lua_script(state: &Arc<Mutex<AppState>>, script_content: &str, chunk_name: &str) {
     // Get reference to Lua instance
-    let lua = {
+    let (lua, project) = {
         let state_lock = state.lock().unwrap();
-        state_lock.lua.clone()
+        (state_lock.lua.clone(), state_lock.project_path.clone())
     };
 
-    let lua_lock = lua.lock().unwrap();
+    let result = {
+        let lua_lock = lua.lock().unwrap();
+        let run = || run_chunk(&lua_lock, script_content, chunk_name);
+        // Rerunning the project replaces the event handlers its last run subscribed.
+        match project {
+            Some(ref path) => events::run_script(&lua_lock, path, run),
+            None => run(),
+        }
+    };
 
-    // Load and execute Lua script
-    match lua_lock.load(script_content).exec() {
-        Ok(_) => log_info("Lua script compiled and executed successfuly."),
-        Err(err) => log_error(&format!("Failed to execute Lua script: {:?}", err)),
-    }
+    let diagnostics = match result {
+        Ok(()) => {
+            log_info("Lua script compiled and executed successfuly.");
+            Vec::new()
+        }
+        Err(diagnostic) => {
+            log_error(&format!("Lua script failed: {}", diagnostic));
+            if let Some(ref traceback) = diagnostic.traceback {
+                log_error(traceback);
+            }
+            vec![diagnostic]
+        }
+    };
+    show_diagnostics(state, diagnostics);
 }
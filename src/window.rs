@@ -2,22 +2,429 @@
 // github.com/cvusmo/lustre
 // src/window.rs
 
-use crate::render::lustre_render;
+use crate::block::{BlockId, BlockRegistry, AIR};
+use crate::camera_controller::{CameraController, CameraMode, CameraSettings};
+use crate::character::{CharacterController, CharacterSettings};
+use crate::chunk::CHUNK_SIZE;
+use crate::events::{emit, Event};
+use crate::game::{GameLoop, Player, Transform};
+use crate::mesher::ChunkMesher;
+use crate::physics::PhysicsWorld;
+use crate::planet::PlanetGenerator;
+use crate::raycast::{self, RaycastHit, REACH};
+use crate::render::Renderer;
+use crate::save::{WorldMetadata, WorldSave};
+use crate::settings::SETTINGS_PATH;
+use crate::state::{log_error, log_info, log_warn};
+use crate::streaming::{ChunkStreamer, StreamingSettings};
+use crate::terrain::{
+    DefaultGenerator, GeneratorRegistry, TerrainGenerator, TerrainSettings, DEFAULT_GENERATOR,
+};
+use crate::world::{BlockPos, ChunkPos, World, WorldSettings};
+use rapier3d::na::{Point3, Vector3};
+use specs::{Builder, Entity, WorldExt};
+use std::cell::RefCell;
+use std::rc::Rc;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use vulkano::instance::{Instance, InstanceCreateFlags, InstanceCreateInfo};
 use vulkano::library::VulkanLibrary;
 use vulkano::swapchain::Surface;
 use winit::application::ApplicationHandler;
-use winit::event::WindowEvent;
+use winit::event::{DeviceEvent, DeviceId, ElementState, KeyEvent, MouseButton, WindowEvent};
 use winit::event_loop::{ActiveEventLoop, ControlFlow, EventLoop};
+use winit::keyboard::{KeyCode, PhysicalKey};
 use winit::raw_window_handle::HasDisplayHandle;
-use winit::window::{Window, WindowId};
+use winit::window::{CursorGrabMode, Window, WindowId};
+
+// Height of the camera above the ground at spawn.
+const SPAWN_CLEARANCE: f32 = 20.0;
+
+// Size and colour of the crates dropped with G.
+const CRATE_HALF_EXTENT: f32 = 0.4;
+const CRATE_COLOR: [f32; 3] = [0.6, 0.4, 0.2];
+
+// How often edited chunks and world metadata are written to the save.
+const AUTOSAVE_INTERVAL: Duration = Duration::from_secs(30);
+
+// Chunks searched downwards from for the ground under a registered generator's spawn.
+const SPAWN_SEARCH_TOP: i32 = 8;
+const SPAWN_SEARCH_BOTTOM: i32 = -8;
+
+// Highest solid block a generator puts in the column at `x`, `z`, searching the chunks
+// between `SPAWN_SEARCH_TOP` and `SPAWN_SEARCH_BOTTOM`.
+fn find_ground(
+    generator: &dyn TerrainGenerator,
+    registry: &BlockRegistry,
+    x: i32,
+    z: i32,
+) -> Option<i32> {
+    let column = BlockPos::new(x, 0, z).chunk();
+    let local = BlockPos::new(x, 0, z).local();
+    (SPAWN_SEARCH_BOTTOM..=SPAWN_SEARCH_TOP)
+        .rev()
+        .find_map(|chunk_y| {
+            let pos = ChunkPos::new(column.x, chunk_y, column.z);
+            let chunk = generator.generate(pos, registry);
+            (0..CHUNK_SIZE)
+                .rev()
+                .find(|&y| chunk.get(local.0, y, local.2) != AIR)
+                .map(|y| pos.origin().y + y as i32)
+        })
+}
+
+// Creates the generator described by the settings and picks the camera's spawn position.
+// Chunks are streamed in around the camera once it is placed.
+fn create_generator(
+    settings: &WorldSettings,
+    generators: &GeneratorRegistry,
+    registry: &BlockRegistry,
+) -> (Box<dyn TerrainGenerator>, Point3<f32>) {
+    let terrain = TerrainSettings::default();
+    if let Some(ref planet) = settings.planet {
+        let generator = PlanetGenerator::new(settings.seed, planet.clone(), terrain);
+        // Spawn above the north pole.
+        let height = generator.surface_radius(&Vector3::y()) as f32;
+        return (
+            Box::new(generator),
+            Point3::new(0.0, height + SPAWN_CLEARANCE, 0.0),
+        );
+    }
+
+    if settings.generator != DEFAULT_GENERATOR {
+        match generators.create(&settings.generator, settings.seed) {
+            Some(generator) => {
+                log_info(&format!("Generating terrain with {}", settings.generator));
+                let height = find_ground(generator.as_ref(), registry, 0, 0).unwrap_or(0) + 1;
+                return (
+                    generator,
+                    Point3::new(0.0, height as f32 + SPAWN_CLEARANCE, 0.0),
+                );
+            }
+            None => log_warn(&format!(
+                "Unknown terrain generator {}, using {}",
+                settings.generator, DEFAULT_GENERATOR
+            )),
+        }
+    }
+
+    let generator = DefaultGenerator::new(settings.seed, terrain);
+    let height = generator.surface_height(0, 0) as f32;
+    (
+        Box::new(generator),
+        Point3::new(0.0, height + SPAWN_CLEARANCE, 0.0),
+    )
+}
+
+// Opens the save directory from the settings and reads its metadata, if it has any.
+fn open_save(settings: &WorldSettings) -> (Option<WorldSave>, Option<WorldMetadata>) {
+    let Some(ref dir) = settings.save_dir else {
+        return (None, None);
+    };
+    let save = match WorldSave::open(dir) {
+        Ok(save) => save,
+        Err(err) => {
+            log_error(&format!("Failed to open save {}: {}", dir, err));
+            return (None, None);
+        }
+    };
+    match save.load_metadata() {
+        Ok(metadata) => (Some(save), metadata),
+        // Don't overwrite a save we can't read.
+        Err(err) => {
+            log_error(&format!("Failed to read save {}: {}", dir, err));
+            (None, None)
+        }
+    }
+}
 
-#[derive(Default)]
 struct App {
     window: Option<Arc<Window>>,
-    surface: Option<Arc<Surface>>,
-    instance: Option<Arc<Instance>>,
+    renderer: Option<Renderer>,
+    camera_controller: CameraController,
+    // Shared with the editor's scripts, like the game.
+    world: Rc<RefCell<World>>,
+    generator: Box<dyn TerrainGenerator>,
+    streamer: ChunkStreamer,
+    mesher: ChunkMesher,
+    physics: PhysicsWorld,
+    // Shared with the editor's scripts.
+    game: Rc<RefCell<GameLoop>>,
+    // The player while walking, and the entity that stands for it in the game.
+    character: CharacterController,
+    player: Entity,
+    // Block under the crosshair, and the block right click places.
+    target: Option<RaycastHit>,
+    held_block: BlockId,
+    metadata: WorldMetadata,
+    // Where the camera starts.
+    spawn: Point3<f32>,
+    last_frame: Instant,
+    last_save: Instant,
+}
+
+impl App {
+    fn new(
+        camera_settings: CameraSettings,
+        character_settings: CharacterSettings,
+        mut world_settings: WorldSettings,
+        streaming_settings: StreamingSettings,
+        world: Rc<RefCell<World>>,
+        game: Rc<RefCell<GameLoop>>,
+        generators: &RefCell<GeneratorRegistry>,
+    ) -> Self {
+        let (save, saved) = open_save(&world_settings);
+        if let Some(ref saved) = saved {
+            world_settings.seed = saved.seed;
+            world_settings.generator = saved.generator.clone();
+            world_settings.planet = saved.planet.clone();
+        }
+
+        let (held_block, physics) = {
+            let mut world = world.borrow_mut();
+            world.set_planet(world_settings.planet.clone());
+            (
+                world.registry().id_of("lustre:stone").unwrap_or_default(),
+                PhysicsWorld::new(world.gravity()),
+            )
+        };
+        let (generator, spawn) = create_generator(
+            &world_settings,
+            &generators.borrow(),
+            world.borrow().registry(),
+        );
+        let metadata = saved.unwrap_or_else(|| WorldMetadata {
+            seed: world_settings.seed,
+            generator: world_settings.generator.clone(),
+            spawn: spawn.into(),
+            planet: world_settings.planet.clone(),
+            ..Default::default()
+        });
+        let spawn = Point3::from(metadata.spawn);
+
+        let mut streamer = ChunkStreamer::new(streaming_settings);
+        if let Some(ref save) = save {
+            log_info(&format!("Playing world saved in {}", save.dir().display()));
+        }
+        streamer.set_save(save);
+
+        // Leave one core for the event loop and renderer.
+        let threads = std::thread::available_parallelism().map_or(1, |n| n.get().saturating_sub(1));
+        let mesher = ChunkMesher::new(world.borrow().registry(), threads);
+        if !streamer.start_workers(generator.as_ref(), world.borrow().registry(), threads) {
+            log_info("Terrain generator runs on the render thread");
+        }
+
+        let player = game
+            .borrow_mut()
+            .world_mut()
+            .create_entity()
+            .with(Transform::new(spawn))
+            .with(Player)
+            .build();
+        let events = game.borrow().events().clone();
+        emit(&events, &Event::EntitySpawned { entity: player });
+        emit(&events, &Event::PlayerJoined { entity: player });
+        Self {
+            window: None,
+            renderer: None,
+            camera_controller: CameraController::new(camera_settings),
+            spawn,
+            world,
+            generator,
+            streamer,
+            mesher,
+            physics,
+            game,
+            character: CharacterController::new(character_settings, spawn),
+            player,
+            target: None,
+            held_block,
+            metadata,
+            last_frame: Instant::now(),
+            last_save: Instant::now(),
+        }
+    }
+
+    // Writes edited chunks and the world metadata to the save, if there is one.
+    fn save_world(&mut self) {
+        self.last_save = Instant::now();
+        let Some(save) = self.streamer.save_mut() else {
+            return;
+        };
+        if let Err(err) = save.save_unsaved(&mut self.world.borrow_mut()) {
+            log_error(&format!("Failed to save chunks: {}", err));
+        }
+        if let Err(err) = save.save_metadata(&self.metadata) {
+            log_error(&format!("Failed to save world metadata: {}", err));
+        }
+    }
+
+    // Streams chunks around the camera, queues changed chunks for meshing and uploads the
+    // meshes that are ready.
+    fn update_chunks(&mut self) {
+        let Some(ref mut renderer) = self.renderer else {
+            return;
+        };
+        let camera = renderer.camera();
+        let frustum = camera.frustum(renderer.aspect_ratio());
+        let mut world = self.world.borrow_mut();
+        let update = self.streamer.update(
+            &mut world,
+            self.generator.as_ref(),
+            &mut self.mesher,
+            camera.position,
+            Some(&frustum),
+        );
+        for key in update.removed_meshes {
+            renderer.remove_mesh(key);
+        }
+
+        // Chunks scripts edited since the last frame are remeshed here, once each.
+        self.mesher.schedule_dirty(&mut world);
+        for (key, mesh) in self.mesher.poll() {
+            renderer.upload_mesh(key, &mesh);
+        }
+
+        // Handlers may edit the world, so it's released first.
+        drop(world);
+        let events = self.game.borrow().events().clone();
+        for pos in update.loaded {
+            emit(&events, &Event::ChunkLoaded { pos });
+        }
+    }
+
+    // Grabs or releases the pointer for mouse look.
+    fn set_pointer_grabbed(&mut self, grabbed: bool) {
+        let Some(ref window) = self.window else {
+            return;
+        };
+
+        let result = if grabbed {
+            // Not every platform supports locking; confining is the next best thing.
+            window
+                .set_cursor_grab(CursorGrabMode::Locked)
+                .or_else(|_| window.set_cursor_grab(CursorGrabMode::Confined))
+        } else {
+            window.set_cursor_grab(CursorGrabMode::None)
+        };
+
+        match result {
+            Ok(()) => {
+                window.set_cursor_visible(!grabbed);
+                self.camera_controller.set_pointer_grabbed(grabbed);
+            }
+            Err(err) => log_warn(&format!("Failed to change pointer grab: {}", err)),
+        }
+    }
+
+    // Mines, places or picks the targeted block with the left, right or middle button.
+    fn use_target(&mut self, button: MouseButton) {
+        let Some(target) = self.target else {
+            return;
+        };
+        let edit = {
+            let world = self.world.borrow();
+            match button {
+                MouseButton::Left if raycast::can_mine(&world, &target) => {
+                    Some((target.block, AIR))
+                }
+                // Don't bury the player.
+                MouseButton::Right => raycast::placement(&world, &target)
+                    .filter(|pos| {
+                        self.camera_controller.mode() != CameraMode::Walk
+                            || !self.character.intersects_block(&world, *pos)
+                    })
+                    .map(|pos| (pos, self.held_block)),
+                MouseButton::Middle => {
+                    self.held_block = target.id;
+                    None
+                }
+                _ => None,
+            }
+        };
+        let Some((pos, new)) = edit else {
+            return;
+        };
+
+        // Scripts can refuse the edit.
+        let old = self.world.borrow().get_block(pos);
+        let events = self.game.borrow().events().clone();
+        if !emit(&events, &Event::BlockChanged { pos, old, new }) {
+            self.world.borrow_mut().set_block(pos, new);
+        }
+    }
+
+    fn handle_key(&mut self, key: KeyCode, state: ElementState) {
+        // Scripts see presses first and can keep them from the engine.
+        if state == ElementState::Pressed {
+            let events = self.game.borrow().events().clone();
+            let key = format!("{:?}", key);
+            if emit(&events, &Event::KeyPressed { key }) {
+                return;
+            }
+        }
+        if self.camera_controller.process_keyboard(key, state) || state != ElementState::Pressed {
+            return;
+        }
+
+        match key {
+            KeyCode::Escape => self.set_pointer_grabbed(false),
+            KeyCode::Tab => {
+                if let Some(ref renderer) = self.renderer {
+                    self.camera_controller.toggle_mode(renderer.camera());
+                }
+                if self.camera_controller.mode() == CameraMode::Orbit {
+                    self.set_pointer_grabbed(false);
+                }
+            }
+            // Drop a physics crate in front of the camera.
+            KeyCode::KeyG => {
+                let Some(ref renderer) = self.renderer else {
+                    return;
+                };
+                let camera = renderer.camera();
+                self.game.borrow_mut().spawn_dynamic_box(
+                    &mut self.physics,
+                    camera.position + camera.forward() * 3.0,
+                    Vector3::repeat(CRATE_HALF_EXTENT),
+                    CRATE_COLOR,
+                );
+            }
+            // Drop into the world where the camera is, or take off again.
+            KeyCode::KeyF => {
+                let Some(ref renderer) = self.renderer else {
+                    return;
+                };
+                let camera = renderer.camera();
+                let mode = if self.camera_controller.mode() == CameraMode::Walk {
+                    CameraMode::FreeFly
+                } else {
+                    let eye_height = self.character.settings.eye_height;
+                    self.character
+                        .teleport(camera.position - camera.up() * eye_height);
+                    CameraMode::Walk
+                };
+                self.camera_controller.set_mode(mode, camera);
+            }
+            // Pause, single-step and change the speed of the simulation.
+            KeyCode::KeyP => self.game.borrow().clock().borrow_mut().toggle_pause(),
+            KeyCode::Period => self.game.borrow().clock().borrow_mut().step(1),
+            KeyCode::BracketLeft | KeyCode::BracketRight => {
+                let game = self.game.borrow();
+                let mut clock = game.clock().borrow_mut();
+                let factor = if key == KeyCode::BracketLeft {
+                    0.5
+                } else {
+                    2.0
+                };
+                let time_scale = clock.time_scale() * factor;
+                clock.set_time_scale(time_scale);
+                log_info(&format!("Time scale {}x", clock.time_scale()));
+            }
+            _ => (),
+        }
+    }
 }
 
 impl ApplicationHandler for App {
@@ -43,37 +450,165 @@ impl ApplicationHandler for App {
             },
         )
         .expect("Failed to create instance");
-        self.instance = Some(instance.clone());
 
         // Create the surface.
-        let surface = Surface::from_window(instance, window).expect("Failed to create surface");
-        self.surface = Some(surface);
+        let window_size = window.inner_size();
+        let surface =
+            Surface::from_window(instance.clone(), window).expect("Failed to create surface");
+
+        // Create the renderer once; it is reused for every frame.
+        let mut renderer =
+            Renderer::new(instance, surface, [window_size.width, window_size.height]);
+        // Start above the spawn point, looking down at the terrain.
+        let camera = renderer.camera_mut();
+        camera.position = self.spawn;
+        camera.set_up(self.world.borrow().up_at(&self.spawn));
+        camera.pitch = -0.4;
+        // Draw out to the edge of the coarsest streamed level.
+        camera.far = self.streamer.settings().range() * 2.0;
+        self.renderer = Some(renderer);
     }
 
     fn window_event(&mut self, event_loop: &ActiveEventLoop, _id: WindowId, event: WindowEvent) {
         match event {
             WindowEvent::CloseRequested => {
                 println!("Closing");
+                self.save_world();
                 event_loop.exit();
             }
+            WindowEvent::Resized(size) => {
+                if let Some(ref mut renderer) = self.renderer {
+                    renderer.resize([size.width, size.height]);
+                }
+                // Redraws stop while minimized; start them again once there's something to draw.
+                if size.width > 0 && size.height > 0 {
+                    if let Some(ref window) = self.window {
+                        window.request_redraw();
+                    }
+                }
+            }
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        physical_key: PhysicalKey::Code(key),
+                        state,
+                        repeat: false,
+                        ..
+                    },
+                ..
+            } => self.handle_key(key, state),
+            WindowEvent::MouseInput { button, state, .. } => {
+                self.camera_controller.process_mouse_button(button, state);
+                // Clicking into the window starts mouse look; after that clicks edit blocks.
+                if state == ElementState::Pressed
+                    && self.camera_controller.mode() != CameraMode::Orbit
+                {
+                    if self.camera_controller.pointer_grabbed() {
+                        self.use_target(button);
+                    } else if button == MouseButton::Left {
+                        self.set_pointer_grabbed(true);
+                    }
+                }
+            }
+            WindowEvent::MouseWheel { delta, .. } => self.camera_controller.process_scroll(&delta),
+            WindowEvent::Focused(false) => {
+                // Keys released while unfocused are never reported.
+                self.camera_controller.release_all();
+                self.set_pointer_grabbed(false);
+            }
             WindowEvent::RedrawRequested => {
-                println!("Redrawing");
-                if let (Some(ref instance), Some(ref surface)) = (&self.instance, &self.surface) {
-                    lustre_render(instance.clone(), surface.clone());
+                let now = Instant::now();
+                let dt = (now - self.last_frame).as_secs_f32();
+                self.last_frame = now;
+
+                self.update_chunks();
+                self.physics.sync_terrain(&self.world.borrow());
+                // The camera keeps real time so it can fly around a paused world; the
+                // player and the game follow the clock.
+                let frame = self.game.borrow_mut().update(&mut self.physics, dt);
+                self.metadata.game_time += frame.delta as f64;
+                if self.last_save.elapsed() >= AUTOSAVE_INTERVAL {
+                    self.save_world();
                 }
-                // Optionally, request another redraw.
+                if let Some(ref mut renderer) = self.renderer {
+                    let world = self.world.borrow();
+                    let camera = renderer.camera_mut();
+                    self.camera_controller.update_camera(camera, dt);
+                    if self.camera_controller.mode() == CameraMode::Walk {
+                        let input = self.camera_controller.character_input(camera);
+                        self.character
+                            .update(&self.physics, &world, &input, frame.delta);
+                        camera.position = self.character.eye_position(&world);
+                        if let Some(transform) = self
+                            .game
+                            .borrow()
+                            .world()
+                            .write_storage::<Transform>()
+                            .get_mut(self.player)
+                        {
+                            transform.position = self.character.position();
+                        }
+                    }
+                    // Keep the camera upright as it moves around a planet.
+                    camera.set_up(world.up_at(&camera.position));
+                    // Target blocks along the view direction, except while orbiting.
+                    self.target = match self.camera_controller.mode() {
+                        CameraMode::Orbit => None,
+                        _ => raycast::raycast(&world, camera.position, camera.forward(), REACH),
+                    };
+                    renderer.set_highlight(self.target.map(|target| target.block));
+                    renderer.set_entity_mesh(&self.game.borrow().render_mesh());
+                    renderer.render();
+                }
+                // Keep drawing unless minimized, so a hidden window doesn't spin.
                 if let Some(ref window) = self.window {
-                    window.request_redraw();
+                    let size = window.inner_size();
+                    if size.width > 0 && size.height > 0 {
+                        window.request_redraw();
+                    }
                 }
             }
             _ => (),
         }
     }
+
+    // Raw motion is used for mouse look so it keeps working while the pointer is locked.
+    fn device_event(&mut self, _event_loop: &ActiveEventLoop, _id: DeviceId, event: DeviceEvent) {
+        if let DeviceEvent::MouseMotion { delta } = event {
+            self.camera_controller
+                .process_mouse_motion(delta.0, delta.1);
+        }
+    }
 }
 
 pub fn lustre_window() {
+    lustre_window_with(
+        Rc::new(RefCell::new(World::new(BlockRegistry::with_defaults()))),
+        Rc::new(RefCell::new(GameLoop::new())),
+        Rc::new(RefCell::new(GeneratorRegistry::new())),
+    );
+}
+
+/// Opens the render window playing `world` and `game`, so whoever shares them can script
+/// their blocks and entities and pause or step the game's clock. The terrain generator is
+/// looked up in `generators` by the name in the world settings.
+pub fn lustre_window_with(
+    world: Rc<RefCell<World>>,
+    game: Rc<RefCell<GameLoop>>,
+    generators: Rc<RefCell<GeneratorRegistry>>,
+) {
     let event_loop = EventLoop::new().unwrap();
     event_loop.set_control_flow(ControlFlow::Wait);
-    let mut app = App::default();
-    event_loop.run_app(&mut app);
+    let mut app = App::new(
+        CameraSettings::load(SETTINGS_PATH),
+        CharacterSettings::load(SETTINGS_PATH),
+        WorldSettings::load(SETTINGS_PATH),
+        StreamingSettings::load(SETTINGS_PATH),
+        world,
+        game,
+        &generators,
+    );
+    if let Err(err) = event_loop.run_app(&mut app) {
+        log_error(&format!("Render window event loop failed: {}", err));
+    }
 }
@@ -0,0 +1,587 @@
+// Copyright 2025 Nicholas Jordan. All Rights Reserved.
+// github.com/cvusmo/lustre
+// src/world.rs
+
+use crate::block::{Block, BlockId, BlockRegistry, AIR};
+use crate::chunk::{Chunk, CHUNK_SIZE};
+use crate::events::{self, Event};
+use crate::planet::{Gravity, Planet};
+use crate::settings;
+use crate::terrain::DEFAULT_GENERATOR;
+
+use mlua::prelude::*;
+use rapier3d::na::{Point3, Vector3};
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+// Most blocks one `world.fill` call from Lua may touch, so a typo can't stall the engine.
+const MAX_LUA_FILL_VOLUME: i64 = 1 << 21;
+
+/// Position of a chunk, in chunk units.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct ChunkPos {
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+}
+
+impl ChunkPos {
+    pub const fn new(x: i32, y: i32, z: i32) -> Self {
+        Self { x, y, z }
+    }
+
+    /// World position of the chunk's minimum corner block.
+    pub fn origin(&self) -> BlockPos {
+        let size = CHUNK_SIZE as i32;
+        BlockPos::new(self.x * size, self.y * size, self.z * size)
+    }
+
+    pub fn offset(&self, dx: i32, dy: i32, dz: i32) -> Self {
+        Self::new(self.x + dx, self.y + dy, self.z + dz)
+    }
+
+    /// The six face-adjacent chunks.
+    pub fn neighbours(&self) -> [ChunkPos; 6] {
+        [
+            self.offset(1, 0, 0),
+            self.offset(-1, 0, 0),
+            self.offset(0, 1, 0),
+            self.offset(0, -1, 0),
+            self.offset(0, 0, 1),
+            self.offset(0, 0, -1),
+        ]
+    }
+}
+
+/// Position of a block in world coordinates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct BlockPos {
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+}
+
+impl BlockPos {
+    pub const fn new(x: i32, y: i32, z: i32) -> Self {
+        Self { x, y, z }
+    }
+
+    /// The chunk containing this block.
+    pub fn chunk(&self) -> ChunkPos {
+        let size = CHUNK_SIZE as i32;
+        ChunkPos::new(
+            self.x.div_euclid(size),
+            self.y.div_euclid(size),
+            self.z.div_euclid(size),
+        )
+    }
+
+    /// Position of this block inside its chunk.
+    pub fn local(&self) -> (usize, usize, usize) {
+        let size = CHUNK_SIZE as i32;
+        (
+            self.x.rem_euclid(size) as usize,
+            self.y.rem_euclid(size) as usize,
+            self.z.rem_euclid(size) as usize,
+        )
+    }
+
+    pub fn offset(&self, dx: i32, dy: i32, dz: i32) -> Self {
+        Self::new(self.x + dx, self.y + dy, self.z + dz)
+    }
+}
+
+/// Coarsest level a [`LodPos`] can have.
+pub const MAX_LOD_LEVEL: u8 = 16;
+
+/// A cube of 2^`level` chunks per side, meshed as one coarse chunk for distant terrain.
+///
+/// Level 0 is a single chunk. Coordinates are in units of the cell's own size. Levels
+/// above [`MAX_LOD_LEVEL`] are a bug.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct LodPos {
+    pub level: u8,
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+}
+
+impl LodPos {
+    pub const fn new(level: u8, x: i32, y: i32, z: i32) -> Self {
+        assert!(level <= MAX_LOD_LEVEL);
+        Self { level, x, y, z }
+    }
+
+    /// The cell at `level` containing a chunk.
+    pub fn of_chunk(pos: ChunkPos, level: u8) -> Self {
+        Self::new(level, pos.x >> level, pos.y >> level, pos.z >> level)
+    }
+
+    /// Width of the cell in chunks.
+    pub fn size_in_chunks(&self) -> i32 {
+        1 << self.level
+    }
+
+    /// World blocks covered by each block of the cell's coarse chunk.
+    pub fn scale(&self) -> i32 {
+        self.size_in_chunks()
+    }
+
+    /// The chunk at the cell's minimum corner.
+    pub fn min_chunk(&self) -> ChunkPos {
+        let size = self.size_in_chunks();
+        ChunkPos::new(self.x * size, self.y * size, self.z * size)
+    }
+
+    /// World position of the cell's minimum corner block.
+    pub fn origin(&self) -> BlockPos {
+        self.min_chunk().origin()
+    }
+
+    /// The eight cells one level down. Level 0 cells return themselves.
+    pub fn children(&self) -> [LodPos; 8] {
+        if self.level == 0 {
+            return [*self; 8];
+        }
+        let level = self.level - 1;
+        let (x, y, z) = (self.x * 2, self.y * 2, self.z * 2);
+        std::array::from_fn(|i| {
+            let i = i as i32;
+            LodPos::new(level, x + (i & 1), y + ((i >> 1) & 1), z + ((i >> 2) & 1))
+        })
+    }
+}
+
+/// How a world is created, read from the `[world]` table of the config file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WorldSettings {
+    pub seed: u64,
+    /// Name of the terrain generator in the [`GeneratorRegistry`](crate::terrain::GeneratorRegistry), which mods can add to.
+    /// Unknown names fall back to the built-in generator.
+    pub generator: String,
+    /// When set, the world is a planet instead of a flat plane, generated by the planet
+    /// generator whatever `generator` says.
+    pub planet: Option<Planet>,
+    /// Directory the world is saved to. An existing save's seed, generator and planet take
+    /// precedence over the settings above. Unset to play without saving.
+    pub save_dir: Option<String>,
+}
+
+impl Default for WorldSettings {
+    fn default() -> Self {
+        Self {
+            seed: 1337,
+            generator: DEFAULT_GENERATOR.to_string(),
+            planet: None,
+            save_dir: Some("saves/world".to_string()),
+        }
+    }
+}
+
+impl WorldSettings {
+    /// Reads the `[world]` table of a config file.
+    pub fn load(path: &str) -> Self {
+        settings::load_section(path, "world")
+    }
+}
+
+/// A voxel world made of chunks keyed by chunk position.
+///
+/// Chunks that were never generated or set read as air. A world is flat with +Y up
+/// unless it has a planet, in which case up points away from the planet centre.
+pub struct World {
+    registry: BlockRegistry,
+    chunks: HashMap<ChunkPos, Chunk>,
+    // Chunks changed by `set_block` since they were last saved.
+    unsaved: HashSet<ChunkPos>,
+    // Bumped whenever a chunk is inserted or one of its blocks changes.
+    revisions: HashMap<ChunkPos, u64>,
+    next_revision: u64,
+    planet: Option<Planet>,
+}
+
+impl World {
+    pub fn new(registry: BlockRegistry) -> Self {
+        Self {
+            registry,
+            chunks: HashMap::new(),
+            unsaved: HashSet::new(),
+            revisions: HashMap::new(),
+            next_revision: 1,
+            planet: None,
+        }
+    }
+
+    pub fn planet(&self) -> Option<&Planet> {
+        self.planet.as_ref()
+    }
+
+    pub fn set_planet(&mut self, planet: Option<Planet>) {
+        self.planet = planet;
+    }
+
+    /// Gravity for cameras, bodies and characters in this world.
+    pub fn gravity(&self) -> Gravity {
+        self.planet
+            .as_ref()
+            .map_or_else(Gravity::default, Planet::gravity)
+    }
+
+    /// Unit vector pointing up at a position.
+    pub fn up_at(&self, position: &Point3<f32>) -> Vector3<f32> {
+        self.gravity().up(position)
+    }
+
+    /// Axis-aligned up for building at `pos`: the normal of its cube face on a planet, +Y
+    /// on a flat world.
+    pub fn block_up(&self, pos: BlockPos) -> [i32; 3] {
+        self.planet
+            .as_ref()
+            .map_or([0, 1, 0], |planet| planet.block_up(pos))
+    }
+
+    pub fn registry(&self) -> &BlockRegistry {
+        &self.registry
+    }
+
+    pub fn registry_mut(&mut self) -> &mut BlockRegistry {
+        &mut self.registry
+    }
+
+    pub fn get_block(&self, pos: BlockPos) -> BlockId {
+        let (x, y, z) = pos.local();
+        self.chunks
+            .get(&pos.chunk())
+            .map_or(AIR, |chunk| chunk.get(x, y, z))
+    }
+
+    /// Sets a block, creating its chunk if needed, and returns the block it replaced.
+    ///
+    /// Neighbouring chunks are marked dirty when the block lies on a chunk border,
+    /// since their visible faces may change too.
+    pub fn set_block(&mut self, pos: BlockPos, block: BlockId) -> BlockId {
+        let chunk_pos = pos.chunk();
+        let (x, y, z) = pos.local();
+
+        let old = match self.chunks.get_mut(&chunk_pos) {
+            Some(chunk) => chunk.set(x, y, z, block),
+            // Writing air into a missing chunk changes nothing.
+            None if block == AIR => return AIR,
+            None => {
+                let mut chunk = Chunk::new();
+                chunk.set(x, y, z, block);
+                self.chunks.insert(chunk_pos, chunk);
+                AIR
+            }
+        };
+
+        if old != block {
+            self.unsaved.insert(chunk_pos);
+            self.bump_revision(chunk_pos);
+            self.mark_border_neighbours_dirty(chunk_pos, (x, y, z));
+        }
+        old
+    }
+
+    /// Sets every block in the box between `min` and `max`, inclusive, and returns how many
+    /// changed. Chunks are only marked dirty, so each is remeshed once however many of its
+    /// blocks changed.
+    pub fn fill(&mut self, min: BlockPos, max: BlockPos, block: BlockId) -> usize {
+        box_positions(min, max)
+            .filter(|&pos| self.set_block(pos, block) != block)
+            .count()
+    }
+
+    /// Like `fill`, but only sets blocks `keep` accepts. `keep` gets each position whose
+    /// block would change and the block there now, and runs while `world` isn't borrowed,
+    /// so it may read or edit the world itself.
+    pub fn fill_where(
+        world: &RefCell<World>,
+        min: BlockPos,
+        max: BlockPos,
+        block: BlockId,
+        mut keep: impl FnMut(BlockPos, BlockId) -> bool,
+    ) -> usize {
+        box_positions(min, max)
+            .filter(|&pos| {
+                let old = world.borrow().get_block(pos);
+                if old == block || !keep(pos, old) {
+                    return false;
+                }
+                world.borrow_mut().set_block(pos, block);
+                true
+            })
+            .count()
+    }
+
+    fn mark_border_neighbours_dirty(&mut self, chunk_pos: ChunkPos, local: (usize, usize, usize)) {
+        let last = CHUNK_SIZE - 1;
+        let (x, y, z) = local;
+        let borders = [
+            (x == 0, chunk_pos.offset(-1, 0, 0)),
+            (x == last, chunk_pos.offset(1, 0, 0)),
+            (y == 0, chunk_pos.offset(0, -1, 0)),
+            (y == last, chunk_pos.offset(0, 1, 0)),
+            (z == 0, chunk_pos.offset(0, 0, -1)),
+            (z == last, chunk_pos.offset(0, 0, 1)),
+        ];
+        for (on_border, neighbour) in borders {
+            if on_border {
+                if let Some(chunk) = self.chunks.get_mut(&neighbour) {
+                    chunk.mark_dirty();
+                }
+            }
+        }
+    }
+
+    pub fn chunk(&self, pos: ChunkPos) -> Option<&Chunk> {
+        self.chunks.get(&pos)
+    }
+
+    pub fn chunk_mut(&mut self, pos: ChunkPos) -> Option<&mut Chunk> {
+        self.chunks.get_mut(&pos)
+    }
+
+    /// Inserts a chunk, replacing and returning any previous one. Neighbours are marked dirty.
+    pub fn insert_chunk(&mut self, pos: ChunkPos, mut chunk: Chunk) -> Option<Chunk> {
+        chunk.mark_dirty();
+        for neighbour in pos.neighbours() {
+            if let Some(chunk) = self.chunks.get_mut(&neighbour) {
+                chunk.mark_dirty();
+            }
+        }
+        self.bump_revision(pos);
+        self.chunks.insert(pos, chunk)
+    }
+
+    /// Removes a chunk. Check `is_unsaved` first if its changes should be kept.
+    pub fn remove_chunk(&mut self, pos: ChunkPos) -> Option<Chunk> {
+        self.unsaved.remove(&pos);
+        self.revisions.remove(&pos);
+        self.chunks.remove(&pos)
+    }
+
+    fn bump_revision(&mut self, pos: ChunkPos) {
+        self.revisions.insert(pos, self.next_revision);
+        self.next_revision += 1;
+    }
+
+    /// Changes whenever the chunk at `pos` is inserted or edited through `set_block`;
+    /// 0 if it is not loaded. Lets systems such as physics notice changes without
+    /// touching the dirty flag the mesher owns.
+    pub fn revision(&self, pos: ChunkPos) -> u64 {
+        self.revisions.get(&pos).copied().unwrap_or(0)
+    }
+
+    pub fn is_loaded(&self, pos: ChunkPos) -> bool {
+        self.chunks.contains_key(&pos)
+    }
+
+    pub fn chunks(&self) -> impl Iterator<Item = (&ChunkPos, &Chunk)> {
+        self.chunks.iter()
+    }
+
+    pub fn chunk_count(&self) -> usize {
+        self.chunks.len()
+    }
+
+    /// Positions of chunks changed since their dirty flag was last cleared.
+    pub fn dirty_chunks(&self) -> Vec<ChunkPos> {
+        self.chunks
+            .iter()
+            .filter(|(_, chunk)| chunk.is_dirty())
+            .map(|(pos, _)| *pos)
+            .collect()
+    }
+
+    pub fn clear_dirty(&mut self, pos: ChunkPos) {
+        if let Some(chunk) = self.chunks.get_mut(&pos) {
+            chunk.clear_dirty();
+        }
+    }
+
+    /// Whether a chunk was edited since it was generated, loaded or last saved.
+    ///
+    /// Tracked separately from the dirty flag, which the mesher clears.
+    pub fn is_unsaved(&self, pos: ChunkPos) -> bool {
+        self.unsaved.contains(&pos)
+    }
+
+    /// Positions of chunks edited since they were last saved.
+    pub fn unsaved_chunks(&self) -> Vec<ChunkPos> {
+        self.unsaved.iter().copied().collect()
+    }
+
+    pub fn mark_saved(&mut self, pos: ChunkPos) {
+        self.unsaved.remove(&pos);
+    }
+}
+
+// Reads `{ x = ..., y = ..., z = ... }` as a block position.
+fn block_pos_from_table(table: &LuaTable) -> LuaResult<BlockPos> {
+    Ok(BlockPos::new(
+        table.get("x")?,
+        table.get("y")?,
+        table.get("z")?,
+    ))
+}
+
+// Accepts a block id or a block name such as `lustre:stone`.
+fn block_from_lua(registry: &BlockRegistry, block: LuaValue) -> LuaResult<BlockId> {
+    match block {
+        LuaValue::Integer(id) => BlockId::try_from(id)
+            .ok()
+            .filter(|&id| registry.get(id).is_some())
+            .ok_or_else(|| LuaError::runtime(format!("Unknown block id {}", id))),
+        LuaValue::String(name) => {
+            let name = name.to_str()?;
+            registry
+                .id_of(&name)
+                .ok_or_else(|| LuaError::runtime(format!("Unknown block '{}'", &*name)))
+        }
+        _ => Err(LuaError::runtime(format!(
+            "Expected a block id or name, got {}",
+            block.type_name()
+        ))),
+    }
+}
+
+fn block_to_table(lua: &Lua, block: &Block) -> LuaResult<LuaTable> {
+    let table = lua.create_table()?;
+    table.set("id", block.id)?;
+    table.set("name", block.name.as_str())?;
+    table.set("solid", block.properties.solid)?;
+    table.set("transparent", block.properties.transparent)?;
+    table.set("fluid", block.properties.fluid)?;
+    table.set("hardness", block.properties.hardness)?;
+    table.set("color", block.properties.color)?;
+    Ok(table)
+}
+
+// Every position in the box between two corners, inclusive.
+fn box_positions(a: BlockPos, b: BlockPos) -> impl Iterator<Item = BlockPos> {
+    let (min, max) = (
+        BlockPos::new(a.x.min(b.x), a.y.min(b.y), a.z.min(b.z)),
+        BlockPos::new(a.x.max(b.x), a.y.max(b.y), a.z.max(b.z)),
+    );
+    (min.x..=max.x).flat_map(move |x| {
+        (min.y..=max.y).flat_map(move |y| (min.z..=max.z).map(move |z| BlockPos::new(x, y, z)))
+    })
+}
+
+// Emits a block change to the bus registered with `lua`, if any. Returns whether a handler
+// cancelled it.
+fn block_change_cancelled(lua: &Lua, pos: BlockPos, old: BlockId, new: BlockId) -> bool {
+    events::shared_bus(lua)
+        .is_some_and(|bus| events::emit(&bus, &Event::BlockChanged { pos, old, new }))
+}
+
+/// Adds block reads, edits and registry lookups to the `world` table in Lua, creating the
+/// table if needed.
+///
+/// `world.get_block(x, y, z)` returns a block id and `world.set_block(x, y, z, block)` the
+/// id it replaced. `world.fill({min = {x, y, z}, max = {x, y, z}}, block)` sets a box,
+/// inclusive, and returns how many blocks changed. Blocks are ids or names such as
+/// `"lustre:stone"`. `world.block(block)` describes one registered block and
+/// `world.blocks()` lists them all.
+///
+/// Edits emit a cancellable `block_changed` event for each block they would change; a
+/// cancelled `set_block` returns the block that stayed. Edits only mark chunks dirty, so
+/// however many a script makes, each changed chunk is remeshed once on the next frame.
+pub fn register_lua_api(lua: &Lua, world: Rc<RefCell<World>>) -> LuaResult<()> {
+    let table = match lua.globals().get::<Option<LuaTable>>("world")? {
+        Some(table) => table,
+        None => lua.create_table()?,
+    };
+
+    let shared = world.clone();
+    table.set(
+        "get_block",
+        lua.create_function(move |_, (x, y, z): (i32, i32, i32)| {
+            Ok(shared.borrow().get_block(BlockPos::new(x, y, z)))
+        })?,
+    )?;
+
+    let shared = world.clone();
+    table.set(
+        "set_block",
+        lua.create_function(move |lua, (x, y, z, block): (i32, i32, i32, LuaValue)| {
+            let pos = BlockPos::new(x, y, z);
+            let (old, block) = {
+                let world = shared.borrow();
+                (
+                    world.get_block(pos),
+                    block_from_lua(world.registry(), block)?,
+                )
+            };
+            if old != block && block_change_cancelled(lua, pos, old, block) {
+                return Ok(old);
+            }
+            Ok(shared.borrow_mut().set_block(pos, block))
+        })?,
+    )?;
+
+    let shared = world.clone();
+    table.set(
+        "fill",
+        lua.create_function(move |lua, (region, block): (LuaTable, LuaValue)| {
+            let min = block_pos_from_table(&region.get("min")?)?;
+            let max = block_pos_from_table(&region.get("max")?)?;
+            // Sides of a box spanning all of i32 overflow i32, and its volume overflows i64.
+            let volume = [(min.x, max.x), (min.y, max.y), (min.z, max.z)]
+                .iter()
+                .map(|&(a, b)| (b as i64 - a as i64).abs() + 1)
+                .fold(1i64, i64::saturating_mul);
+            if volume > MAX_LUA_FILL_VOLUME {
+                return Err(LuaError::runtime(format!(
+                    "Fill of {} blocks is larger than the limit of {}",
+                    volume, MAX_LUA_FILL_VOLUME
+                )));
+            }
+            let block = block_from_lua(shared.borrow().registry(), block)?;
+            let watched = events::shared_bus(lua)
+                .is_some_and(|bus| bus.borrow().has_subscribers("block_changed"));
+            if !watched {
+                return Ok(shared.borrow_mut().fill(min, max, block));
+            }
+            // Handlers see each block that would change and may keep it.
+            Ok(World::fill_where(&shared, min, max, block, |pos, old| {
+                !block_change_cancelled(lua, pos, old, block)
+            }))
+        })?,
+    )?;
+
+    let shared = world.clone();
+    table.set(
+        "block",
+        lua.create_function(move |lua, block: LuaValue| {
+            let world = shared.borrow();
+            let Ok(id) = block_from_lua(world.registry(), block) else {
+                return Ok(None);
+            };
+            world
+                .registry()
+                .get(id)
+                .map(|block| block_to_table(lua, block))
+                .transpose()
+        })?,
+    )?;
+
+    table.set(
+        "blocks",
+        lua.create_function(move |lua, ()| {
+            let world = world.borrow();
+            world
+                .registry()
+                .iter()
+                .map(|block| block_to_table(lua, block))
+                .collect::<LuaResult<Vec<_>>>()
+        })?,
+    )?;
+
+    lua.globals().set("world", table)?;
+    Ok(())
+}
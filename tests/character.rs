@@ -0,0 +1,188 @@
+// Copyright 2025 Nicholas Jordan. All Rights Reserved.
+// github.com/cvusmo/lustre
+// tests/character.rs
+
+use lustre::block::BlockRegistry;
+use lustre::character::{CharacterController, CharacterInput, CharacterSettings};
+use lustre::physics::PhysicsWorld;
+use lustre::planet::Planet;
+use lustre::world::{BlockPos, World};
+use rapier3d::na::{Point3, Vector3};
+
+const DT: f32 = 1.0 / 60.0;
+
+// A world with `block` filling the given inclusive ranges.
+fn fill(world: &mut World, block: &str, min: (i32, i32, i32), max: (i32, i32, i32)) {
+    let id = world.registry().id_of(block).unwrap();
+    for x in min.0..=max.0 {
+        for y in min.1..=max.1 {
+            for z in min.2..=max.2 {
+                world.set_block(BlockPos::new(x, y, z), id);
+            }
+        }
+    }
+}
+
+// A stone floor whose top is at y = 1, spanning x and z in 0..16.
+fn floor() -> World {
+    let mut world = World::new(BlockRegistry::with_defaults());
+    fill(&mut world, "lustre:stone", (0, 0, 0), (15, 0, 15));
+    world
+}
+
+fn run(
+    character: &mut CharacterController,
+    world: &World,
+    input: CharacterInput,
+    frames: usize,
+) -> PhysicsWorld {
+    let mut physics = PhysicsWorld::new(world.gravity());
+    physics.sync_terrain(world);
+    for _ in 0..frames {
+        character.update(&physics, world, &input, DT);
+    }
+    physics
+}
+
+fn walk(direction: Vector3<f32>) -> CharacterInput {
+    CharacterInput {
+        direction,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn falls_and_stands_on_the_ground() {
+    let world = floor();
+    let mut character =
+        CharacterController::new(CharacterSettings::default(), Point3::new(4.0, 4.0, 4.0));
+    run(&mut character, &world, CharacterInput::default(), 90);
+    assert!(character.is_grounded());
+    assert!(
+        (character.position().y - 1.0).abs() < 0.1,
+        "{:?}",
+        character.position()
+    );
+}
+
+#[test]
+fn jumps_to_the_configured_height() {
+    let world = floor();
+    let settings = CharacterSettings::default();
+    let jump_height = settings.jump_height;
+    let mut character = CharacterController::new(settings, Point3::new(4.0, 1.5, 4.0));
+    let physics = run(&mut character, &world, CharacterInput::default(), 30);
+    let ground = character.position().y;
+
+    let jump = CharacterInput {
+        jump: true,
+        ..Default::default()
+    };
+    character.update(&physics, &world, &jump, DT);
+    let mut apex = ground;
+    for _ in 0..90 {
+        character.update(&physics, &world, &CharacterInput::default(), DT);
+        apex = apex.max(character.position().y);
+    }
+    assert!(
+        (apex - ground - jump_height).abs() < 0.15,
+        "apex {}",
+        apex - ground
+    );
+    assert!(character.is_grounded());
+}
+
+#[test]
+fn steps_onto_single_blocks_but_not_walls() {
+    let mut world = floor();
+    fill(&mut world, "lustre:stone", (8, 1, 0), (15, 1, 15));
+    // A two-block wall along z = 12.
+    fill(&mut world, "lustre:stone", (0, 1, 12), (7, 2, 12));
+
+    let mut character =
+        CharacterController::new(CharacterSettings::default(), Point3::new(4.0, 1.5, 4.0));
+    run(&mut character, &world, walk(Vector3::x()), 90);
+    assert!(character.position().x > 9.0, "{:?}", character.position());
+    assert!(
+        (character.position().y - 2.0).abs() < 0.1,
+        "{:?}",
+        character.position()
+    );
+
+    let mut character =
+        CharacterController::new(CharacterSettings::default(), Point3::new(4.0, 1.5, 8.0));
+    run(&mut character, &world, walk(Vector3::z()), 90);
+    assert!(character.position().z < 12.0);
+    assert!(character.position().y < 1.5);
+}
+
+#[test]
+fn crouching_stops_at_edges() {
+    let world = floor();
+    let start = Point3::new(12.0, 1.5, 4.0);
+
+    let mut crouching = CharacterController::new(CharacterSettings::default(), start);
+    let input = CharacterInput {
+        direction: Vector3::x(),
+        crouch: true,
+        ..Default::default()
+    };
+    run(&mut crouching, &world, input, 240);
+    assert!(crouching.is_grounded());
+    assert!(crouching.position().x < 16.5, "{:?}", crouching.position());
+
+    let mut walking = CharacterController::new(CharacterSettings::default(), start);
+    run(&mut walking, &world, walk(Vector3::x()), 240);
+    assert!(walking.position().y < 0.0);
+}
+
+#[test]
+fn swims_in_fluids() {
+    let mut world = floor();
+    fill(&mut world, "lustre:water", (0, 1, 0), (15, 8, 15));
+    let start = Point3::new(4.0, 5.0, 4.0);
+
+    // Sinking is slower than falling.
+    let mut character = CharacterController::new(CharacterSettings::default(), start);
+    run(&mut character, &world, CharacterInput::default(), 30);
+    assert!(character.is_swimming());
+    assert!(character.position().y > 4.0);
+
+    let mut character = CharacterController::new(CharacterSettings::default(), start);
+    let up = CharacterInput {
+        jump: true,
+        ..Default::default()
+    };
+    run(&mut character, &world, up, 30);
+    assert!(character.position().y > start.y + 0.5);
+}
+
+#[test]
+fn walks_on_any_side_of_a_planet() {
+    let planet = Planet::default();
+    let mut world = World::new(BlockRegistry::with_defaults());
+    world.set_planet(Some(planet.clone()));
+    // A platform on the -X side, facing away from the centre.
+    let surface = -(planet.radius as i32);
+    fill(
+        &mut world,
+        "lustre:stone",
+        (surface, -8, -8),
+        (surface, 8, 8),
+    );
+
+    let feet = Point3::new(surface as f32 - 3.0, 0.5, 0.5);
+    let mut character = CharacterController::new(CharacterSettings::default(), feet);
+    run(&mut character, &world, walk(Vector3::y()), 90);
+
+    assert!(character.is_grounded());
+    // Standing on top of the platform's outer face, having walked along it.
+    assert!(
+        (character.position().x - surface as f32).abs() < 0.1,
+        "{:?}",
+        character.position()
+    );
+    assert!(character.position().y > 3.0);
+    let eye = character.eye_position(&world);
+    assert!(eye.x < character.position().x - 1.0);
+}
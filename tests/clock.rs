@@ -0,0 +1,83 @@
+// Copyright 2025 Nicholas Jordan. All Rights Reserved.
+// github.com/cvusmo/lustre
+// tests/clock.rs
+
+use lustre::clock::{register_lua_api, Clock};
+use mlua::Lua;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+const STEP: f32 = 0.25;
+
+#[test]
+fn frames_turn_into_fixed_steps() {
+    let mut clock = Clock::new(STEP);
+    let frame = clock.advance(0.6);
+    assert_eq!(frame.steps, 2);
+    assert!((frame.delta - 0.6).abs() < 1e-6);
+    assert!((frame.alpha - 0.4).abs() < 1e-5);
+
+    // Leftover time carries into the next frame.
+    assert_eq!(clock.advance(0.15).steps, 1);
+    assert_eq!(clock.steps(), 3);
+    assert!((clock.time() - 0.75).abs() < 1e-6);
+
+    // Long stalls are capped instead of simulated in full.
+    assert!(clock.advance(100.0).steps < 10);
+}
+
+#[test]
+fn paused_clocks_only_run_requested_steps() {
+    let mut clock = Clock::new(STEP);
+    clock.pause();
+    let frame = clock.advance(1.0);
+    assert_eq!(frame.steps, 0);
+    assert_eq!(frame.delta, 0.0);
+    assert_eq!(frame.real_delta, 1.0);
+
+    clock.step(3);
+    let frame = clock.advance(0.0);
+    assert_eq!(frame.steps, 3);
+    assert!((frame.delta - 3.0 * STEP).abs() < 1e-6);
+    assert_eq!(clock.advance(1.0).steps, 0);
+    assert!(clock.is_paused());
+
+    clock.toggle_pause();
+    assert_eq!(clock.advance(0.5).steps, 2);
+}
+
+#[test]
+fn time_scale_changes_how_many_steps_run() {
+    let mut clock = Clock::new(STEP);
+    clock.set_time_scale(2.0);
+    assert_eq!(clock.advance(0.5).steps, 4);
+    clock.set_time_scale(0.5);
+    assert_eq!(clock.advance(0.5).steps, 1);
+
+    // Nonsense scales are kept in range.
+    clock.set_time_scale(-1.0);
+    assert!(clock.time_scale() > 0.0);
+    clock.set_time_scale(f32::NAN);
+    assert_eq!(clock.time_scale(), 1.0);
+}
+
+#[test]
+fn lua_scripts_control_the_clock() {
+    let clock = Rc::new(RefCell::new(Clock::new(STEP)));
+    let lua = Lua::new();
+    register_lua_api(&lua, clock.clone()).unwrap();
+
+    lua.load("clock.set_time_scale(0.5); clock.step(2)")
+        .exec()
+        .unwrap();
+    assert!(clock.borrow().is_paused());
+    assert_eq!(clock.borrow().time_scale(), 0.5);
+    assert_eq!(clock.borrow_mut().advance(0.0).steps, 2);
+
+    let (paused, time): (bool, f64) = lua
+        .load("clock.resume(); return clock.is_paused(), clock.time()")
+        .eval()
+        .unwrap();
+    assert!(!paused);
+    assert!((time - 0.5).abs() < 1e-6);
+}
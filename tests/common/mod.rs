@@ -0,0 +1,64 @@
+// Copyright 2025 Nicholas Jordan. All Rights Reserved.
+// github.com/cvusmo/lustre
+// tests/common/mod.rs
+
+// Each test crate uses only some of these helpers.
+#![allow(dead_code)]
+
+use lustre::headless::HeadlessContext;
+use std::fs;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+
+// Set to 1 to skip GPU tests on machines without any Vulkan driver.
+const SKIP_GPU_ENV: &str = "LUSTRE_SKIP_GPU_TESTS";
+
+/// Returns a headless context. Without a Vulkan device the test fails, unless
+/// `LUSTRE_SKIP_GPU_TESTS=1` is set, in which case this returns None and the test is
+/// skipped.
+pub fn context() -> Option<HeadlessContext> {
+    match HeadlessContext::new() {
+        Ok(context) => Some(context),
+        Err(err) if std::env::var(SKIP_GPU_ENV).as_deref() == Ok("1") => {
+            eprintln!("skipping GPU test, no Vulkan device: {}", err);
+            None
+        }
+        Err(err) => panic!(
+            "no Vulkan device for GPU tests: {}; set {}=1 to skip them",
+            err, SKIP_GPU_ENV
+        ),
+    }
+}
+
+/// An empty directory under the system temp dir, removed again when dropped.
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    /// Creates the directory; `name` must be unique within the test crate.
+    pub fn new(name: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!("lustre-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        Self(dir)
+    }
+}
+
+impl Deref for TempDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl AsRef<Path> for TempDir {
+    fn as_ref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
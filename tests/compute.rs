@@ -0,0 +1,65 @@
+// Copyright 2025 Nicholas Jordan. All Rights Reserved.
+// github.com/cvusmo/lustre
+// tests/compute.rs
+
+mod common;
+
+use common::context;
+use lustre::compute::{dispatch_to_image, workgroup_count, LOCAL_SIZE_2D};
+use lustre::golden::{check_golden, Tolerance};
+use lustre::shaders::{compute_border, compute_mandelbrot, image};
+
+const SIZE: u32 = 128;
+
+// Escape-time fractals diverge by an iteration or two near the set boundary
+// depending on the driver's float precision.
+const FRACTAL_TOLERANCE: Tolerance = Tolerance {
+    max_channel_delta: 4,
+    max_mismatched_ratio: 0.02,
+};
+
+#[test]
+fn border_matches_reference() {
+    let Some(context) = context() else { return };
+    let shader = compute_border::load(context.device.clone()).unwrap();
+    // Safety: the bundled shaders skip invocations outside their image.
+    let output =
+        unsafe { dispatch_to_image(&context, shader, SIZE, SIZE) }.expect("dispatch failed");
+    let exact = Tolerance {
+        max_channel_delta: 0,
+        max_mismatched_ratio: 0.0,
+    };
+    if let Err(err) = check_golden("border", &output, &exact) {
+        panic!("{}", err);
+    }
+}
+
+#[test]
+fn mandelbrot_matches_reference() {
+    let Some(context) = context() else { return };
+    let shader = compute_mandelbrot::load(context.device.clone()).unwrap();
+    // Safety: the bundled shaders skip invocations outside their image.
+    let output =
+        unsafe { dispatch_to_image(&context, shader, SIZE, SIZE) }.expect("dispatch failed");
+    if let Err(err) = check_golden("mandelbrot", &output, &FRACTAL_TOLERANCE) {
+        panic!("{}", err);
+    }
+}
+
+#[test]
+fn image_shader_matches_reference() {
+    let Some(context) = context() else { return };
+    let shader = image::load(context.device.clone()).unwrap();
+    // Safety: the bundled shaders skip invocations outside their image.
+    let output =
+        unsafe { dispatch_to_image(&context, shader, SIZE, SIZE) }.expect("dispatch failed");
+    if let Err(err) = check_golden("image", &output, &FRACTAL_TOLERANCE) {
+        panic!("{}", err);
+    }
+}
+
+#[test]
+fn workgroup_count_covers_partial_groups() {
+    assert_eq!(workgroup_count(128, 128, LOCAL_SIZE_2D), [16, 16, 1]);
+    assert_eq!(workgroup_count(130, 1, LOCAL_SIZE_2D), [17, 1, 1]);
+}
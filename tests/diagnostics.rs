@@ -0,0 +1,84 @@
+// Copyright 2025 Nicholas Jordan. All Rights Reserved.
+// github.com/cvusmo/lustre
+// tests/diagnostics.rs
+
+use lustre::diagnostics::{chunk_name, collect_reports, run_chunk, take_reports, Diagnostic};
+use lustre::events::{emit, register_lua_api, Event, EventBus};
+use mlua::{Lua, Result as LuaResult};
+use std::cell::RefCell;
+use std::path::Path;
+use std::rc::Rc;
+
+#[test]
+fn errors_point_at_the_script_line() {
+    let lua = Lua::new();
+    let name = chunk_name(Path::new("scripts/door.lua"));
+
+    let script = "local door = {}\n\nfunction door.open()\n  error('stuck')\nend\n\ndoor.open()\n";
+    let diagnostic = run_chunk(&lua, script, &name).unwrap_err();
+    assert_eq!(diagnostic.chunk, "scripts/door.lua");
+    assert_eq!(diagnostic.line, Some(4));
+    assert_eq!(diagnostic.message, "stuck");
+    let traceback = diagnostic.traceback.unwrap();
+    assert!(traceback.contains("scripts/door.lua:4:"), "{}", traceback);
+    assert!(traceback.contains("scripts/door.lua:7:"), "{}", traceback);
+
+    let diagnostic = run_chunk(&lua, "local x =\n\nif", &name).unwrap_err();
+    assert_eq!(diagnostic.line, Some(3));
+    assert!(diagnostic.is_in(&name));
+    assert!(diagnostic.traceback.is_none());
+
+    assert!(run_chunk(&lua, "x = 1", &name).is_ok());
+}
+
+#[test]
+fn rust_errors_use_the_calling_line() {
+    let lua = Lua::new();
+    let fail = lua
+        .create_function(|_, ()| -> LuaResult<()> { Err(mlua::Error::runtime("no world loaded")) })
+        .unwrap();
+    lua.globals().set("fail", fail).unwrap();
+
+    let long = format!("@{}/main.lua", "deeply/nested".repeat(10));
+    let diagnostic = run_chunk(&lua, "local a = 1\nfail()\n", &long).unwrap_err();
+    assert_eq!(diagnostic.line, Some(2));
+    assert!(diagnostic.message.contains("no world loaded"));
+    assert!(diagnostic.chunk.starts_with("..."));
+    assert!(diagnostic.is_in(&long));
+    assert!(!diagnostic.is_in("@other.lua"));
+
+    assert_eq!(
+        Diagnostic::from_message("a:b.lua:12: bad").to_string(),
+        "a:b.lua:12: bad"
+    );
+}
+
+#[test]
+fn handler_errors_are_reported_once() {
+    let lua = Lua::new();
+    let bus = Rc::new(RefCell::new(EventBus::new()));
+    register_lua_api(&lua, bus.clone()).unwrap();
+    lua.load("events.on('tick', function() error('broken handler') end)")
+        .set_name("=handlers")
+        .exec()
+        .unwrap();
+
+    // Without a collector errors are only logged.
+    let tick = Event::Tick {
+        tick: 1,
+        delta: 0.1,
+    };
+    emit(&bus, &tick);
+    assert!(take_reports(&lua).is_empty());
+
+    collect_reports(&lua);
+    emit(&bus, &tick);
+    emit(&bus, &tick);
+    let reports = take_reports(&lua);
+    assert_eq!(reports.len(), 1);
+    assert_eq!(reports[0].chunk, "handlers");
+    assert_eq!(reports[0].line, Some(1));
+    assert_eq!(reports[0].message, "broken handler");
+    assert!(reports[0].traceback.is_some());
+    assert!(take_reports(&lua).is_empty());
+}
@@ -0,0 +1,127 @@
+// Copyright 2025 Nicholas Jordan. All Rights Reserved.
+// github.com/cvusmo/lustre
+// tests/events.rs
+
+use lustre::block::BlockRegistry;
+use lustre::events::{emit, register_lua_api, Event, EventBus, Propagation};
+use lustre::world::{self, BlockPos, World};
+use mlua::{Lua, Value};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+fn custom(name: &str) -> Event {
+    Event::Custom {
+        name: name.to_string(),
+        data: Value::Nil,
+    }
+}
+
+#[test]
+fn handlers_run_by_priority_and_can_cancel() {
+    let bus = Rc::new(RefCell::new(EventBus::new()));
+    let lua = Lua::new();
+    register_lua_api(&lua, bus.clone()).unwrap();
+
+    let seen = Rc::new(RefCell::new(Vec::new()));
+    let log = seen.clone();
+    bus.borrow_mut().subscribe("door_opened", 10, move |_| {
+        log.borrow_mut().push("rust");
+        Propagation::Continue
+    });
+    lua.load(
+        r#"
+        order = {}
+        events.on("door_opened", function(event) table.insert(order, "late") end, -5)
+        events.on("door_opened", function(event) table.insert(order, "first") end)
+        events.on("door_opened", function(event)
+            table.insert(order, "second")
+            if event.data == "locked" then event:cancel() end
+        end)
+        events.on("tick", function(event) event:cancel() end)
+        "#,
+    )
+    .exec()
+    .unwrap();
+
+    assert!(!emit(&bus, &custom("door_opened")));
+    let order: Vec<String> = lua.globals().get("order").unwrap();
+    assert_eq!(order, ["first", "second", "late"]);
+    assert_eq!(*seen.borrow(), ["rust"]);
+
+    // Cancelling stops the handlers after it and is reported to the emitter.
+    let cancelled: bool = lua
+        .load("order = {} return events.emit('door_opened', 'locked')")
+        .eval()
+        .unwrap();
+    assert!(cancelled);
+    let order: Vec<String> = lua.globals().get("order").unwrap();
+    assert_eq!(order, ["first", "second"]);
+
+    // Ticks can't be cancelled, and scripts can't fake engine events.
+    assert!(!emit(
+        &bus,
+        &Event::Tick {
+            tick: 1,
+            delta: 0.1
+        }
+    ));
+    assert!(lua.load("events.emit('tick')").exec().is_err());
+}
+
+#[test]
+fn scripts_can_refuse_block_changes() {
+    let registry = BlockRegistry::with_defaults();
+    let stone = registry.id_of("lustre:stone").unwrap();
+    let shared = Rc::new(RefCell::new(World::new(registry)));
+    let lua = Lua::new();
+    register_lua_api(&lua, Rc::new(RefCell::new(EventBus::new()))).unwrap();
+    world::register_lua_api(&lua, shared.clone()).unwrap();
+
+    lua.load(
+        r#"
+        changes = 0
+        events.on("block_changed", function(event)
+            changes = changes + 1
+            if event.pos.y < 0 then event:cancel() end
+        end)
+        world.set_block(0, 5, 0, "lustre:stone")
+        world.set_block(0, -1, 0, "lustre:stone")
+        filled = world.fill({min = {x = 1, y = -1, z = 0}, max = {x = 1, y = 1, z = 0}}, "lustre:stone")
+        "#,
+    )
+    .exec()
+    .unwrap();
+
+    let world = shared.borrow();
+    assert_eq!(world.get_block(BlockPos::new(0, 5, 0)), stone);
+    assert_ne!(world.get_block(BlockPos::new(0, -1, 0)), stone);
+    assert_eq!(lua.globals().get::<u32>("filled").unwrap(), 2);
+    assert_eq!(lua.globals().get::<u32>("changes").unwrap(), 5);
+}
+
+#[test]
+fn handlers_that_emit_their_own_event_run_once() {
+    let bus = Rc::new(RefCell::new(EventBus::new()));
+    let lua = Lua::new();
+    register_lua_api(&lua, bus.clone()).unwrap();
+
+    lua.load(
+        r#"
+        echoes, pings, pongs = 0, 0, 0
+        events.on("echo", function() echoes = echoes + 1 events.emit("echo") end)
+        events.on("ping", function() pings = pings + 1 events.emit("pong") end)
+        events.on("pong", function() pongs = pongs + 1 events.emit("ping") end)
+        "#,
+    )
+    .exec()
+    .unwrap();
+
+    emit(&bus, &custom("echo"));
+    emit(&bus, &custom("ping"));
+    let count = |name: &str| lua.globals().get::<u32>(name).unwrap();
+    assert_eq!((count("echoes"), count("pings"), count("pongs")), (1, 1, 1));
+
+    // Once a handler returns, it runs again.
+    emit(&bus, &custom("echo"));
+    assert_eq!(count("echoes"), 2);
+}
@@ -0,0 +1,125 @@
+// Copyright 2025 Nicholas Jordan. All Rights Reserved.
+// github.com/cvusmo/lustre
+// tests/game.rs
+
+use lustre::block::BlockRegistry;
+use lustre::game::{GameLoop, Player, Transform, Velocity};
+use lustre::physics::{PhysicsWorld, FIXED_TIMESTEP};
+use lustre::world::{BlockPos, World};
+use rapier3d::na::{Point3, Vector3};
+use specs::prelude::*;
+
+fn position(game: &GameLoop, entity: Entity) -> Point3<f32> {
+    game.world()
+        .read_storage::<Transform>()
+        .get(entity)
+        .unwrap()
+        .position
+}
+
+#[test]
+fn ticks_move_entities_and_frames_interpolate_between_them() {
+    let mut game = GameLoop::new();
+    let mut physics = PhysicsWorld::default();
+    let entity = game
+        .world_mut()
+        .create_entity()
+        .with(Transform::new(Point3::origin()))
+        .with(Velocity {
+            linear: Vector3::new(6.0, 0.0, 0.0),
+            ..Default::default()
+        })
+        .build();
+
+    assert_eq!(game.update(&mut physics, FIXED_TIMESTEP * 2.5).steps, 2);
+    assert_eq!(game.ticks(), 2);
+    let step = 6.0 * FIXED_TIMESTEP;
+    let current = position(&game, entity);
+    assert!((current.x - 2.0 * step).abs() < 1e-5);
+
+    // Half a tick is left over, so the entity is drawn halfway through the last tick.
+    let drawn = game.interpolated_transform(entity).unwrap().position;
+    assert!((drawn.x - 1.5 * step).abs() < 1e-4);
+    // Long stalls are capped instead of simulated in full.
+    assert!(game.update(&mut physics, 10.0).steps < 100);
+}
+
+#[test]
+fn dynamic_boxes_follow_their_bodies() {
+    let registry = BlockRegistry::with_defaults();
+    let stone = registry.id_of("lustre:stone").unwrap();
+    let mut world = World::new(registry);
+    for x in 0..16 {
+        for z in 0..16 {
+            world.set_block(BlockPos::new(x, 0, z), stone);
+        }
+    }
+    let mut physics = PhysicsWorld::new(world.gravity());
+    physics.sync_terrain(&world);
+
+    let mut game = GameLoop::new();
+    let crate_entity = game.spawn_dynamic_box(
+        &mut physics,
+        Point3::new(8.0, 4.0, 8.0),
+        Vector3::repeat(0.5),
+        [1.0, 0.0, 0.0],
+    );
+    for _ in 0..120 {
+        game.tick(&mut physics);
+    }
+    let resting = position(&game, crate_entity);
+    assert!(
+        (resting.y - 1.5).abs() < 0.05,
+        "crate rests at {}",
+        resting.y
+    );
+    assert_eq!(game.render_mesh().quad_count(), 6);
+
+    // Velocities set by systems or scripts are handed to physics.
+    game.world()
+        .write_storage::<Velocity>()
+        .get_mut(crate_entity)
+        .unwrap()
+        .linear = Vector3::new(0.0, 5.0, 0.0);
+    game.tick(&mut physics);
+    let moved = position(&game, crate_entity);
+    assert!(moved.y > resting.y);
+
+    game.despawn(&mut physics, crate_entity);
+    game.world_mut().maintain();
+    assert_eq!(physics.bodies().len(), 0);
+    assert!(game.render_mesh().is_empty());
+}
+
+// Walks every player forward along z.
+struct WalkPlayers;
+
+impl<'a> System<'a> for WalkPlayers {
+    type SystemData = (ReadStorage<'a, Player>, WriteStorage<'a, Velocity>);
+
+    fn run(&mut self, (players, mut velocities): Self::SystemData) {
+        for (_, velocity) in (&players, &mut velocities).join() {
+            velocity.linear.z = 1.0;
+        }
+    }
+}
+
+#[test]
+fn game_systems_run_after_the_core_systems() {
+    let mut game = GameLoop::with_systems(|builder| builder.add(WalkPlayers, "walk_players", &[]));
+    let mut physics = PhysicsWorld::default();
+    let player = game
+        .world_mut()
+        .create_entity()
+        .with(Transform::new(Point3::origin()))
+        .with(Velocity::default())
+        .with(Player)
+        .build();
+    assert_eq!(game.player(), Some(player));
+
+    // The first tick sets the velocity after movement ran; the second moves the player.
+    game.tick(&mut physics);
+    game.tick(&mut physics);
+    let position = position(&game, player);
+    assert!((position.z - FIXED_TIMESTEP).abs() < 1e-5);
+}
@@ -0,0 +1,65 @@
+// Copyright 2025 Nicholas Jordan. All Rights Reserved.
+// github.com/cvusmo/lustre
+// tests/golden.rs
+
+mod common;
+
+use common::context;
+use image::{Rgba, RgbaImage};
+use lustre::golden::{check_golden, compare_images, golden_dir, GoldenError, Tolerance, BLESS_ENV};
+use lustre::headless::render_offscreen;
+use rapier3d::na::Matrix4;
+
+const SIZE: u32 = 256;
+
+#[test]
+fn triangle_matches_reference() {
+    let Some(context) = context() else { return };
+    // Identity matrices draw the triangle directly in clip space.
+    let identity = Matrix4::identity();
+    let image = render_offscreen(&context, SIZE, SIZE, &identity, &identity)
+        .expect("offscreen render failed");
+    if let Err(err) = check_golden("triangle", &image, &Tolerance::default()) {
+        panic!("{}", err);
+    }
+}
+
+#[test]
+fn compare_accepts_small_channel_drift() {
+    let expected = RgbaImage::from_pixel(8, 8, Rgba([100, 100, 100, 255]));
+    let actual = RgbaImage::from_pixel(8, 8, Rgba([102, 99, 100, 255]));
+    let tolerance = Tolerance::default();
+
+    let comparison = compare_images(&actual, &expected, &tolerance);
+    assert_eq!(comparison.mismatched_pixels, 0);
+    assert_eq!(comparison.max_channel_delta, 2);
+    assert!(comparison.passes(&tolerance));
+}
+
+#[test]
+fn compare_reports_mismatched_pixels_in_diff() {
+    let expected = RgbaImage::from_pixel(8, 8, Rgba([0, 0, 255, 255]));
+    let mut actual = expected.clone();
+    actual.put_pixel(3, 4, Rgba([255, 0, 0, 255]));
+    let tolerance = Tolerance {
+        max_channel_delta: 0,
+        max_mismatched_ratio: 0.0,
+    };
+
+    let comparison = compare_images(&actual, &expected, &tolerance);
+    assert_eq!(comparison.mismatched_pixels, 1);
+    assert!(!comparison.passes(&tolerance));
+    assert_eq!(comparison.diff.get_pixel(3, 4), &Rgba([255, 0, 0, 255]));
+    assert_ne!(comparison.diff.get_pixel(0, 0), &Rgba([255, 0, 0, 255]));
+}
+
+#[test]
+fn missing_reference_is_an_error() {
+    if std::env::var(BLESS_ENV).as_deref() == Ok("1") {
+        return;
+    }
+    let image = RgbaImage::from_pixel(4, 4, Rgba([0, 0, 0, 255]));
+    let result = check_golden("no_such_scene", &image, &Tolerance::default());
+    assert!(matches!(result, Err(GoldenError::MissingReference { .. })));
+    assert!(!golden_dir().join("no_such_scene.png").exists());
+}
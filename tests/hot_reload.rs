@@ -0,0 +1,119 @@
+// Copyright 2025 Nicholas Jordan. All Rights Reserved.
+// github.com/cvusmo/lustre
+// tests/hot_reload.rs
+
+mod common;
+
+use common::TempDir;
+use lustre::events::{self, emit, Event, EventBus};
+use lustre::hot_reload::reload_script;
+use lustre::mods::{load_mod, reload_mod, ModManifest, ModSource};
+use lustre::scripting::ScriptLimits;
+use mlua::{Function, Lua, Table};
+use std::cell::RefCell;
+use std::fs;
+use std::rc::Rc;
+
+// A counter mod whose behaviour table returns `speed`.
+fn counter_script(speed: u32) -> String {
+    format!(
+        r#"
+        Spinner = {{ speed = function() return {} end }}
+        function on_unload() return {{ loads = loads }} end
+        function on_load(state) loads = (state and state.loads or 0) + 1 end
+        "#,
+        speed
+    )
+}
+
+#[test]
+fn reloaded_mods_keep_their_state_and_tables() {
+    let dir = TempDir::new("mod");
+    fs::write(
+        dir.join("mod.toml"),
+        "id = \"counter\"\nversion = \"1.0.0\"\n",
+    )
+    .unwrap();
+    fs::write(dir.join("main.lua"), counter_script(1)).unwrap();
+
+    let lua = Lua::new();
+    let limits = ScriptLimits::default();
+    let source = ModSource {
+        dir: dir.to_path_buf(),
+        manifest: ModManifest::load(&dir.join("mod.toml")).unwrap(),
+    };
+    let mut loaded = load_mod(&lua, &source, &limits).unwrap();
+    // Held the way an entity holds its behaviour.
+    let spinner: Table = loaded.env.get("Spinner").unwrap();
+    let speed = || {
+        spinner
+            .get::<Function>("speed")
+            .unwrap()
+            .call::<u32>(())
+            .unwrap()
+    };
+    assert_eq!(speed(), 1);
+
+    fs::write(dir.join("main.lua"), counter_script(2)).unwrap();
+    reload_mod(&lua, &mut loaded, &limits).unwrap();
+    assert_eq!(speed(), 2);
+    assert_eq!(loaded.env.get::<u32>("loads").unwrap(), 2);
+
+    // A broken version leaves the running one in place.
+    fs::write(dir.join("main.lua"), "Spinner = {").unwrap();
+    assert!(reload_mod(&lua, &mut loaded, &limits).is_err());
+    fs::write(dir.join("main.lua"), "error('boom')").unwrap();
+    assert!(reload_mod(&lua, &mut loaded, &limits).is_err());
+    assert_eq!(speed(), 2);
+    assert_eq!(loaded.env.get::<u32>("loads").unwrap(), 3);
+}
+
+#[test]
+fn reloaded_project_scripts_update_global_tables() {
+    let dir = TempDir::new("script");
+    let path = dir.join("game.lua");
+    fs::write(&path, "Door = { open = function() return 'creak' end }").unwrap();
+
+    let lua = Lua::new();
+    reload_script(&lua, &path).unwrap();
+    let door: Table = lua.globals().get("Door").unwrap();
+    door.set("state", "closed").unwrap();
+
+    fs::write(&path, "Door = { open = function() return 'swish' end }").unwrap();
+    reload_script(&lua, &path).unwrap();
+    let open: Function = door.get("open").unwrap();
+    assert_eq!(open.call::<String>(()).unwrap(), "swish");
+    assert_eq!(door.get::<String>("state").unwrap(), "closed");
+    assert_eq!(lua.globals().get::<Table>("Door").unwrap(), door);
+}
+
+#[test]
+fn reloaded_project_scripts_replace_their_handlers() {
+    let dir = TempDir::new("handlers");
+    let path = dir.join("game.lua");
+    fs::write(&path, "events.on('tick', function() ticks = ticks + 1 end)").unwrap();
+
+    let lua = Lua::new();
+    let bus = Rc::new(RefCell::new(EventBus::new()));
+    events::register_lua_api(&lua, bus.clone()).unwrap();
+    lua.globals().set("ticks", 0).unwrap();
+    let tick = || {
+        emit(
+            &bus,
+            &Event::Tick {
+                tick: 1,
+                delta: 0.1,
+            },
+        );
+        lua.globals().get::<u32>("ticks").unwrap()
+    };
+
+    reload_script(&lua, &path).unwrap();
+    reload_script(&lua, &path).unwrap();
+    assert_eq!(tick(), 1);
+
+    // A broken version keeps the running handler.
+    fs::write(&path, "events.on('tick', function() end) error('boom')").unwrap();
+    assert!(reload_script(&lua, &path).is_err());
+    assert_eq!(tick(), 2);
+}
@@ -0,0 +1,100 @@
+// Copyright 2025 Nicholas Jordan. All Rights Reserved.
+// github.com/cvusmo/lustre
+// tests/mesher.rs
+
+use lustre::block::BlockRegistry;
+use lustre::chunk::CHUNK_SIZE;
+use lustre::mesher::{mesh_chunk, ChunkMesh, ChunkMesher, ChunkSample, MeshKey, MeshPalette};
+use lustre::world::{BlockPos, ChunkPos, World};
+use std::time::{Duration, Instant};
+
+fn stone_world() -> (World, u16) {
+    let registry = BlockRegistry::with_defaults();
+    let stone = registry.id_of("lustre:stone").unwrap();
+    (World::new(registry), stone)
+}
+
+fn mesh(world: &World, pos: ChunkPos) -> ChunkMesh {
+    let palette = MeshPalette::from_registry(world.registry());
+    mesh_chunk(&ChunkSample::from_world(world, pos), &palette)
+}
+
+#[test]
+fn single_block_has_six_unoccluded_faces() {
+    let (mut world, stone) = stone_world();
+    world.set_block(BlockPos::new(4, 4, 4), stone);
+
+    let mesh = mesh(&world, ChunkPos::new(0, 0, 0));
+    assert_eq!(mesh.quad_count(), 6);
+    assert_eq!(mesh.vertices.len(), 24);
+    assert!(mesh.vertices.iter().all(|vertex| vertex.ao == 1.0));
+}
+
+#[test]
+fn flat_layer_merges_into_one_quad_per_side() {
+    let (mut world, stone) = stone_world();
+    let size = CHUNK_SIZE as i32;
+    for x in 0..size {
+        for z in 0..size {
+            world.set_block(BlockPos::new(x, 0, z), stone);
+        }
+    }
+
+    let mesh = mesh(&world, ChunkPos::new(0, 0, 0));
+    assert_eq!(mesh.quad_count(), 6);
+}
+
+#[test]
+fn faces_between_chunks_are_culled() {
+    let (mut world, stone) = stone_world();
+    let last = CHUNK_SIZE as i32 - 1;
+    world.set_block(BlockPos::new(last, 0, 0), stone);
+    world.set_block(BlockPos::new(last + 1, 0, 0), stone);
+
+    assert_eq!(mesh(&world, ChunkPos::new(0, 0, 0)).quad_count(), 5);
+    assert_eq!(mesh(&world, ChunkPos::new(1, 0, 0)).quad_count(), 5);
+}
+
+#[test]
+fn corners_next_to_blocks_are_occluded() {
+    let (mut world, stone) = stone_world();
+    for x in 0..3 {
+        for z in 0..3 {
+            world.set_block(BlockPos::new(x, 0, z), stone);
+        }
+    }
+    world.set_block(BlockPos::new(1, 1, 1), stone);
+
+    let mesh = mesh(&world, ChunkPos::new(0, 0, 0));
+    let floor_top: Vec<_> = mesh
+        .vertices
+        .iter()
+        .filter(|vertex| vertex.normal == [0.0, 1.0, 0.0] && vertex.position[1] == 1.0)
+        .collect();
+    assert!(floor_top.iter().any(|vertex| vertex.ao < 1.0));
+    // Corners far from the raised block stay bright.
+    assert!(floor_top
+        .iter()
+        .any(|vertex| vertex.position == [0.0, 1.0, 0.0] && vertex.ao == 1.0));
+}
+
+#[test]
+fn mesher_rebuilds_only_dirty_chunks() {
+    let (mut world, stone) = stone_world();
+    world.set_block(BlockPos::new(0, 0, 0), stone);
+    let mut mesher = ChunkMesher::new(world.registry(), 2);
+
+    assert_eq!(mesher.schedule_dirty(&mut world), 1);
+    assert_eq!(mesher.schedule_dirty(&mut world), 0);
+
+    let deadline = Instant::now() + Duration::from_secs(10);
+    let mut finished = Vec::new();
+    while finished.is_empty() && Instant::now() < deadline {
+        finished = mesher.poll();
+        std::thread::sleep(Duration::from_millis(1));
+    }
+    assert_eq!(finished.len(), 1);
+    assert_eq!(finished[0].0, MeshKey::Chunk(ChunkPos::new(0, 0, 0)));
+    assert_eq!(finished[0].1.quad_count(), 6);
+    assert_eq!(mesher.pending(), 0);
+}
@@ -0,0 +1,383 @@
+// Copyright 2025 Nicholas Jordan. All Rights Reserved.
+// github.com/cvusmo/lustre
+// tests/mods.rs
+
+mod common;
+
+use common::TempDir;
+use lustre::block::{BlockRegistry, AIR};
+use lustre::events::{self, emit, Event, EventBus, Propagation};
+use lustre::mods::{
+    load_mod, load_mods, resolve_load_order, Capability, ModError, ModManifest, ModSettings,
+    ModSource,
+};
+use lustre::scripting::{run_with_limits, ScriptLimits};
+use lustre::terrain::{self, GeneratorRegistry};
+use lustre::world::ChunkPos;
+use mlua::{Function, Lua};
+use semver::Version;
+use std::cell::RefCell;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+// Writes a mod directory with a manifest and entry script.
+fn write_mod(dir: &Path, id: &str, manifest: &str, script: &str) -> PathBuf {
+    let mod_dir = dir.join(id);
+    fs::create_dir_all(&mod_dir).unwrap();
+    fs::write(
+        mod_dir.join("mod.toml"),
+        format!("id = \"{}\"\nversion = \"1.0.0\"\n{}", id, manifest),
+    )
+    .unwrap();
+    fs::write(mod_dir.join("main.lua"), script).unwrap();
+    mod_dir
+}
+
+fn source(id: &str, version: &str, dependencies: &[(&str, &str)]) -> ModSource {
+    let mut manifest = ModManifest::new(id, Version::parse(version).unwrap());
+    for (dependency, requirement) in dependencies {
+        manifest
+            .dependencies
+            .insert(dependency.to_string(), requirement.parse().unwrap());
+    }
+    ModSource {
+        dir: PathBuf::from(id),
+        manifest,
+    }
+}
+
+fn ids(order: &[ModSource]) -> Vec<&str> {
+    order
+        .iter()
+        .map(|source| source.manifest.id.as_str())
+        .collect()
+}
+
+#[test]
+fn mods_run_in_their_own_sandbox() {
+    let dir = TempDir::new("sandbox");
+    write_mod(
+        &dir,
+        "probe",
+        "",
+        r#"
+        leaked = true
+        string.upper = nil
+        has_io = io ~= nil
+        has_require = require ~= nil or load ~= nil or dofile ~= nil
+        has_execute = os.execute ~= nil
+        has_fs = fs ~= nil
+        string_meta = getmetatable("") ~= nil
+        print("loaded", 1)
+        "#,
+    );
+    fs::write(dir.join("notes.txt"), "not a mod").unwrap();
+    fs::write(dir.join("loose.lua"), "leaked = true").unwrap();
+
+    let lua = Lua::new();
+    let settings = ModSettings {
+        dir: dir.to_path_buf(),
+        ..Default::default()
+    };
+    let mods = load_mods(&lua, &settings);
+    assert_eq!(mods.len(), 1);
+    let env = &mods[0].env;
+    assert_eq!(mods[0].id(), "probe");
+
+    // Globals and libraries stay untouched for everyone else.
+    assert!(env.get::<bool>("leaked").unwrap());
+    assert!(lua
+        .globals()
+        .get::<Option<bool>>("leaked")
+        .unwrap()
+        .is_none());
+    assert!(lua
+        .load("return string.upper('a')")
+        .eval::<String>()
+        .is_ok());
+
+    for denied in [
+        "has_io",
+        "has_require",
+        "has_execute",
+        "has_fs",
+        "string_meta",
+    ] {
+        assert!(!env.get::<bool>(denied).unwrap(), "{} was allowed", denied);
+    }
+}
+
+#[test]
+fn mods_load_from_a_relative_directory() {
+    let dir = TempDir::new("relative");
+    write_mod(&dir, "nearby", "", "loaded = true");
+
+    // The same directory, reached from the working directory like the default `mods`.
+    let cwd = std::env::current_dir().unwrap();
+    let root = dir.ancestors().last().unwrap();
+    let relative = PathBuf::from("../".repeat(cwd.components().count() - 1))
+        .join(dir.strip_prefix(root).unwrap());
+    assert!(relative.is_relative());
+
+    let lua = Lua::new();
+    let settings = ModSettings {
+        dir: relative,
+        ..Default::default()
+    };
+    let mods = load_mods(&lua, &settings);
+    assert_eq!(mods.len(), 1);
+    assert!(mods[0].env.get::<bool>("loaded").unwrap());
+}
+
+#[test]
+fn filesystem_capability_is_limited_to_the_mod_directory() {
+    let dir = TempDir::new("filesystem");
+    fs::write(dir.join("secret.txt"), "hidden").unwrap();
+    let mod_dir = write_mod(
+        &dir,
+        "notes",
+        "capabilities = [\"filesystem\"]\n",
+        r#"
+        fs.write("data/state.txt", "saved")
+        read_back = fs.read("data/state.txt")
+        listed = fs.list("data")[1]
+        escaped = pcall(fs.read, "../secret.txt")
+        absolute = pcall(fs.read, "/etc/hostname")
+        "#,
+    );
+    let source = ModSource {
+        dir: mod_dir.clone(),
+        manifest: ModManifest::load(&mod_dir.join("mod.toml")).unwrap(),
+    };
+
+    let lua = Lua::new();
+    let loaded = load_mod(&lua, &source, &ScriptLimits::default()).unwrap();
+    assert!(loaded.manifest.allows(Capability::Filesystem));
+    let env = &loaded.env;
+    assert_eq!(env.get::<String>("read_back").unwrap(), "saved");
+    assert_eq!(env.get::<String>("listed").unwrap(), "state.txt");
+    assert!(!env.get::<bool>("escaped").unwrap());
+    assert!(!env.get::<bool>("absolute").unwrap());
+
+    // Unknown capabilities make the manifest invalid.
+    fs::write(
+        mod_dir.join("mod.toml"),
+        "id = \"notes\"\nversion = \"1.0.0\"\ncapabilities = [\"root\"]\n",
+    )
+    .unwrap();
+    assert!(ModManifest::load(&mod_dir.join("mod.toml")).is_err());
+}
+
+#[test]
+fn mods_load_after_their_dependencies() {
+    let dir = TempDir::new("order");
+    write_mod(
+        &dir,
+        "castles",
+        "[dependencies]\nstonework = \"^1\"\n[optional_dependencies]\nbanners = \"*\"\n",
+        "",
+    );
+    write_mod(&dir, "stonework", "", "");
+    write_mod(&dir, "banners", "", "");
+    write_mod(&dir, "zz_first", "", "");
+
+    let lua = Lua::new();
+    let settings = ModSettings {
+        dir: dir.to_path_buf(),
+        ..Default::default()
+    };
+    let loaded = load_mods(&lua, &settings);
+    let order: Vec<&str> = loaded.iter().map(|loaded| loaded.id()).collect();
+    assert_eq!(order, ["banners", "stonework", "castles", "zz_first"]);
+
+    // The same manifests always give the same order, whatever order they're found in.
+    let sources = vec![
+        source("b", "1.0.0", &[("a", "^1")]),
+        source("c", "1.0.0", &[]),
+        source("a", "1.2.0", &[]),
+    ];
+    let (order, errors) = resolve_load_order(sources.into_iter().rev().collect());
+    assert!(errors.is_empty());
+    assert_eq!(ids(&order), ["a", "b", "c"]);
+}
+
+#[test]
+fn unresolvable_mods_are_reported() {
+    let sources = vec![
+        source("needs_missing", "1.0.0", &[("ghost", "*")]),
+        source("needs_new", "1.0.0", &[("base", "^2")]),
+        source("base", "1.4.0", &[]),
+        source("after_failed", "1.0.0", &[("needs_missing", "*")]),
+        source("ping", "1.0.0", &[("pong", "*")]),
+        source("pong", "1.0.0", &[("ping", "*")]),
+    ];
+    let (order, errors) = resolve_load_order(sources);
+    assert_eq!(ids(&order), ["base"]);
+    assert!(errors.contains(&ModError::MissingDependency {
+        id: "needs_missing".to_string(),
+        dependency: "ghost".to_string(),
+        requirement: "*".parse().unwrap(),
+    }));
+    assert!(errors.contains(&ModError::IncompatibleDependency {
+        id: "needs_new".to_string(),
+        dependency: "base".to_string(),
+        requirement: "^2".parse().unwrap(),
+        found: Version::new(1, 4, 0),
+    }));
+    assert!(errors.contains(&ModError::DependencyFailed {
+        id: "after_failed".to_string(),
+        dependency: "needs_missing".to_string(),
+    }));
+    let cycle = ModError::Cycle(vec!["ping".into(), "pong".into(), "ping".into()]);
+    assert_eq!(
+        cycle.to_string(),
+        "mods depend on each other: ping -> pong -> ping"
+    );
+    assert!(errors.contains(&cycle));
+}
+
+#[test]
+fn optional_dependency_cycles_are_broken() {
+    let optional = |mut source: ModSource, dependency: &str| {
+        source
+            .manifest
+            .optional_dependencies
+            .insert(dependency.to_string(), "*".parse().unwrap());
+        source
+    };
+    let sources = vec![
+        optional(source("maps", "1.0.0", &[]), "trains"),
+        optional(source("trains", "1.0.0", &[]), "maps"),
+        optional(source("signals", "1.0.0", &[("tracks", "*")]), "switches"),
+        source("switches", "1.0.0", &[("signals", "*")]),
+        source("tracks", "1.0.0", &[]),
+    ];
+    let (order, errors) = resolve_load_order(sources);
+    assert!(errors.is_empty());
+    assert_eq!(
+        ids(&order),
+        ["tracks", "maps", "trains", "signals", "switches"]
+    );
+}
+
+#[test]
+fn runaway_scripts_are_stopped() {
+    let lua = Lua::new();
+    let limits = ScriptLimits {
+        memory: 1 << 20,
+        instructions: 100_000,
+    };
+
+    let spin: Function = lua
+        .load("return function() while true do end end")
+        .eval()
+        .unwrap();
+    assert!(run_with_limits(&lua, &limits, || spin.call::<()>(())).is_err());
+
+    let hog: Function = lua
+        .load("return function() local t = {} for i = 1, 1e7 do t[i] = i end end")
+        .eval()
+        .unwrap();
+    let limits = ScriptLimits {
+        instructions: 0,
+        ..limits
+    };
+    assert!(run_with_limits(&lua, &limits, || hog.call::<()>(())).is_err());
+
+    // Limits are lifted afterwards.
+    let count: i64 = lua
+        .load("local n = 0 for i = 1, 1e6 do n = n + 1 end return n")
+        .eval()
+        .unwrap();
+    assert_eq!(count, 1_000_000);
+}
+
+#[test]
+fn mod_event_handlers_run_within_limits_and_own_their_ids() {
+    let dir = TempDir::new("handlers");
+    write_mod(
+        &dir,
+        "spinner",
+        "",
+        r#"
+        events.on("tick", function() while true do end end)
+        "#,
+    );
+    write_mod(
+        &dir,
+        "meddler",
+        "",
+        r#"
+        removed = {}
+        for id = 0, 10 do table.insert(removed, events.off(id)) end
+        "#,
+    );
+
+    let lua = Lua::new();
+    let bus = Rc::new(RefCell::new(EventBus::new()));
+    events::register_lua_api(&lua, bus.clone()).unwrap();
+    let ticks = Rc::new(RefCell::new(0));
+    let counter = ticks.clone();
+    bus.borrow_mut().subscribe("tick", -10, move |_| {
+        *counter.borrow_mut() += 1;
+        Propagation::Continue
+    });
+
+    let settings = ModSettings {
+        dir: dir.to_path_buf(),
+        limits: ScriptLimits {
+            instructions: 100_000,
+            ..Default::default()
+        },
+    };
+    let mods = load_mods(&lua, &settings);
+    assert_eq!(mods.len(), 2);
+
+    // Neither the engine's handler nor another mod's could be removed.
+    let meddler = mods.iter().find(|loaded| loaded.id() == "meddler").unwrap();
+    let removed: Vec<bool> = meddler.env.get("removed").unwrap();
+    assert!(removed.iter().all(|removed| !removed));
+
+    // The runaway handler is stopped and the ones after it still run.
+    emit(
+        &bus,
+        &Event::Tick {
+            tick: 1,
+            delta: 0.1,
+        },
+    );
+    assert_eq!(*ticks.borrow(), 1);
+}
+
+#[test]
+fn mod_terrain_generators_run_within_limits() {
+    let dir = TempDir::new("generators");
+    write_mod(
+        &dir,
+        "spinner",
+        "",
+        r#"
+        terrain.register_generator("spinner:spin", function() while true do end end)
+        "#,
+    );
+
+    let lua = Lua::new();
+    let generators = Rc::new(RefCell::new(GeneratorRegistry::new()));
+    terrain::register_lua_api(&lua, generators.clone()).unwrap();
+
+    let settings = ModSettings {
+        dir: dir.to_path_buf(),
+        limits: ScriptLimits {
+            instructions: 100_000,
+            ..Default::default()
+        },
+    };
+    assert_eq!(load_mods(&lua, &settings).len(), 1);
+
+    // The runaway generator is stopped and leaves the chunk empty.
+    let registry = BlockRegistry::with_defaults();
+    let generator = generators.borrow().create("spinner:spin", 1).unwrap();
+    let chunk = generator.generate(ChunkPos::new(0, 0, 0), &registry);
+    assert!(chunk.is_uniform(AIR));
+}
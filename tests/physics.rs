@@ -0,0 +1,91 @@
+// Copyright 2025 Nicholas Jordan. All Rights Reserved.
+// github.com/cvusmo/lustre
+// tests/physics.rs
+
+use lustre::block::{BlockRegistry, AIR};
+use lustre::chunk::{Chunk, CHUNK_SIZE};
+use lustre::physics::{voxel_boxes, PhysicsWorld};
+use lustre::planet::Planet;
+use lustre::world::{BlockPos, ChunkPos, World};
+use rapier3d::na::{Point3, Vector3};
+
+// A world with a stone floor filling y = 0 across one chunk.
+fn floor_world() -> (World, u16) {
+    let registry = BlockRegistry::with_defaults();
+    let stone = registry.id_of("lustre:stone").unwrap();
+    let mut world = World::new(registry);
+    for x in 0..CHUNK_SIZE as i32 {
+        for z in 0..CHUNK_SIZE as i32 {
+            world.set_block(BlockPos::new(x, 0, z), stone);
+        }
+    }
+    (world, stone)
+}
+
+#[test]
+fn solid_blocks_merge_into_few_boxes() {
+    let registry = BlockRegistry::with_defaults();
+    let stone = registry.id_of("lustre:stone").unwrap();
+    assert_eq!(voxel_boxes(&Chunk::filled(stone), &registry).len(), 1);
+    assert!(voxel_boxes(&Chunk::new(), &registry).is_empty());
+    // Water is not solid.
+    let water = registry.id_of("lustre:water").unwrap();
+    assert!(voxel_boxes(&Chunk::filled(water), &registry).is_empty());
+
+    let mut chunk = Chunk::filled(stone);
+    chunk.set(5, 5, 5, AIR);
+    let boxes = voxel_boxes(&chunk, &registry);
+    let volume: usize = boxes.iter().map(|b| b.size.iter().product::<usize>()).sum();
+    assert_eq!(volume, CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE - 1);
+    assert!(boxes.len() <= 6);
+}
+
+#[test]
+fn bodies_land_on_terrain_and_fall_when_it_is_removed() {
+    let (mut world, _) = floor_world();
+    let mut physics = PhysicsWorld::default();
+    assert_eq!(physics.sync_terrain(&world), 1);
+    assert_eq!(physics.sync_terrain(&world), 0);
+
+    let ball = physics.add_dynamic_ball(Point3::new(8.5, 4.0, 8.5), 0.5);
+    for _ in 0..180 {
+        physics.step();
+    }
+    let resting = physics.body(ball).unwrap().translation().y;
+    assert!((resting - 1.5).abs() < 0.05, "ball rests at {}", resting);
+
+    // Dig the floor out from under the ball.
+    for x in 6..11 {
+        for z in 6..11 {
+            world.set_block(BlockPos::new(x, 0, z), AIR);
+        }
+    }
+    assert_eq!(physics.sync_terrain(&world), 1);
+    physics.body_mut(ball).unwrap().wake_up(true);
+    for _ in 0..60 {
+        physics.step();
+    }
+    assert!(physics.body(ball).unwrap().translation().y < 0.0);
+
+    world.remove_chunk(ChunkPos::new(0, 0, 0));
+    assert_eq!(physics.sync_terrain(&world), 1);
+    assert_eq!(physics.colliders().len(), 1);
+}
+
+#[test]
+fn radial_gravity_pulls_towards_the_planet() {
+    let planet = Planet::default();
+    let mut physics = PhysicsWorld::new(planet.gravity());
+    let start = Point3::new(600.0, 0.0, 0.0);
+    let ball = physics.add_dynamic_ball(start, 0.5);
+    for _ in 0..30 {
+        physics.step();
+    }
+    let velocity = *physics.body(ball).unwrap().linvel();
+    assert!(velocity.x < -1.0);
+    assert!(velocity.yz().norm() < 1e-3);
+    assert_eq!(
+        physics.gravity.acceleration(&start),
+        Vector3::new(-planet.surface_gravity, 0.0, 0.0)
+    );
+}
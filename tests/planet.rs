@@ -0,0 +1,103 @@
+// Copyright 2025 Nicholas Jordan. All Rights Reserved.
+// github.com/cvusmo/lustre
+// tests/planet.rs
+
+use lustre::block::{BlockRegistry, AIR};
+use lustre::camera::Camera;
+use lustre::planet::{cube_to_sphere, CubeFace, Gravity, Planet, PlanetGenerator};
+use lustre::terrain::{TerrainGenerator, TerrainSettings};
+use lustre::world::{BlockPos, World};
+use rapier3d::na::{Point3, Vector3};
+
+#[test]
+fn cube_faces_map_onto_the_unit_sphere() {
+    for face in CubeFace::ALL {
+        for (u, v) in [(-0.9, -0.9), (0.0, 0.0), (0.3, -0.8), (0.95, 0.5)] {
+            let point = face.sphere_point(u, v);
+            assert!((point.norm() - 1.0).abs() < 1e-9);
+            assert_eq!(CubeFace::from_direction(&point), face);
+        }
+    }
+    let (u, v) = CubeFace::PosY.face_coords(&Vector3::new(0.5, 2.0, -1.0));
+    assert_eq!((u, v), (-0.5, 0.25));
+    let corner = cube_to_sphere(&Vector3::new(1.0, 1.0, 1.0));
+    assert!((corner.norm() - 1.0).abs() < 1e-9);
+}
+
+#[test]
+fn blocks_are_axis_aligned_within_their_face() {
+    let planet = Planet::default();
+    assert_eq!(planet.block_up(BlockPos::new(3, 600, -10)), [0, 1, 0]);
+    assert_eq!(planet.block_up(BlockPos::new(-600, 3, 10)), [-1, 0, 0]);
+    assert_eq!(planet.block_up(BlockPos::new(5, -2, 550)), [0, 0, 1]);
+
+    let mut world = World::new(BlockRegistry::new());
+    assert_eq!(world.block_up(BlockPos::new(-600, 3, 10)), [0, 1, 0]);
+    world.set_planet(Some(planet));
+    assert_eq!(world.block_up(BlockPos::new(-600, 3, 10)), [-1, 0, 0]);
+}
+
+#[test]
+fn gravity_points_at_the_planet_centre() {
+    let planet = Planet::default();
+    let position = Point3::new(0.0, 0.0, -600.0);
+    let acceleration = planet.gravity().acceleration(&position);
+    assert!((acceleration - Vector3::new(0.0, 0.0, planet.surface_gravity)).norm() < 1e-4);
+    assert!((planet.up(&position) + Vector3::z()).norm() < 1e-6);
+
+    let flat = Gravity::default();
+    assert_eq!(flat.up(&position), Vector3::y());
+
+    let mut world = World::new(BlockRegistry::new());
+    assert_eq!(world.gravity(), Gravity::default());
+    world.set_planet(Some(planet));
+    assert!((world.up_at(&position) + Vector3::z()).norm() < 1e-6);
+}
+
+#[test]
+fn camera_follows_a_new_up() {
+    let mut camera = Camera::default();
+    camera.yaw = 0.7;
+    camera.set_up(Vector3::new(1.0, 1.0, 0.0));
+
+    let up = camera.up();
+    assert!((up - Vector3::new(1.0, 1.0, 0.0).normalize()).norm() < 1e-5);
+    assert!(camera.forward().dot(&up).abs() < 1e-5);
+    assert!(camera.right().dot(&up).abs() < 1e-5);
+
+    // Upside down is handled without producing NaNs.
+    camera.set_up(-up);
+    assert!((camera.up() + up).norm() < 1e-5);
+}
+
+#[test]
+fn planet_terrain_wraps_around_every_face() {
+    let registry = BlockRegistry::with_defaults();
+    let planet = Planet {
+        radius: 256.0,
+        ..Default::default()
+    };
+    let generator = PlanetGenerator::new(3, planet, TerrainSettings::default());
+
+    for direction in [Vector3::y(), -Vector3::x(), Vector3::z()] {
+        let surface = generator.surface_radius(&direction);
+        let below = (direction * (surface - 1.5)).map(|c| c.floor() as i32);
+        let above = (direction * (surface + 2.5)).map(|c| c.floor() as i32);
+
+        let solid = BlockPos::new(below.x, below.y, below.z);
+        let open = BlockPos::new(above.x, above.y, above.z);
+        let chunk = generator.generate(solid.chunk(), &registry);
+        let (x, y, z) = solid.local();
+        assert_ne!(chunk.get(x, y, z), AIR);
+
+        let chunk = generator.generate(open.chunk(), &registry);
+        let (x, y, z) = open.local();
+        let block = chunk.get(x, y, z);
+        assert!(block == AIR || Some(block) == registry.id_of("lustre:water"));
+    }
+
+    let space = BlockPos::new(0, 1000, 0).chunk();
+    assert!(generator.generate(space, &registry).is_empty());
+    let core = generator.generate(BlockPos::new(0, 0, 0).chunk(), &registry);
+    assert!(core.is_uniform(registry.id_of("lustre:bedrock").unwrap()));
+}
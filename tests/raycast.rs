@@ -0,0 +1,132 @@
+// Copyright 2025 Nicholas Jordan. All Rights Reserved.
+// github.com/cvusmo/lustre
+// tests/raycast.rs
+
+use lustre::block::{BlockRegistry, AIR};
+use lustre::mesher::block_outline;
+use lustre::raycast::{mine_block, place_block, raycast, register_lua_api};
+use lustre::world::{BlockPos, World};
+use mlua::{Lua, Table};
+use rapier3d::na::{Point3, Vector3};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+// A single stone block at (5, 0, 0), with water in front of it.
+fn world_with_block() -> World {
+    let mut world = World::new(BlockRegistry::with_defaults());
+    let stone = world.registry().id_of("lustre:stone").unwrap();
+    let water = world.registry().id_of("lustre:water").unwrap();
+    world.set_block(BlockPos::new(5, 0, 0), stone);
+    world.set_block(BlockPos::new(4, 0, 0), water);
+    world
+}
+
+#[test]
+fn rays_hit_the_first_solid_block_and_its_face() {
+    let world = world_with_block();
+    let stone = world.registry().id_of("lustre:stone").unwrap();
+    let origin = Point3::new(0.5, 0.5, 0.5);
+
+    let hit = raycast(&world, origin, Vector3::x(), 10.0).unwrap();
+    assert_eq!(hit.block, BlockPos::new(5, 0, 0));
+    assert_eq!(hit.id, stone);
+    assert_eq!(hit.normal, [-1, 0, 0]);
+    assert!((hit.distance - 4.5).abs() < 1e-5);
+    assert_eq!(hit.adjacent(), BlockPos::new(4, 0, 0));
+
+    // From the other side and from above, at an angle.
+    let hit = raycast(&world, Point3::new(9.5, 0.5, 0.5), -Vector3::x(), 10.0).unwrap();
+    assert_eq!(hit.normal, [1, 0, 0]);
+    let down = Vector3::new(1.0, -1.0, 0.0);
+    let hit = raycast(&world, Point3::new(4.2, 2.5, 0.5), down, 10.0).unwrap();
+    assert_eq!(hit.block, BlockPos::new(5, 0, 0));
+    assert_eq!(hit.normal, [0, 1, 0]);
+
+    // Out of reach, pointing away, or with no direction.
+    assert!(raycast(&world, origin, Vector3::x(), 4.0).is_none());
+    assert!(raycast(&world, origin, -Vector3::x(), 100.0).is_none());
+    assert!(raycast(&world, origin, Vector3::zeros(), 100.0).is_none());
+}
+
+#[test]
+fn mining_and_placing_edit_the_targeted_blocks() {
+    let mut world = world_with_block();
+    let dirt = world.registry().id_of("lustre:dirt").unwrap();
+    let bedrock = world.registry().id_of("lustre:bedrock").unwrap();
+    let origin = Point3::new(0.5, 0.5, 0.5);
+
+    // Blocks go in front of the hit face, even into water.
+    let hit = raycast(&world, origin, Vector3::x(), 10.0).unwrap();
+    assert_eq!(
+        place_block(&mut world, &hit, dirt),
+        Some(BlockPos::new(4, 0, 0))
+    );
+    assert_eq!(world.get_block(BlockPos::new(4, 0, 0)), dirt);
+    assert_eq!(place_block(&mut world, &hit, dirt), None);
+
+    let hit = raycast(&world, origin, Vector3::x(), 10.0).unwrap();
+    assert_eq!(mine_block(&mut world, &hit), Some(dirt));
+    assert_eq!(world.get_block(BlockPos::new(4, 0, 0)), AIR);
+
+    world.set_block(BlockPos::new(4, 0, 0), bedrock);
+    let hit = raycast(&world, origin, Vector3::x(), 10.0).unwrap();
+    assert_eq!(mine_block(&mut world, &hit), None);
+    assert_eq!(world.get_block(BlockPos::new(4, 0, 0)), bedrock);
+}
+
+#[test]
+fn lua_scripts_can_raycast() {
+    let world = Rc::new(RefCell::new(world_with_block()));
+    let lua = Lua::new();
+    register_lua_api(&lua, world).unwrap();
+
+    let hit: Table = lua
+        .load("return world.raycast({x = 0.5, y = 0.5, z = 0.5}, {x = 1, y = 0, z = 0})")
+        .eval()
+        .unwrap();
+    assert_eq!(hit.get::<i32>("x").unwrap(), 5);
+    assert_eq!(
+        hit.get::<Table>("normal").unwrap().get::<i32>("x").unwrap(),
+        -1
+    );
+    assert!((hit.get::<f32>("distance").unwrap() - 4.5).abs() < 1e-5);
+
+    let miss: Option<Table> = lua
+        .load("return world.raycast({x = 0.5, y = 0.5, z = 0.5}, {x = 1, y = 0, z = 0}, 2)")
+        .eval()
+        .unwrap();
+    assert!(miss.is_none());
+}
+
+#[test]
+fn huge_distances_still_end() {
+    let world = world_with_block();
+    let origin = Point3::new(0.5, 0.5, 0.5);
+    assert!(raycast(&world, origin, -Vector3::x(), f32::INFINITY).is_none());
+    assert!(raycast(&world, origin, Vector3::y(), 1e30).is_none());
+
+    let world = Rc::new(RefCell::new(world));
+    let lua = Lua::new();
+    register_lua_api(&lua, world).unwrap();
+    assert!(lua
+        .load("return world.raycast({x = 0.5, y = 0.5, z = 0.5}, {x = 0, y = 1, z = 0}, math.huge)")
+        .eval::<Option<Table>>()
+        .is_err());
+    let miss: Option<Table> = lua
+        .load("return world.raycast({x = 0.5, y = 0.5, z = 0.5}, {x = 0, y = 1, z = 0}, 2^30)")
+        .eval()
+        .unwrap();
+    assert!(miss.is_none());
+}
+
+#[test]
+fn block_outlines_surround_the_block() {
+    let outline = block_outline(BlockPos::new(2, -3, 4));
+    assert_eq!(outline.quad_count(), 12 * 6);
+    for vertex in &outline.vertices {
+        let [x, y, z] = vertex.position;
+        assert!((1.9..=3.1).contains(&x));
+        assert!((-3.1..=-1.9).contains(&y));
+        assert!((3.9..=5.1).contains(&z));
+    }
+}
@@ -0,0 +1,213 @@
+// Copyright 2025 Nicholas Jordan. All Rights Reserved.
+// github.com/cvusmo/lustre
+// tests/save.rs
+
+mod common;
+
+use common::TempDir;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use lustre::block::{BlockRegistry, AIR};
+use lustre::chunk::Chunk;
+use lustre::mesher::ChunkMesher;
+use lustre::planet::Planet;
+use lustre::region::{RegionFile, RegionPos, REGION_SIZE};
+use lustre::save::{WorldMetadata, WorldSave, SAVE_VERSION};
+use lustre::streaming::{ChunkStreamer, StreamingSettings};
+use lustre::terrain::{TerrainGenerator, DEFAULT_GENERATOR};
+use lustre::world::{BlockPos, ChunkPos, World};
+use rapier3d::na::Point3;
+use std::fs;
+use std::io::{ErrorKind, Seek, SeekFrom, Write};
+use std::path::Path;
+
+// Generates nothing, so every solid block in a test comes from the save.
+struct EmptyGenerator;
+
+impl TerrainGenerator for EmptyGenerator {
+    fn generate(&self, _pos: ChunkPos, _registry: &BlockRegistry) -> Chunk {
+        Chunk::new()
+    }
+}
+
+#[test]
+fn region_files_round_trip_chunks() {
+    let dir = TempDir::new("region");
+    let path = dir.join(RegionPos::of_chunk(ChunkPos::new(-1, 0, 3)).file_name());
+    let registry = BlockRegistry::with_defaults();
+    let stone = registry.id_of("lustre:stone").unwrap();
+    let dirt = registry.id_of("lustre:dirt").unwrap();
+
+    let mut chunk = Chunk::filled(stone);
+    chunk.set(1, 2, 3, dirt);
+    chunk.set(4, 5, 6, AIR);
+    {
+        let mut region = RegionFile::open(&path).unwrap();
+        region
+            .write_chunk(ChunkPos::new(-1, 0, 3), &chunk, &registry)
+            .unwrap();
+        region
+            .write_chunk(ChunkPos::new(0, 0, 0), &Chunk::new(), &registry)
+            .unwrap();
+        // A larger payload is appended instead of overwriting its neighbour.
+        let mut bigger = chunk.clone();
+        for i in 0..16 {
+            bigger.set(i, i, i, registry.id_of("lustre:sand").unwrap());
+        }
+        region
+            .write_chunk(ChunkPos::new(-1, 0, 3), &bigger, &registry)
+            .unwrap();
+    }
+
+    let mut region = RegionFile::open(&path).unwrap();
+    assert!(!region.contains(ChunkPos::new(1, 1, 1)));
+    let loaded = region
+        .read_chunk(ChunkPos::new(-1, 0, 3), &registry)
+        .unwrap()
+        .unwrap();
+    assert_eq!(loaded.get(1, 2, 3), dirt);
+    assert_eq!(loaded.get(4, 5, 6), AIR);
+    assert_eq!(loaded.get(7, 7, 7), registry.id_of("lustre:sand").unwrap());
+    assert_eq!(loaded.get(31, 31, 31), stone);
+    let empty = region
+        .read_chunk(ChunkPos::new(0, 0, 0), &registry)
+        .unwrap()
+        .unwrap();
+    assert!(empty.is_empty());
+
+    assert_eq!(RegionPos::of_chunk(ChunkPos::new(-1, 0, REGION_SIZE)).z, 1);
+    fs::write(&path, b"not a region").unwrap();
+    assert!(RegionFile::open(&path).is_err());
+}
+
+// Stores `data` compressed as chunk (0, 0, 0)'s payload, bypassing the encoder.
+fn write_raw_chunk(path: &Path, data: &[u8]) {
+    let mut encoder = ZlibEncoder::new(vec![1], Compression::default());
+    encoder.write_all(data).unwrap();
+    let payload = encoder.finish().unwrap();
+
+    let mut file = fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(path)
+        .unwrap();
+    let offset = file.seek(SeekFrom::End(0)).unwrap();
+    file.write_all(&payload).unwrap();
+    // Slot 0's index entry follows the magic and version.
+    file.seek(SeekFrom::Start(8)).unwrap();
+    file.write_all(&offset.to_le_bytes()).unwrap();
+    file.write_all(&(payload.len() as u32).to_le_bytes())
+        .unwrap();
+}
+
+#[test]
+fn damaged_chunks_are_invalid_data() {
+    let dir = TempDir::new("damaged");
+    let path = dir.join(RegionPos::of_chunk(ChunkPos::new(0, 0, 0)).file_name());
+    let registry = BlockRegistry::with_defaults();
+    RegionFile::open(&path).unwrap();
+    let read = |path: &Path| {
+        RegionFile::open(path)
+            .unwrap()
+            .read_chunk(ChunkPos::new(0, 0, 0), &registry)
+            .unwrap_err()
+            .kind()
+    };
+
+    // A palette claiming four billion entries.
+    write_raw_chunk(&path, &u32::MAX.to_le_bytes());
+    assert_eq!(read(&path), ErrorKind::InvalidData);
+
+    // One air entry, then indices wider than 32 bits.
+    let mut data = 1u32.to_le_bytes().to_vec();
+    data.extend_from_slice(&0u16.to_le_bytes());
+    data.extend_from_slice(&40u32.to_le_bytes());
+    write_raw_chunk(&path, &data);
+    assert_eq!(read(&path), ErrorKind::InvalidData);
+
+    // An index entry pointing past the end of the file.
+    let mut file = fs::OpenOptions::new().write(true).open(&path).unwrap();
+    file.seek(SeekFrom::Start(16)).unwrap();
+    file.write_all(&u32::MAX.to_le_bytes()).unwrap();
+    assert_eq!(read(&path), ErrorKind::InvalidData);
+}
+
+#[test]
+fn metadata_is_versioned_toml() {
+    let dir = TempDir::new("metadata");
+    let save = WorldSave::open(&dir).unwrap();
+    assert_eq!(save.load_metadata().unwrap(), None);
+
+    let metadata = WorldMetadata {
+        seed: 42,
+        spawn: [1.0, 70.0, -3.0],
+        game_time: 125.5,
+        generator: "mymod:islands".to_string(),
+        planet: Some(Planet::default()),
+        ..Default::default()
+    };
+    save.save_metadata(&metadata).unwrap();
+    assert_eq!(save.load_metadata().unwrap(), Some(metadata));
+    let text = fs::read_to_string(dir.join("world.toml")).unwrap();
+    assert!(text.contains(&format!("version = {}", SAVE_VERSION)));
+
+    // Saves from before generators were named used the built-in one.
+    fs::write(
+        dir.join("world.toml"),
+        format!("version = {}\nseed = 1\n", SAVE_VERSION),
+    )
+    .unwrap();
+    let metadata = save.load_metadata().unwrap().unwrap();
+    assert_eq!(metadata.generator, DEFAULT_GENERATOR);
+
+    fs::write(dir.join("world.toml"), "version = 999\nseed = 1\n").unwrap();
+    assert!(save.load_metadata().is_err());
+}
+
+#[test]
+fn only_edited_chunks_are_saved_and_they_load_lazily() {
+    let dir = TempDir::new("world");
+    let registry = BlockRegistry::with_defaults();
+    let stone = registry.id_of("lustre:stone").unwrap();
+    let mut world = World::new(registry);
+    let mut save = WorldSave::open(&dir).unwrap();
+
+    // Generated chunks are not saved; edits are, once.
+    world.insert_chunk(ChunkPos::new(5, 0, 0), Chunk::filled(stone));
+    world.set_block(BlockPos::new(3, 4, 5), stone);
+    assert_eq!(save.save_unsaved(&mut world).unwrap(), 1);
+    assert_eq!(save.save_unsaved(&mut world).unwrap(), 0);
+    assert!(save
+        .load_chunk(&world, ChunkPos::new(5, 0, 0))
+        .unwrap()
+        .is_none());
+
+    // Edits made while streaming are written when their chunk unloads, then read back
+    // instead of generated when the viewer returns.
+    let mut world = World::new(BlockRegistry::with_defaults());
+    let mut mesher = ChunkMesher::new(world.registry(), 1);
+    let mut streamer = ChunkStreamer::new(StreamingSettings {
+        view_distance: 1,
+        lod_levels: 0,
+        frame_budget_ms: 1000.0,
+        ..Default::default()
+    });
+    streamer.set_save(Some(save));
+    let home = Point3::new(8.0, 8.0, 8.0);
+    streamer.update(&mut world, &EmptyGenerator, &mut mesher, home, None);
+    assert_eq!(world.get_block(BlockPos::new(3, 4, 5)), stone);
+
+    world.set_block(BlockPos::new(6, 6, 6), stone);
+    streamer.update(
+        &mut world,
+        &EmptyGenerator,
+        &mut mesher,
+        Point3::new(1000.0, 8.0, 8.0),
+        None,
+    );
+    assert!(!world.is_loaded(ChunkPos::new(0, 0, 0)));
+    streamer.update(&mut world, &EmptyGenerator, &mut mesher, home, None);
+    assert_eq!(world.get_block(BlockPos::new(6, 6, 6)), stone);
+    assert_eq!(world.get_block(BlockPos::new(3, 4, 5)), stone);
+    assert!(!world.is_unsaved(ChunkPos::new(0, 0, 0)));
+}
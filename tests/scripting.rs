@@ -0,0 +1,130 @@
+// Copyright 2025 Nicholas Jordan. All Rights Reserved.
+// github.com/cvusmo/lustre
+// tests/scripting.rs
+
+use lustre::game::{GameLoop, Transform};
+use lustre::physics::{PhysicsWorld, FIXED_TIMESTEP};
+use lustre::scripting::{register_lua_api, LuaEntity, ScriptRunner};
+use mlua::{Lua, Table};
+use rapier3d::na::Point3;
+use specs::prelude::*;
+
+// A game whose entity scripts run in `lua`, with the `ecs` table registered.
+fn scripted_game(lua: &Lua) -> GameLoop {
+    let runner = ScriptRunner::new(lua.clone());
+    let game = GameLoop::with_systems(|builder| builder.add_thread_local(runner));
+    register_lua_api(lua, game.shared_world()).unwrap();
+    game
+}
+
+#[test]
+fn lua_spawns_edits_queries_and_despawns_entities() {
+    let lua = Lua::new();
+    let mut game = scripted_game(&lua);
+    let mut physics = PhysicsWorld::default();
+
+    let entity: LuaEntity = lua
+        .load(
+            r#"
+            local e = ecs.spawn({
+                transform = { position = { x = 1, y = 2, z = 3 } },
+                player = true,
+            })
+            ecs.set(e, "velocity", { linear = { x = 0, y = 4, z = 0 } })
+            ecs.spawn({ transform = {} })
+            return e
+            "#,
+        )
+        .eval()
+        .unwrap();
+    assert_eq!(game.player(), Some(entity.0));
+    let position = game
+        .world()
+        .read_storage::<Transform>()
+        .get(entity.0)
+        .unwrap()
+        .position;
+    assert_eq!(position, Point3::new(1.0, 2.0, 3.0));
+
+    let (transforms, movers, y): (usize, usize, f32) = lua
+        .load(
+            r#"
+            local movers = ecs.query("transform", "velocity")
+            local velocity = ecs.get(movers[1], "velocity")
+            return #ecs.query("transform"), #movers, velocity.linear.y
+            "#,
+        )
+        .eval()
+        .unwrap();
+    assert_eq!((transforms, movers, y), (2, 1, 4.0));
+
+    // Unknown components are errors; removed ones read as nil.
+    assert!(lua.load("ecs.query('health')").exec().is_err());
+    let gone: bool = lua
+        .load(
+            r#"
+            local e = ecs.query("player")[1]
+            ecs.set(e, "velocity", nil)
+            return ecs.get(e, "velocity") == nil
+            "#,
+        )
+        .eval()
+        .unwrap();
+    assert!(gone);
+
+    // Despawned entities are gone for scripts at once and for the game after a tick.
+    let alive: bool = lua
+        .load("local e = ecs.query('player')[1]; ecs.despawn(e); return ecs.is_alive(e)")
+        .eval()
+        .unwrap();
+    assert!(!alive);
+    game.tick(&mut physics);
+    assert_eq!(game.player(), None);
+    lua.globals().set("despawned", entity).unwrap();
+    assert!(lua
+        .load("return ecs.get(despawned, 'transform')")
+        .exec()
+        .is_err());
+}
+
+#[test]
+fn behaviour_scripts_update_every_tick() {
+    let lua = Lua::new();
+    let mut game = scripted_game(&lua);
+    let mut physics = PhysicsWorld::default();
+
+    let entity: LuaEntity = lua
+        .load(
+            r#"
+            local Climber = {}
+            function Climber:on_update(dt)
+                local transform = ecs.get(self.entity, "transform")
+                transform.position.y = transform.position.y + dt
+                ecs.set(self.entity, "transform", transform)
+            end
+            -- Errors are logged without stopping the other scripts.
+            ecs.spawn({ script = { on_update = function() error("broken") end } })
+            return ecs.spawn({ transform = {}, script = setmetatable({}, { __index = Climber }) })
+            "#,
+        )
+        .eval()
+        .unwrap();
+
+    for _ in 0..3 {
+        game.tick(&mut physics);
+    }
+    let y = game
+        .world()
+        .read_storage::<Transform>()
+        .get(entity.0)
+        .unwrap()
+        .position
+        .y;
+    assert!((y - 3.0 * FIXED_TIMESTEP).abs() < 1e-5);
+
+    let script: Table = lua
+        .load("return ecs.get(ecs.query('transform')[1], 'script')")
+        .eval()
+        .unwrap();
+    assert_eq!(script.get::<LuaEntity>("entity").unwrap(), entity);
+}
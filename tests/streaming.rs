@@ -0,0 +1,217 @@
+// Copyright 2025 Nicholas Jordan. All Rights Reserved.
+// github.com/cvusmo/lustre
+// tests/streaming.rs
+
+use lustre::block::{BlockRegistry, AIR};
+use lustre::camera::Camera;
+use lustre::chunk::{Chunk, CHUNK_SIZE};
+use lustre::mesher::{ChunkMesher, MeshKey};
+use lustre::streaming::{ChunkStreamer, StreamingSettings};
+use lustre::terrain::TerrainGenerator;
+use lustre::world::{BlockPos, ChunkPos, LodPos, World, MAX_LOD_LEVEL};
+use rapier3d::na::Point3;
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+// Solid below y = 0 at every level of detail.
+struct FlatGenerator;
+
+impl TerrainGenerator for FlatGenerator {
+    fn generate(&self, pos: ChunkPos, registry: &BlockRegistry) -> Chunk {
+        self.generate_lod(pos.origin(), 1, registry).unwrap()
+    }
+
+    fn share(&self) -> Option<Arc<dyn TerrainGenerator + Send + Sync>> {
+        Some(Arc::new(FlatGenerator))
+    }
+
+    fn generate_lod(
+        &self,
+        origin: BlockPos,
+        _scale: i32,
+        registry: &BlockRegistry,
+    ) -> Option<Chunk> {
+        Some(if origin.y < 0 {
+            Chunk::filled(registry.id_of("lustre:stone").unwrap())
+        } else {
+            Chunk::new()
+        })
+    }
+}
+
+fn settings(view_distance: i32, lod_levels: u8) -> StreamingSettings {
+    StreamingSettings {
+        view_distance,
+        lod_levels,
+        frame_budget_ms: 1000.0,
+        offscreen_penalty: 4.0,
+    }
+}
+
+#[test]
+fn lod_cells_nest() {
+    let cell = LodPos::of_chunk(ChunkPos::new(-1, 5, 8), 2);
+    assert_eq!(cell, LodPos::new(2, -1, 1, 2));
+    assert_eq!(cell.min_chunk(), ChunkPos::new(-4, 4, 8));
+    let size = CHUNK_SIZE as i32;
+    assert_eq!(cell.origin(), BlockPos::new(-4 * size, 4 * size, 8 * size));
+    assert_eq!(cell.scale(), 4);
+
+    let children = cell.children();
+    assert!(children.iter().all(|child| child.level == 1));
+    assert!(children
+        .iter()
+        .all(|child| LodPos::of_chunk(child.min_chunk(), 2) == cell));
+    assert_eq!(children.iter().collect::<HashSet<_>>().len(), 8);
+}
+
+#[test]
+fn cells_cover_each_chunk_once_with_detail_near_the_viewer() {
+    let registry = BlockRegistry::with_defaults();
+    let mut world = World::new(registry);
+    let mut mesher = ChunkMesher::new(world.registry(), 1);
+    let mut streamer = ChunkStreamer::new(settings(2, 2));
+    streamer.update(
+        &mut world,
+        &FlatGenerator,
+        &mut mesher,
+        Point3::new(8.0, 8.0, 8.0),
+        None,
+    );
+
+    let wanted: Vec<LodPos> = streamer.wanted().copied().collect();
+    assert!(wanted.contains(&LodPos::new(0, 0, 0, 0)));
+    assert!(wanted.iter().any(|cell| cell.level == 2));
+
+    // No chunk lies in two cells.
+    let mut covered = HashSet::new();
+    for cell in &wanted {
+        let size = cell.size_in_chunks();
+        let min = cell.min_chunk();
+        for x in 0..size {
+            for y in 0..size {
+                for z in 0..size {
+                    assert!(covered.insert(min.offset(x, y, z)));
+                }
+            }
+        }
+    }
+    // Everything was generated within the budget; solid chunks sit below the viewer.
+    assert_eq!(streamer.queued(), 0);
+    assert!(world.is_loaded(ChunkPos::new(0, -1, 0)));
+    assert!(!world.is_loaded(ChunkPos::new(0, 0, 0)));
+}
+
+#[test]
+fn nearest_and_visible_chunks_load_first() {
+    let mut world = World::new(BlockRegistry::with_defaults());
+    let mut mesher = ChunkMesher::new(world.registry(), 1);
+    let mut streamer = ChunkStreamer::new(StreamingSettings {
+        frame_budget_ms: 0.0,
+        ..settings(3, 0)
+    });
+
+    // Looking down -Z from just above the ground.
+    let mut camera = Camera::default();
+    camera.position = Point3::new(16.0, 1.0, 16.0);
+    let frustum = camera.frustum(1.0);
+    // One job per frame with no budget: the viewer's chunk, the one below it, then the two
+    // in front. Chunks above the ground are empty and never added to the world.
+    for _ in 0..4 {
+        streamer.update(
+            &mut world,
+            &FlatGenerator,
+            &mut mesher,
+            camera.position,
+            Some(&frustum),
+        );
+    }
+    assert_eq!(world.chunk_count(), 2);
+    assert!(world.is_loaded(ChunkPos::new(0, -1, 0)));
+    assert!(world.is_loaded(ChunkPos::new(0, -1, -1)));
+    // Just as close, but behind the camera.
+    assert!(!world.is_loaded(ChunkPos::new(0, -1, 1)));
+}
+
+#[test]
+fn moving_away_unloads_chunks_and_their_meshes() {
+    let mut world = World::new(BlockRegistry::with_defaults());
+    let mut mesher = ChunkMesher::new(world.registry(), 1);
+    let mut streamer = ChunkStreamer::new(settings(1, 0));
+
+    streamer.update(
+        &mut world,
+        &FlatGenerator,
+        &mut mesher,
+        Point3::new(8.0, 8.0, 8.0),
+        None,
+    );
+    assert!(world.is_loaded(ChunkPos::new(0, -1, 0)));
+
+    let far = (CHUNK_SIZE * 10) as f32;
+    let update = streamer.update(
+        &mut world,
+        &FlatGenerator,
+        &mut mesher,
+        Point3::new(far, 8.0, 8.0),
+        None,
+    );
+    assert!(!world.is_loaded(ChunkPos::new(0, -1, 0)));
+    assert!(update
+        .unloaded
+        .iter()
+        .any(|(pos, chunk)| *pos == ChunkPos::new(0, -1, 0) && chunk.get(0, 0, 0) != AIR));
+    assert!(update
+        .removed_meshes
+        .contains(&MeshKey::Chunk(ChunkPos::new(0, -1, 0))));
+}
+
+#[test]
+fn workers_generate_the_same_cells() {
+    let viewer = Point3::new(8.0, 8.0, 8.0);
+    let mut inline_world = World::new(BlockRegistry::with_defaults());
+    let mut mesher = ChunkMesher::new(inline_world.registry(), 1);
+    let mut inline = ChunkStreamer::new(settings(2, 1));
+    inline.update(&mut inline_world, &FlatGenerator, &mut mesher, viewer, None);
+
+    let mut world = World::new(BlockRegistry::with_defaults());
+    let mut streamer = ChunkStreamer::new(settings(2, 1));
+    assert!(streamer.start_workers(&FlatGenerator, world.registry(), 2));
+    let deadline = Instant::now() + Duration::from_secs(10);
+    loop {
+        streamer.update(&mut world, &FlatGenerator, &mut mesher, viewer, None);
+        if streamer.queued() == 0 || Instant::now() > deadline {
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(1));
+    }
+    assert_eq!(streamer.queued(), 0);
+    assert_eq!(world.chunk_count(), inline_world.chunk_count());
+    assert!(world.is_loaded(ChunkPos::new(0, -1, 0)));
+}
+
+#[test]
+fn out_of_range_settings_are_clamped() {
+    let streamer = ChunkStreamer::new(settings(0, 200));
+    assert_eq!(streamer.settings().view_distance, 1);
+    assert_eq!(streamer.settings().lod_levels, MAX_LOD_LEVEL);
+    assert!(streamer.settings().range().is_finite());
+}
+
+#[test]
+fn frustum_culls_boxes_behind_the_camera() {
+    let camera = Camera::default();
+    let frustum = camera.frustum(1.0);
+    let (x, y) = (camera.position.x, camera.position.y);
+    let ahead = camera.position.z - 10.0;
+    assert!(frustum.intersects_aabb(
+        &Point3::new(x - 1.0, y - 1.0, ahead - 1.0),
+        &Point3::new(x + 1.0, y + 1.0, ahead + 1.0)
+    ));
+    let behind = camera.position.z + 10.0;
+    assert!(!frustum.intersects_aabb(
+        &Point3::new(x - 1.0, y - 1.0, behind - 1.0),
+        &Point3::new(x + 1.0, y + 1.0, behind + 1.0)
+    ));
+}
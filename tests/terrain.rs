@@ -0,0 +1,142 @@
+// Copyright 2025 Nicholas Jordan. All Rights Reserved.
+// github.com/cvusmo/lustre
+// tests/terrain.rs
+
+use lustre::block::{BlockRegistry, AIR};
+use lustre::chunk::{Chunk, CHUNK_SIZE};
+use lustre::terrain::{
+    register_lua_api, Biome, DefaultGenerator, GeneratorRegistry, Perlin, TerrainGenerator,
+    TerrainSettings, DEFAULT_GENERATOR,
+};
+use lustre::world::{BlockPos, ChunkPos};
+use mlua::Lua;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+fn same_blocks(a: &Chunk, b: &Chunk) -> bool {
+    (0..CHUNK_SIZE)
+        .all(|y| (0..CHUNK_SIZE).all(|z| (0..CHUNK_SIZE).all(|x| a.get(x, y, z) == b.get(x, y, z))))
+}
+
+#[test]
+fn noise_is_deterministic_per_seed() {
+    let a = Perlin::new(42);
+    let b = Perlin::new(42);
+    let c = Perlin::new(43);
+
+    let samples: Vec<(f64, f64, f64)> = (0..64)
+        .map(|i| (i as f64 * 0.37, i as f64 * -1.13, i as f64 * 0.71))
+        .collect();
+    for &(x, y, z) in &samples {
+        assert_eq!(a.get3(x, y, z), b.get3(x, y, z));
+        assert!(a.get3(x, y, z).abs() <= 1.0);
+        assert!(a.fbm2(x, z, 4).abs() <= 1.0);
+    }
+    assert!(samples
+        .iter()
+        .any(|&(x, y, z)| a.get3(x, y, z) != c.get3(x, y, z)));
+}
+
+#[test]
+fn generator_is_deterministic_per_seed() {
+    let registry = BlockRegistry::with_defaults();
+    let pos = ChunkPos::new(3, -1, -2);
+
+    let first = DefaultGenerator::new(7, TerrainSettings::default()).generate(pos, &registry);
+    let second = DefaultGenerator::new(7, TerrainSettings::default()).generate(pos, &registry);
+    let other = DefaultGenerator::new(8, TerrainSettings::default()).generate(pos, &registry);
+
+    assert!(same_blocks(&first, &second));
+    assert!(!same_blocks(&first, &other));
+}
+
+#[test]
+fn terrain_has_sky_and_bedrock() {
+    let registry = BlockRegistry::with_defaults();
+    let settings = TerrainSettings::default();
+    let bedrock_chunk = settings.bedrock_level.div_euclid(CHUNK_SIZE as i32) - 1;
+    let generator = DefaultGenerator::new(1, settings);
+
+    assert!(generator
+        .generate(ChunkPos::new(0, 8, 0), &registry)
+        .is_empty());
+    let deep = generator.generate(ChunkPos::new(0, bedrock_chunk, 0), &registry);
+    assert!(deep.is_uniform(registry.id_of("lustre:bedrock").unwrap()));
+}
+
+#[test]
+fn surface_matches_column_height() {
+    let registry = BlockRegistry::with_defaults();
+    let generator = DefaultGenerator::new(99, TerrainSettings::default());
+    let height = generator.surface_height(5, 9);
+
+    let chunk_y = height.div_euclid(CHUNK_SIZE as i32);
+    let local_y = height.rem_euclid(CHUNK_SIZE as i32) as usize;
+    let chunk = generator.generate(ChunkPos::new(0, chunk_y, 0), &registry);
+    assert_ne!(chunk.get(5, local_y, 9), AIR);
+    if local_y + 1 < CHUNK_SIZE {
+        let above = chunk.get(5, local_y + 1, 9);
+        assert!(above == AIR || Some(above) == registry.id_of("lustre:water"));
+    }
+}
+
+#[test]
+fn biomes_follow_climate() {
+    assert_eq!(Biome::from_climate(0.1, 0.5), Biome::Tundra);
+    assert_eq!(Biome::from_climate(0.9, 0.1), Biome::Desert);
+    assert_eq!(Biome::from_climate(0.5, 0.9), Biome::Forest);
+    assert_eq!(Biome::from_climate(0.9, 0.5), Biome::Savanna);
+    assert_eq!(Biome::from_climate(0.5, 0.5), Biome::Plains);
+}
+
+#[test]
+fn lua_mods_can_register_generators() {
+    let lua = Lua::new();
+    let generators = Rc::new(RefCell::new(GeneratorRegistry::new()));
+    register_lua_api(&lua, generators.clone()).unwrap();
+    lua.load(
+        r#"
+        terrain.register_generator("test:flat", function(chunk, ctx)
+            if ctx.y < 0 then
+                chunk:fill(ctx.blocks["lustre:stone"])
+            elseif ctx.y == 0 then
+                chunk:set(1, 2, 3, ctx.blocks["lustre:dirt"])
+            end
+        end)
+        "#,
+    )
+    .exec()
+    .unwrap();
+
+    let registry = BlockRegistry::with_defaults();
+    let generators = generators.borrow();
+    assert!(generators.contains(DEFAULT_GENERATOR));
+    let generator = generators.create("test:flat", 5).unwrap();
+
+    let below = generator.generate(ChunkPos::new(0, -1, 0), &registry);
+    assert!(below.is_uniform(registry.id_of("lustre:stone").unwrap()));
+    let ground = generator.generate(ChunkPos::new(0, 0, 0), &registry);
+    assert_eq!(ground.get(1, 2, 3), registry.id_of("lustre:dirt").unwrap());
+    assert_eq!(ground.get(0, 0, 0), AIR);
+}
+
+#[test]
+fn lod_chunks_follow_the_surface() {
+    let registry = BlockRegistry::with_defaults();
+    let generator = DefaultGenerator::new(11, TerrainSettings::default());
+    let pos = ChunkPos::new(1, 0, -1);
+    let full = generator.generate_lod(pos.origin(), 1, &registry).unwrap();
+    assert!(same_blocks(&full, &generator.generate(pos, &registry)));
+
+    // Each coarse block covers 4³ world blocks; the column under the origin is solid up to
+    // about the surface height.
+    let scale = 4;
+    let origin = BlockPos::new(0, -64, 0);
+    let coarse = generator.generate_lod(origin, scale, &registry).unwrap();
+    let top = (generator.surface_height(0, 0) - origin.y).div_euclid(scale) as usize;
+    assert_ne!(coarse.get(0, top, 0), AIR);
+    if top + 1 < CHUNK_SIZE {
+        let above = coarse.get(0, top + 1, 0);
+        assert!(above == AIR || Some(above) == registry.id_of("lustre:water"));
+    }
+}
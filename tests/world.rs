@@ -0,0 +1,186 @@
+// Copyright 2025 Nicholas Jordan. All Rights Reserved.
+// github.com/cvusmo/lustre
+// tests/world.rs
+
+use lustre::block::{BlockProperties, BlockRegistry, BlockRegistryError, AIR};
+use lustre::chunk::{Chunk, PackedArray, CHUNK_SIZE, CHUNK_VOLUME};
+use lustre::mesher::ChunkMesher;
+use lustre::world::{register_lua_api, BlockPos, ChunkPos, World};
+use mlua::{Lua, Table};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+#[test]
+fn packed_array_round_trips_values() {
+    let mut array = PackedArray::new(5, 100);
+    for index in 0..100 {
+        array.set(index, (index % 32) as u32);
+    }
+    for index in 0..100 {
+        assert_eq!(array.get(index), (index % 32) as u32);
+    }
+
+    let widened = array.resized(9);
+    assert_eq!(widened.bits(), 9);
+    assert_eq!(widened.get(37), 5);
+
+    let words = array.words().to_vec();
+    assert_eq!(PackedArray::from_raw(5, 100, words.clone()), Some(array));
+    assert_eq!(PackedArray::from_raw(5, 120, words.clone()), None);
+    assert_eq!(PackedArray::from_raw(33, 100, words), None);
+}
+
+#[test]
+fn uniform_chunk_uses_no_index_storage() {
+    let chunk = Chunk::filled(3);
+    assert!(chunk.indices().words().is_empty());
+    assert!(chunk.is_uniform(3));
+    assert_eq!(chunk.get(5, 6, 7), 3);
+}
+
+#[test]
+fn palette_grows_and_compacts() {
+    let mut chunk = Chunk::new();
+    for block in 1..=20u16 {
+        chunk.set(block as usize, 0, 0, block);
+    }
+    assert_eq!(chunk.palette().len(), 21);
+    assert_eq!(chunk.indices().bits(), 5);
+    assert_eq!(chunk.get(7, 0, 0), 7);
+
+    for x in 1..=20 {
+        chunk.set(x, 0, 0, AIR);
+    }
+    chunk.set(0, 0, 0, 9);
+    chunk.compact();
+    assert_eq!(chunk.palette().len(), 2);
+    assert_eq!(chunk.indices().bits(), 1);
+    assert_eq!(chunk.get(0, 0, 0), 9);
+    assert_eq!(
+        chunk.get(CHUNK_SIZE - 1, CHUNK_SIZE - 1, CHUNK_SIZE - 1),
+        AIR
+    );
+}
+
+#[test]
+fn chunk_from_parts_rejects_out_of_range_indices() {
+    let mut indices = PackedArray::new(2, CHUNK_VOLUME);
+    indices.set(10, 3);
+    assert!(Chunk::from_parts(vec![AIR, 1], indices.clone()).is_none());
+    assert!(Chunk::from_parts(vec![AIR, 1, 2, 4], indices).is_some());
+}
+
+#[test]
+fn world_coordinates_map_to_chunks_including_negatives() {
+    let pos = BlockPos::new(-1, 32, 65);
+    assert_eq!(pos.chunk(), ChunkPos::new(-1, 1, 2));
+    assert_eq!(pos.local(), (CHUNK_SIZE - 1, 0, 1));
+
+    let mut world = World::new(BlockRegistry::with_defaults());
+    let stone = world.registry().id_of("lustre:stone").unwrap();
+    assert_eq!(world.get_block(pos), AIR);
+    assert_eq!(world.set_block(pos, stone), AIR);
+    assert_eq!(world.get_block(pos), stone);
+    assert_eq!(world.chunk_count(), 1);
+}
+
+#[test]
+fn border_edits_dirty_neighbouring_chunks() {
+    let mut world = World::new(BlockRegistry::with_defaults());
+    world.insert_chunk(ChunkPos::new(0, 0, 0), Chunk::new());
+    world.insert_chunk(ChunkPos::new(1, 0, 0), Chunk::new());
+    world.clear_dirty(ChunkPos::new(0, 0, 0));
+    world.clear_dirty(ChunkPos::new(1, 0, 0));
+
+    world.set_block(BlockPos::new(10, 0, 0), 1);
+    assert_eq!(world.dirty_chunks(), vec![ChunkPos::new(0, 0, 0)]);
+
+    world.clear_dirty(ChunkPos::new(0, 0, 0));
+    world.set_block(BlockPos::new(CHUNK_SIZE as i32 - 1, 0, 0), 1);
+    let mut dirty = world.dirty_chunks();
+    dirty.sort();
+    assert_eq!(dirty, vec![ChunkPos::new(0, 0, 0), ChunkPos::new(1, 0, 0)]);
+}
+
+#[test]
+fn registry_rejects_duplicate_names() {
+    let mut registry = BlockRegistry::new();
+    let id = registry
+        .register("test:glass", BlockProperties::default())
+        .unwrap();
+    assert_eq!(registry.id_of("test:glass"), Some(id));
+    assert_eq!(
+        registry.register("test:glass", BlockProperties::default()),
+        Err(BlockRegistryError::DuplicateName("test:glass".to_string()))
+    );
+    assert!(!registry.is_solid(AIR));
+}
+
+#[test]
+fn lua_edits_are_batched_into_one_remesh_per_chunk() {
+    let shared = Rc::new(RefCell::new(World::new(BlockRegistry::with_defaults())));
+    let lua = Lua::new();
+    register_lua_api(&lua, shared.clone()).unwrap();
+
+    let (changed, previous, block): (usize, u16, u16) = lua
+        .load(
+            r#"
+            local region = { min = { x = 3, y = 3, z = 3 }, max = { x = 0, y = 0, z = 0 } }
+            local changed = world.fill(region, "lustre:stone")
+            local previous = world.set_block(1, 1, 1, world.block("lustre:dirt").id)
+            for x = 4, 10 do world.set_block(x, 0, 0, "lustre:sand") end
+            return changed, previous, world.get_block(1, 1, 1)
+            "#,
+        )
+        .eval()
+        .unwrap();
+    let mut world = shared.borrow_mut();
+    let stone = world.registry().id_of("lustre:stone").unwrap();
+    let dirt = world.registry().id_of("lustre:dirt").unwrap();
+    assert_eq!((changed, previous, block), (64, stone, dirt));
+    assert_eq!(world.get_block(BlockPos::new(3, 3, 3)), stone);
+
+    // Every edit landed in one chunk, so it is meshed once.
+    assert_eq!(world.dirty_chunks(), vec![ChunkPos::new(0, 0, 0)]);
+    let mut mesher = ChunkMesher::new(world.registry(), 1);
+    assert_eq!(mesher.schedule_dirty(&mut world), 1);
+}
+
+#[test]
+fn lua_reads_the_block_registry() {
+    let world = World::new(BlockRegistry::with_defaults());
+    let count = world.registry().len();
+    let lua = Lua::new();
+    register_lua_api(&lua, Rc::new(RefCell::new(world))).unwrap();
+
+    let blocks: Vec<Table> = lua.load("return world.blocks()").eval().unwrap();
+    assert_eq!(blocks.len(), count);
+    let water: Table = lua
+        .load("return world.block('lustre:water')")
+        .eval()
+        .unwrap();
+    assert!(water.get::<bool>("fluid").unwrap());
+    assert!(!water.get::<bool>("solid").unwrap());
+    let missing: Option<Table> = lua.load("return world.block(9999)").eval().unwrap();
+    assert!(missing.is_none());
+
+    // Unknown blocks and huge fills are refused.
+    assert!(lua
+        .load("world.set_block(0, 0, 0, 'test:nope')")
+        .exec()
+        .is_err());
+    assert!(lua
+        .load(
+            "world.fill({ min = { x = 0, y = 0, z = 0 }, max = { x = 999, y = 999, z = 999 } }, 1)"
+        )
+        .exec()
+        .is_err());
+    assert!(lua
+        .load(
+            "local huge = { min = { x = -2147483648, y = -2147483648, z = -2147483648 },
+                            max = { x = 2147483647, y = 2147483647, z = 2147483647 } }
+             world.fill(huge, 1)"
+        )
+        .exec()
+        .is_err());
+}